@@ -42,22 +42,58 @@
 //! * `source` (as `source = "{{ foo }}"`): directly sets the template source.
 //!   This can be useful for test cases or short templates. The generated path
 //!   is undefined, which generally makes it impossible to refer to this
-//!   template from other templates. If `source` is specified, `ext` must also
-//!   be specified (see below). Cannot be used together with `path`.
+//!   template from other templates. If `source` is specified, `ext` or `name`
+//!   must also be specified (see below). Cannot be used together with `path`.
 //! * `ext` (as `ext = "txt"`): lets you specify the content type as a file
 //!   extension. This is used to infer an escape mode (see below), and some
 //!   web framework integrations use it to determine the content type.
 //!   Cannot be used together with `path`.
+//! * `name` (as `name = "card.html"`): the virtual path of a `source`
+//!   template, from which its extension is inferred.
+//! * `partial` (as `partial = ("base.html", "...")`): define a further
+//!   template by its virtual path and source, which can be extended, included
+//!   or imported like a template file. Can be given more than once.
+//! * `block` (as `block = "content"`): render only the given block of the
+//!   template, e.g. for partial responses.
+//! * `filters` (as `filters = "crate::filters"`): the module custom filters
+//!   are called from, instead of a `filters` module in scope.
+//! * `include_mode` (as `include_mode = "function"`): generate each included
+//!   template as a method called from its include sites, instead of inlining
+//!   it at each of them (`inline`, the default), which reduces code size.
+//! * `config` (as `config = "../askama.toml"`): read the configuration from
+//!   this file, relative to the crate root, instead of `askama.toml` or the
+//!   file in the `ASKAMA_CONFIG` environment variable.
+//! * `extra` (as `extra = "ctx"`): name a field, such as a
+//!   `HashMap<String, String>`, whose values the template reads as
+//!   `Option<&str>` with `get("key")`.
+//! * `dynamic` (as `dynamic = "data"`): name a `serde_json::Value` field, whose
+//!   attributes and indexes like `data.items[0]` are looked up when rendering
+//!   (requires the `serde-json` feature).
 //! * `print` (as `print = "code"`): enable debugging by printing nothing
 //!   (`none`), the parsed syntax tree (`ast`), the generated code (`code`)
-//!   or `all` for both. The requested data will be printed to stdout at
-//!   compile time.
+//!   or `all` for both. The requested data will be printed to stderr at
+//!   compile time. The generated code is formatted with `rustfmt` (or the
+//!   one the `RUSTFMT` environment variable points to) if it can be run.
 //! * `escape` (as `escape = "none"`): override the template's extension used for
 //!   the purpose of determining the escaper for this template. See the section
 //!   on configuring custom escapers for more information.
 //! * `syntax` (as `syntax = "foo"`): set the syntax name for a parser defined
 //!   in the configuration file. The default syntax , "default",  is the one
 //!   provided by Askama.
+//! * `whitespace` (as `whitespace = "minimize"`): how whitespace around
+//!   tags is handled (`"suppress"`, `"preserve"` or `"minimize"`).
+//! * `trim_blocks` and `lstrip_blocks` (as `trim_blocks = true`): remove
+//!   the first newline after a block tag, and strip the indentation before
+//!   a block tag at the start of a line.
+//! * `delimiters` (as `delimiters = ("<% %>", "<%= %>", "<%# %>")`): set the
+//!   block, expression and comment delimiters for this template only.
+//!   Cannot be used together with `syntax`.
+//! * `mime_type` (as `mime_type = "application/rss+xml"`): override the
+//!   MIME type returned by `Template::mime_type()`, which is otherwise
+//!   derived from the template's extension.
+//! * `async` (as `async = true`): allow `.await` in expressions, and
+//!   generate an `async fn render_async()` method instead of implementing
+//!   `Template`.
 
 #![allow(unused_imports)]
 #![deny(elided_lifetimes_in_paths)]
@@ -70,7 +106,36 @@ use std::fs::{self, DirEntry};
 use std::io;
 use std::path::Path;
 
-pub use askama_escape::{Html, Text};
+pub use crate::escaping::{Attr, Html, Json, Text};
+
+/// The escapers used for template output, e.g. for custom filters that
+/// escape part of their output themselves
+///
+/// Templates escape with the same types, so a filter using them gives the
+/// same result as the template would. Mark the filter's output as `safe` in
+/// the template so it isn't escaped a second time:
+///
+/// ```
+/// use askama::escaping::{escape, Html};
+///
+/// /// Wraps each line in a `<p>` element, escaping its text; use as
+/// /// `{{ comment|paragraphs|safe }}`
+/// fn paragraphs(s: &str) -> askama::Result<String> {
+///     let mut buf = String::new();
+///     for line in s.lines() {
+///         buf.push_str(&format!("<p>{}</p>", escape(line, Html)));
+///     }
+///     Ok(buf)
+/// }
+///
+/// let html = paragraphs("a < b\nb > c").unwrap();
+/// assert_eq!(html, "<p>a &lt; b</p><p>b &gt; c</p>");
+/// ```
+pub mod escaping {
+    pub use askama_escape::{
+        escape, Attr, EscapeWriter, Escaped, Escaper, Html, Json, MarkupDisplay, Text,
+    };
+}
 
 /// Main `Template` trait; implementations are generally derived
 pub trait Template {
@@ -80,24 +145,116 @@ pub trait Template {
         self.render_into(&mut buf)?;
         Ok(buf)
     }
+    /// Renders the template into `Bytes`, e.g. for the body of a response
+    ///
+    /// The output is written into a `BytesMut` buffer directly, which is
+    /// then frozen without copying it. Requires the `bytes` feature.
+    #[cfg(feature = "bytes")]
+    fn render_to_bytes(&self) -> Result<bytes::Bytes> {
+        let mut buf = bytes::BytesMut::with_capacity(self.size_hint());
+        self.render_into(&mut buf)?;
+        Ok(buf.freeze())
+    }
     /// Renders the template to the given `writer` buffer
     fn render_into(&self, writer: &mut dyn std::fmt::Write) -> Result<()>;
+    /// Renders the template as UTF-8 bytes to the given `writer`, e.g. a
+    /// `Vec<u8>` or a buffered `TcpStream`
+    ///
+    /// Each piece of output is written to `writer` as soon as it is rendered,
+    /// without collecting it in a `String` first. An error returned by
+    /// `writer` stops the rendering and is returned as [`Error::Io`].
+    fn write_into(&self, writer: &mut dyn io::Write) -> Result<()> {
+        let mut adapter = IoWriter {
+            inner: writer,
+            error: None,
+        };
+        match (self.render_into(&mut adapter), adapter.error) {
+            (Err(_), Some(err)) => Err(err.into()),
+            (res, _) => res,
+        }
+    }
     /// Helper function to inspect the template's extension
     fn extension(&self) -> Option<&'static str>;
+    /// The MIME type of the rendered template, e.g. for a `Content-Type` header
+    ///
+    /// Derived from the template's extension, or given with the `mime_type`
+    /// attribute; `text/plain; charset=utf-8` if the extension is unknown.
+    fn mime_type(&self) -> &'static str {
+        "text/plain; charset=utf-8"
+    }
     /// Provides an conservative estimate of the expanded length of the rendered template
     fn size_hint(&self) -> usize;
 }
 
+/// Object-safe wrapper trait around [`Template`], to render templates of
+/// different types behind a `Box<dyn DynTemplate>`
+///
+/// Implemented for all types implementing `Template`.
+pub trait DynTemplate {
+    /// Renders the template into a new `String`, see [`Template::render()`]
+    fn dyn_render(&self) -> Result<String>;
+    /// Renders the template to the given `writer` buffer, see
+    /// [`Template::render_into()`]
+    fn dyn_render_into(&self, writer: &mut dyn std::fmt::Write) -> Result<()>;
+    /// Renders the template as UTF-8 bytes to the given `writer`, see
+    /// [`Template::write_into()`]
+    fn dyn_write_into(&self, writer: &mut dyn io::Write) -> Result<()>;
+    /// The MIME type of the rendered template, see [`Template::mime_type()`]
+    fn dyn_mime_type(&self) -> &'static str;
+}
+
+impl<T: Template> DynTemplate for T {
+    fn dyn_render(&self) -> Result<String> {
+        <Self as Template>::render(self)
+    }
+
+    fn dyn_render_into(&self, writer: &mut dyn std::fmt::Write) -> Result<()> {
+        <Self as Template>::render_into(self, writer)
+    }
+
+    fn dyn_write_into(&self, writer: &mut dyn io::Write) -> Result<()> {
+        <Self as Template>::write_into(self, writer)
+    }
+
+    fn dyn_mime_type(&self) -> &'static str {
+        <Self as Template>::mime_type(self)
+    }
+}
+
+/// Forwards the rendered text to an `io::Write`, keeping the `io::Error`
+/// that `fmt::Error` can't carry
+struct IoWriter<'a> {
+    inner: &'a mut dyn io::Write,
+    error: Option<io::Error>,
+}
+
+impl std::fmt::Write for IoWriter<'_> {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|err| {
+            self.error = Some(err);
+            std::fmt::Error
+        })
+    }
+}
+
 pub trait SizedTemplate {
     /// Helper function to inspect the template's extension
     fn extension() -> Option<&'static str>;
+    /// The MIME type of the rendered template, see [`Template::mime_type()`]
+    fn mime_type() -> &'static str {
+        "text/plain; charset=utf-8"
+    }
     /// Provides an conservative estimate of the expanded length of the rendered template
     fn size_hint() -> usize;
+    /// The paths of the fields of the template struct referenced by the
+    /// template, its includes and the blocks it inherits, like `user.name`,
+    /// sorted and without duplicates
+    const TEMPLATE_FIELDS: &'static [&'static str] = &[];
 }
 
 pub use crate::shared::filters;
 pub use crate::shared::helpers;
-pub use crate::shared::{read_config_file, Error, MarkupDisplay, Result};
+pub use crate::shared::{read_config_file, read_config_file_at, Error, MarkupDisplay, Result};
 pub use askama_derive::*;
 
 pub mod mime {
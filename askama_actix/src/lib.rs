@@ -16,10 +16,8 @@ impl<T: askama::Template> TemplateToResponse for T {
             return ErrorInternalServerError("Template parsing error").error_response();
         }
 
-        let ctype =
-            askama::mime::extension_to_mime_type(self.extension().unwrap_or("txt")).to_string();
         HttpResponse::Ok()
-            .content_type(ctype.as_str())
+            .content_type(self.mime_type())
             .body(buffer.freeze())
     }
 }
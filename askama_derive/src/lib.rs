@@ -1,16 +1,19 @@
 #![deny(elided_lifetimes_in_paths)]
 
 use askama_shared::heritage::{Context, Heritage};
-use askama_shared::input::{Print, Source, TemplateInput};
-use askama_shared::parser::{parse, Expr, Node};
+use askama_shared::input::{Print, Source, TemplateArgs, TemplateInput};
+use askama_shared::parser::{parse, parse_with_path, Node};
 use askama_shared::{
-    generator, get_template_source, read_config_file, CompileError, Config, Integrations,
+    generator, read_config_file, read_config_file_at, CompileError, Config, Integrations,
 };
 use proc_macro::TokenStream;
 use proc_macro2::Span;
 
 use std::collections::HashMap;
+use std::env;
+use std::io::Write;
 use std::path::PathBuf;
+use std::process::{Command, Stdio};
 
 #[proc_macro_derive(Template, attributes(template))]
 pub fn derive_template(input: TokenStream) -> TokenStream {
@@ -31,12 +34,20 @@ pub fn derive_template(input: TokenStream) -> TokenStream {
 /// the parse tree and/or generated source according to the `print` key's
 /// value as passed to the `template()` attribute.
 fn build_template(ast: &syn::DeriveInput) -> Result<String, CompileError> {
-    let config_toml = read_config_file()?;
-    let config = Config::new(&config_toml)?;
-    let input = TemplateInput::new(ast, &config)?;
+    let mut template_args = TemplateArgs::new(ast)?;
+    template_args.source_file = proc_macro::Span::call_site().local_file();
+    let config_toml = match &template_args.config {
+        Some(config_path) => read_config_file_at(config_path.as_ref())?,
+        None => read_config_file()?,
+    };
+    let mut config = Config::new(&config_toml)?;
+    for (name, source) in &template_args.partials {
+        config.add_source(name, source.clone());
+    }
+    let input = TemplateInput::new(ast, &config, &template_args)?;
     let source: String = match input.source {
         Source::Source(ref s) => s.clone(),
-        Source::Path(_) => get_template_source(&input.path)?,
+        Source::Path(_) => config.template_source(&input.path)?,
     };
 
     let mut sources = HashMap::new();
@@ -44,12 +55,15 @@ fn build_template(ast: &syn::DeriveInput) -> Result<String, CompileError> {
 
     let mut parsed = HashMap::new();
     for (path, src) in &sources {
-        parsed.insert(path, parse(src, input.syntax)?);
+        parsed.insert(path, parse(src, &input.syntax)?);
     }
 
     let mut contexts = HashMap::new();
     for (path, nodes) in &parsed {
-        contexts.insert(*path, Context::new(input.config, path, nodes)?);
+        contexts.insert(
+            *path,
+            Context::new(input.config, path, &sources[*path], nodes)?,
+        );
     }
 
     let ctx = &contexts[&input.path];
@@ -60,16 +74,35 @@ fn build_template(ast: &syn::DeriveInput) -> Result<String, CompileError> {
     };
 
     if input.print == Print::Ast || input.print == Print::All {
-        eprintln!("{:?}", parsed[&input.path]);
+        eprintln!("{:#?}", parsed[&input.path]);
     }
 
     let code = generator::generate(&input, &contexts, &heritage, INTEGRATIONS)?;
     if input.print == Print::Code || input.print == Print::All {
-        eprintln!("{}", code);
+        eprintln!("{}", rustfmt(&code).unwrap_or_else(|| code.clone()));
     }
     Ok(code)
 }
 
+/// Formats the generated code with `rustfmt` (or the one `RUSTFMT` points
+/// to) for printing, if it can be run
+fn rustfmt(code: &str) -> Option<String> {
+    let rustfmt = env::var_os("RUSTFMT").unwrap_or_else(|| "rustfmt".into());
+    let mut child = Command::new(rustfmt)
+        .args(["--edition", "2018", "--emit", "stdout"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    child.stdin.take()?.write_all(code.as_bytes()).ok()?;
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
 fn find_used_templates(
     input: &TemplateInput<'_>,
     map: &mut HashMap<PathBuf, String>,
@@ -78,27 +111,39 @@ fn find_used_templates(
     let mut dependency_graph = Vec::new();
     let mut check = vec![(input.path.clone(), source)];
     while let Some((path, source)) = check.pop() {
-        for n in parse(&source, input.syntax)? {
+        for n in parse_with_path(&source, &input.syntax, input.file_path(&path))? {
             match n {
-                Node::Extends(Expr::StrLit(extends)) => {
-                    let extends = input.config.find_template(extends, Some(&path))?;
-                    let dependency_path = (path.clone(), extends.clone());
-                    if dependency_graph.contains(&dependency_path) {
-                        return Err(CompileError::String(format!(
-                            "cyclic dependecy in graph {:#?}",
-                            dependency_graph
-                                .iter()
-                                .map(|e| format!("{:#?} --> {:#?}", e.0, e.1))
-                                .collect::<Vec<String>>()
-                        )));
+                Node::Extends(parents) => {
+                    // Render-time branching is only generated for the
+                    // template itself, not for the parents it extends.
+                    if parents.len() > 1 && path != input.path {
+                        return Err(format!(
+                            "conditional extends is only supported in the template of \
+                             the struct itself, not in its parent {}",
+                            path.display()
+                        )
+                        .into());
+                    }
+                    for (extends, _) in parents {
+                        let extends = input.config.find_template(extends, Some(&path))?;
+                        let dependency_path = (path.clone(), extends.clone());
+                        if dependency_graph.contains(&dependency_path) {
+                            return Err(CompileError::String(format!(
+                                "cyclic dependecy in graph {:#?}",
+                                dependency_graph
+                                    .iter()
+                                    .map(|e| format!("{:#?} --> {:#?}", e.0, e.1))
+                                    .collect::<Vec<String>>()
+                            )));
+                        }
+                        dependency_graph.push(dependency_path);
+                        let source = input.config.template_source(&extends)?;
+                        check.push((extends, source));
                     }
-                    dependency_graph.push(dependency_path);
-                    let source = get_template_source(&extends)?;
-                    check.push((extends, source));
                 }
                 Node::Import(_, import, _) => {
                     let import = input.config.find_template(import, Some(&path))?;
-                    let source = get_template_source(&import)?;
+                    let source = input.config.template_source(&import)?;
                     check.push((import, source));
                 }
                 _ => {}
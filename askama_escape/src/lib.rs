@@ -7,6 +7,7 @@ extern crate std;
 use core::fmt::{self, Display, Formatter, Write};
 use core::str;
 
+/// A value that is escaped with `E` when displayed, unless it is marked safe
 pub struct MarkupDisplay<E, T>
 where
     E: Escaper,
@@ -21,6 +22,7 @@ where
     E: Escaper,
     T: Display,
 {
+    /// Wraps a value that is escaped when displayed
     pub fn new_unsafe(value: T, escaper: E) -> Self {
         Self {
             value: DisplayValue::Unsafe(value),
@@ -28,6 +30,8 @@ where
         }
     }
 
+    /// Wraps a value that is displayed as is, e.g. markup that is already
+    /// escaped
     pub fn new_safe(value: T, escaper: E) -> Self {
         Self {
             value: DisplayValue::Safe(value),
@@ -35,6 +39,7 @@ where
         }
     }
 
+    /// Marks the value as safe, so that it is no longer escaped
     pub fn mark_safe(mut self) -> MarkupDisplay<E, T> {
         self.value = match self.value {
             DisplayValue::Unsafe(t) => DisplayValue::Safe(t),
@@ -64,6 +69,7 @@ where
     }
 }
 
+/// Escapes everything written to it with `E` before passing it on to `W`
 pub struct EscapeWriter<'a, E, W> {
     fmt: W,
     escaper: &'a E,
@@ -79,6 +85,13 @@ where
     }
 }
 
+/// Escapes `string` with `escaper` when the result is displayed
+///
+/// ```
+/// use askama_escape::{escape, Html};
+///
+/// assert_eq!(escape("<a href='/'>", Html).to_string(), "&lt;a href=&#x27;/&#x27;&gt;");
+/// ```
 pub fn escape<E>(string: &str, escaper: E) -> Escaped<'_, E>
 where
     E: Escaper,
@@ -86,6 +99,7 @@ where
     Escaped { string, escaper }
 }
 
+/// A string that is escaped when displayed, see [`escape()`]
 pub struct Escaped<'a, E>
 where
     E: Escaper,
@@ -103,6 +117,8 @@ where
     }
 }
 
+/// Escapes `<`, `>`, `&`, `"` and `'` for use in HTML text and quoted
+/// attribute values
 pub struct Html;
 
 macro_rules! escaping_body {
@@ -142,6 +158,47 @@ impl Escaper for Html {
     }
 }
 
+/// Escapes text for use in HTML attribute values, quoted or not
+///
+/// Besides the characters [`Html`] escapes, all other ASCII characters except
+/// letters, digits and `,`, `.`, `-` and `_` are written as character
+/// references, so that the value can't end an unquoted attribute either.
+/// Browsers decode the references before using the value.
+pub struct Attr;
+
+impl Escaper for Attr {
+    fn write_escaped<W>(&self, mut fmt: W, string: &str) -> fmt::Result
+    where
+        W: Write,
+    {
+        let bytes = string.as_bytes();
+        let mut start = 0;
+        for (i, b) in bytes.iter().enumerate() {
+            if b.is_ascii_alphanumeric() || matches!(*b, b',' | b'.' | b'-' | b'_') || !b.is_ascii()
+            {
+                continue;
+            }
+            if start < i {
+                fmt.write_str(unsafe { str::from_utf8_unchecked(&bytes[start..i]) })?;
+            }
+            match *b {
+                b'<' => fmt.write_str("&lt;")?,
+                b'>' => fmt.write_str("&gt;")?,
+                b'&' => fmt.write_str("&amp;")?,
+                b'"' => fmt.write_str("&quot;")?,
+                _ => write!(fmt, "&#x{:02X};", b)?,
+            }
+            start = i + 1;
+        }
+        if start < bytes.len() {
+            fmt.write_str(unsafe { str::from_utf8_unchecked(&bytes[start..]) })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Leaves text as is
 pub struct Text;
 
 impl Escaper for Text {
@@ -153,6 +210,48 @@ impl Escaper for Text {
     }
 }
 
+/// Escapes text for use inside a JSON string literal
+///
+/// Besides the characters JSON requires to be escaped, `<`, `>` and `&` are
+/// written as unicode escapes as well, so the output can't close a
+/// surrounding `<script>` element.
+pub struct Json;
+
+impl Escaper for Json {
+    fn write_escaped<W>(&self, mut fmt: W, string: &str) -> fmt::Result
+    where
+        W: Write,
+    {
+        let bytes = string.as_bytes();
+        let mut start = 0;
+        for (i, b) in bytes.iter().enumerate() {
+            match *b {
+                b'"' => escaping_body!(start, i, fmt, bytes, "\\\""),
+                b'\\' => escaping_body!(start, i, fmt, bytes, "\\\\"),
+                b'\n' => escaping_body!(start, i, fmt, bytes, "\\n"),
+                b'\r' => escaping_body!(start, i, fmt, bytes, "\\r"),
+                b'\t' => escaping_body!(start, i, fmt, bytes, "\\t"),
+                b'<' => escaping_body!(start, i, fmt, bytes, "\\u003c"),
+                b'>' => escaping_body!(start, i, fmt, bytes, "\\u003e"),
+                b'&' => escaping_body!(start, i, fmt, bytes, "\\u0026"),
+                0..=0x1f => {
+                    if start < i {
+                        fmt.write_str(unsafe { str::from_utf8_unchecked(&bytes[start..i]) })?;
+                    }
+                    write!(fmt, "\\u{:04x}", b)?;
+                    start = i + 1;
+                }
+                _ => (),
+            }
+        }
+        if start < bytes.len() {
+            fmt.write_str(unsafe { str::from_utf8_unchecked(&bytes[start..]) })
+        } else {
+            Ok(())
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 enum DisplayValue<T>
 where
@@ -162,7 +261,10 @@ where
     Unsafe(T),
 }
 
+/// Escapes text for an output format, see [`Html`], [`Attr`], [`Json`] and
+/// [`Text`]
 pub trait Escaper {
+    /// Writes `string` to `fmt`, escaping the characters that need it
     fn write_escaped<W>(&self, fmt: W, string: &str) -> fmt::Result
     where
         W: Write;
@@ -183,4 +285,44 @@ mod tests {
         assert_eq!(escape("<foo", Html).to_string(), "&lt;foo");
         assert_eq!(escape("bla&h", Html).to_string(), "bla&amp;h");
     }
+
+    #[test]
+    fn test_escape_attr() {
+        assert_eq!(escape("", Attr).to_string(), "");
+        assert_eq!(
+            escape("plain-text_1.0,2", Attr).to_string(),
+            "plain-text_1.0,2"
+        );
+        assert_eq!(
+            escape("\"><script>", Attr).to_string(),
+            "&quot;&gt;&lt;script&gt;"
+        );
+        assert_eq!(
+            escape("x' onmouseover=alert(1)", Attr).to_string(),
+            "x&#x27;&#x20;onmouseover&#x3D;alert&#x28;1&#x29;"
+        );
+        assert_eq!(
+            escape("a&b`c\td", Attr).to_string(),
+            "a&amp;b&#x60;c&#x09;d"
+        );
+        assert_eq!(
+            escape("h\u{e9}llo \u{1f600}", Attr).to_string(),
+            "h\u{e9}llo&#x20;\u{1f600}"
+        );
+    }
+
+    #[test]
+    fn test_escape_json() {
+        assert_eq!(escape("", Json).to_string(), "");
+        assert_eq!(escape("plain", Json).to_string(), "plain");
+        assert_eq!(
+            escape("say \"hi\"\\\n", Json).to_string(),
+            "say \\\"hi\\\"\\\\\\n"
+        );
+        assert_eq!(
+            escape("</script>&\u{1}", Json).to_string(),
+            "\\u003c/script\\u003e\\u0026\\u0001"
+        );
+        assert_eq!(escape("h\u{e9}llo", Json).to_string(), "h\u{e9}llo");
+    }
 }
@@ -7,7 +7,8 @@ pub type Result<I> = ::std::result::Result<I, Error>;
 /// # Feature Interaction
 ///
 /// If the feature `serde_json` is enabled an
-/// additional error variant `Json` is added.
+/// additional error variant `Json` is added, and
+/// likewise `TimeZone` for the feature `chrono-tz`.
 ///
 /// # Why not `failure`/`error-chain`?
 ///
@@ -27,9 +28,15 @@ pub type Result<I> = ::std::result::Result<I, Error>;
 pub enum Error {
     /// formatting error
     Fmt(fmt::Error),
+
+    /// io error, e.g. when rendering into an `io::Write`
+    Io(std::io::Error),
     RegEx(regex::Error),
     Chrono(chrono::format::ParseError),
 
+    /// invalid UTF-8, e.g. when a filter turns bytes into text
+    Utf8(std::str::Utf8Error),
+
     /// json conversion error
     #[cfg(feature = "serde_json")]
     Json(::serde_json::Error),
@@ -37,18 +44,66 @@ pub enum Error {
     /// yaml conversion error
     #[cfg(feature = "serde_yaml")]
     Yaml(::serde_yaml::Error),
+
+    /// unknown time zone name, given to the `date` filter
+    #[cfg(feature = "chrono-tz")]
+    TimeZone(String),
+
+    /// error raised by a user-defined filter, see [`Error::custom()`]
+    Custom(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    /// error returned by a filter called while rendering `template`
+    Render {
+        /// name of the template the filter is called in
+        template: &'static str,
+        /// name of the failing filter
+        filter: &'static str,
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Wrap an arbitrary error, e.g. for a custom filter to return
+    ///
+    /// ```
+    /// # use askama_shared::Error;
+    /// fn myfilter(s: &str) -> askama_shared::Result<u32> {
+    ///     s.parse::<u32>().map_err(Error::custom)
+    /// }
+    /// ```
+    pub fn custom<E>(err: E) -> Self
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync + 'static>>,
+    {
+        Error::Custom(err.into())
+    }
+
+    /// Wrap the error returned by `filter` in `template`, see [`Error::Render`]
+    pub fn render<E: Into<Error>>(template: &'static str, filter: &'static str, err: E) -> Self {
+        Error::Render {
+            template,
+            filter,
+            source: Box::new(err.into()),
+        }
+    }
 }
 
 impl std::error::Error for Error {
-    fn cause(&self) -> Option<&dyn std::error::Error> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match *self {
             Error::Fmt(ref err) => err.source(),
+            Error::Io(ref err) => err.source(),
             Error::RegEx(ref err) => err.source(),
             Error::Chrono(ref err) => err.source(),
+            Error::Utf8(ref err) => err.source(),
             #[cfg(feature = "serde_json")]
             Error::Json(ref err) => err.source(),
             #[cfg(feature = "serde_yaml")]
             Error::Yaml(ref err) => err.source(),
+            #[cfg(feature = "chrono-tz")]
+            Error::TimeZone(_) => None,
+            Error::Custom(ref err) => Some(err.as_ref()),
+            Error::Render { ref source, .. } => Some(source.as_ref()),
         }
     }
 }
@@ -57,12 +112,26 @@ impl Display for Error {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
             Error::Fmt(ref err) => write!(formatter, "formatting error: {}", err),
+            Error::Io(ref err) => write!(formatter, "io error: {}", err),
             Error::RegEx(ref err) => write!(formatter, "regex error: {}", err),
             Error::Chrono(ref err) => write!(formatter, "chrono parse error: {}", err),
+            Error::Utf8(ref err) => write!(formatter, "utf-8 error: {}", err),
             #[cfg(feature = "serde_json")]
             Error::Json(ref err) => write!(formatter, "json conversion error: {}", err),
             #[cfg(feature = "serde_yaml")]
             Error::Yaml(ref err) => write!(formatter, "yaml conversion error: {}", err),
+            #[cfg(feature = "chrono-tz")]
+            Error::TimeZone(ref name) => write!(formatter, "unknown time zone: {:?}", name),
+            Error::Custom(ref err) => write!(formatter, "custom error: {}", err),
+            Error::Render {
+                template,
+                filter,
+                ref source,
+            } => write!(
+                formatter,
+                "error in filter `{}` of template `{}`: {}",
+                filter, template, source
+            ),
         }
     }
 }
@@ -73,6 +142,12 @@ impl From<fmt::Error> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
 impl From<regex::Error> for Error {
     fn from(err: regex::Error) -> Self {
         Error::RegEx(err)
@@ -85,6 +160,12 @@ impl From<chrono::format::ParseError> for Error {
     }
 }
 
+impl From<std::str::Utf8Error> for Error {
+    fn from(err: std::str::Utf8Error) -> Self {
+        Error::Utf8(err)
+    }
+}
+
 #[cfg(feature = "serde_json")]
 impl From<::serde_json::Error> for Error {
     fn from(err: ::serde_json::Error) -> Self {
@@ -105,4 +186,54 @@ mod tests {
 
     trait AssertSendSyncStatic: Send + Sync + 'static {}
     impl AssertSendSyncStatic for Error {}
+
+    #[test]
+    fn test_io_error() {
+        let err = Error::from(std::io::Error::new(
+            std::io::ErrorKind::BrokenPipe,
+            "connection closed",
+        ));
+        assert!(matches!(err, Error::Io(_)));
+        assert_eq!(err.to_string(), "io error: connection closed");
+    }
+
+    #[test]
+    fn test_utf8_error() {
+        let bytes = vec![b'a', b'b', 0xff];
+        let err = Error::from(std::str::from_utf8(&bytes).unwrap_err());
+        assert!(matches!(err, Error::Utf8(_)));
+        assert_eq!(
+            err.to_string(),
+            "utf-8 error: invalid utf-8 sequence of 1 bytes from index 2"
+        );
+    }
+
+    #[test]
+    fn test_custom_error() {
+        let err = Error::custom("not a number");
+        assert!(matches!(err, Error::Custom(_)));
+        assert_eq!(err.to_string(), "custom error: not a number");
+
+        let err = Error::custom("12a".parse::<u32>().unwrap_err());
+        let source = std::error::Error::source(&err).unwrap();
+        assert_eq!(source.to_string(), "invalid digit found in string");
+    }
+
+    #[test]
+    fn test_render_error() {
+        let err = Error::render("index.html", "parse_port", Error::custom("not a number"));
+        assert_eq!(
+            err.to_string(),
+            "error in filter `parse_port` of template `index.html`: custom error: not a number"
+        );
+
+        let source = std::error::Error::source(&err).unwrap();
+        assert!(matches!(
+            source.downcast_ref::<Error>(),
+            Some(Error::Custom(_))
+        ));
+
+        let err = Error::render("index.html", "indent", std::fmt::Error);
+        assert!(matches!(err, Error::Render { source, .. } if matches!(*source, Error::Fmt(_))));
+    }
 }
@@ -0,0 +1,80 @@
+//! Identifier case conversions, e.g. for generating code (requires `case` feature)
+//!
+//! The input is split into words at spaces, punctuation and case changes, so
+//! it can already be in any convention: `HTTPServer`, `http_server` and
+//! `http-server` all become `HttpServer` with `pascalcase`. A run of capitals
+//! is a single word, except for the last one if it starts the next word.
+//! Digits stay with the word they follow.
+
+use std::fmt;
+
+use heck::{ToKebabCase, ToLowerCamelCase, ToShoutySnakeCase, ToSnakeCase, ToUpperCamelCase};
+
+use crate::error::Result;
+
+/// Converts to `PascalCase` (requires `case` feature)
+pub fn pascalcase<T: fmt::Display>(s: T) -> Result<String> {
+    Ok(s.to_string().to_upper_camel_case())
+}
+
+/// Converts to `camelCase` (requires `case` feature)
+pub fn camelcase<T: fmt::Display>(s: T) -> Result<String> {
+    Ok(s.to_string().to_lower_camel_case())
+}
+
+/// Converts to `snake_case` (requires `case` feature)
+pub fn snakecase<T: fmt::Display>(s: T) -> Result<String> {
+    Ok(s.to_string().to_snake_case())
+}
+
+/// Converts to `kebab-case` (requires `case` feature)
+pub fn kebabcase<T: fmt::Display>(s: T) -> Result<String> {
+    Ok(s.to_string().to_kebab_case())
+}
+
+/// Converts to `SHOUTY_SNAKE_CASE` (requires `case` feature)
+pub fn shoutycase<T: fmt::Display>(s: T) -> Result<String> {
+    Ok(s.to_string().to_shouty_snake_case())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acronyms() {
+        assert_eq!(snakecase("HTTPServer").unwrap(), "http_server");
+        assert_eq!(pascalcase("HTTPServer").unwrap(), "HttpServer");
+        assert_eq!(camelcase("HTTPServer").unwrap(), "httpServer");
+        assert_eq!(kebabcase("parseXMLDocument").unwrap(), "parse-xml-document");
+        assert_eq!(shoutycase("ABC").unwrap(), "ABC");
+        assert_eq!(camelcase("ABC").unwrap(), "abc");
+    }
+
+    #[test]
+    fn test_digits() {
+        assert_eq!(snakecase("version2Name").unwrap(), "version2_name");
+        assert_eq!(snakecase("getHTTP2Response").unwrap(), "get_http2_response");
+        assert_eq!(pascalcase("base64_encode").unwrap(), "Base64Encode");
+        assert_eq!(shoutycase("ipv4Addr").unwrap(), "IPV4_ADDR");
+        assert_eq!(kebabcase("x11").unwrap(), "x11");
+        assert_eq!(snakecase("item_2").unwrap(), "item_2");
+    }
+
+    #[test]
+    fn test_mixed_conventions() {
+        let inputs = [
+            "user ID", "user_id", "user-id", "UserId", "userId", "USER_ID",
+        ];
+        for s in inputs {
+            assert_eq!(pascalcase(s).unwrap(), "UserId");
+            assert_eq!(camelcase(s).unwrap(), "userId");
+            assert_eq!(snakecase(s).unwrap(), "user_id");
+            assert_eq!(kebabcase(s).unwrap(), "user-id");
+            assert_eq!(shoutycase(s).unwrap(), "USER_ID");
+        }
+        assert_eq!(snakecase("__init__").unwrap(), "init");
+        assert_eq!(snakecase("").unwrap(), "");
+        assert_eq!(snakecase("ÉtéÀ").unwrap(), "été_à");
+    }
+}
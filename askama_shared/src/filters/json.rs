@@ -1,6 +1,7 @@
 use crate::error::{Error, Result};
 use askama_escape::{Escaper, MarkupDisplay};
 use serde::Serialize;
+use serde_json::ser::{PrettyFormatter, Serializer};
 
 /// Serialize to JSON (requires `serde_json` feature)
 ///
@@ -15,6 +16,55 @@ pub fn json<E: Escaper, S: Serialize>(e: E, s: S) -> Result<MarkupDisplay<E, Str
     }
 }
 
+/// Serialize to indented JSON (requires `serde_json` feature)
+///
+/// The optional argument sets the indentation width, which defaults to 2 spaces.
+///
+/// ## Errors
+///
+/// Returns `Err(Error::Json)` if `S`'s implementation of `Serialize` fails,
+/// or if `S` contains a map with non-string keys.
+pub fn json_pretty<E: Escaper, S: Serialize>(
+    e: E,
+    s: S,
+    indent: usize,
+) -> Result<MarkupDisplay<E, String>> {
+    let indent = " ".repeat(indent);
+    let mut buf = Vec::new();
+    let mut ser =
+        Serializer::with_formatter(&mut buf, PrettyFormatter::with_indent(indent.as_bytes()));
+    s.serialize(&mut ser)?;
+    // serde_json only ever produces valid UTF-8
+    let s = String::from_utf8(buf).expect("serde_json produced invalid UTF-8");
+    Ok(MarkupDisplay::new_safe(s, e))
+}
+
+/// Serialize to JSON that can be embedded in an HTML `<script>` element
+/// (requires `serde_json` feature)
+///
+/// The output is compact, and `<`, `>` and `&` are replaced by their `\u003c`,
+/// `\u003e` and `\u0026` escapes, which JSON parsers and JavaScript read back
+/// as the same characters, so a value can't close the element with
+/// `</script>` or open a comment with `<!--`.
+///
+/// ## Errors
+///
+/// Returns `Err(Error::Json)` if `S`'s implementation of `Serialize` fails,
+/// or if `S` contains a map with non-string keys.
+pub fn json_script<E: Escaper, S: Serialize>(e: E, s: S) -> Result<MarkupDisplay<E, String>> {
+    let json = serde_json::to_string(&s)?;
+    let mut escaped = String::with_capacity(json.len());
+    for c in json.chars() {
+        match c {
+            '<' => escaped.push_str("\\u003c"),
+            '>' => escaped.push_str("\\u003e"),
+            '&' => escaped.push_str("\\u0026"),
+            c => escaped.push(c),
+        }
+    }
+    Ok(MarkupDisplay::new_safe(escaped, e))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -34,4 +84,43 @@ mod tests {
 ]"#
         );
     }
+
+    #[test]
+    fn test_json_pretty() {
+        assert_eq!(json_pretty(Html, "foo", 2).unwrap().to_string(), r#""foo""#);
+        assert_eq!(
+            json_pretty(Html, vec!["foo", "bar"], 4)
+                .unwrap()
+                .to_string(),
+            r#"[
+    "foo",
+    "bar"
+]"#
+        );
+        assert_eq!(
+            json_pretty(Html, vec![vec![1]], 1).unwrap().to_string(),
+            "[\n [\n  1\n ]\n]"
+        );
+    }
+
+    #[test]
+    fn test_json_script() {
+        assert_eq!(json_script(Html, "foo").unwrap().to_string(), r#""foo""#);
+        assert_eq!(
+            json_script(Html, "</script><script>alert(1)</script>")
+                .unwrap()
+                .to_string(),
+            r#""\u003c/script\u003e\u003cscript\u003ealert(1)\u003c/script\u003e""#
+        );
+        assert_eq!(
+            json_script(Html, vec!["a & b", "<!--"])
+                .unwrap()
+                .to_string(),
+            r#"["a \u0026 b","\u003c!--"]"#
+        );
+
+        let json = json_script(Html, "<'\"&>").unwrap().to_string();
+        let value: String = serde_json::from_str(&json).unwrap();
+        assert_eq!(value, "<'\"&>");
+    }
 }
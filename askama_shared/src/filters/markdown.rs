@@ -0,0 +1,110 @@
+use crate::error::Result;
+use askama_escape::{Escaper, MarkupDisplay};
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag};
+
+/// Render CommonMark to HTML (requires `markdown` feature)
+///
+/// Raw HTML in the input is escaped, and the URLs of links and images are
+/// dropped unless they are relative or use the `http`, `https` or `mailto`
+/// scheme, so the filter can be used for untrusted input; pass `true` as the
+/// optional argument to let both through instead. Tables, footnotes,
+/// strikethrough and task lists are enabled.
+pub fn markdown<E: Escaper, S: AsRef<str>>(
+    e: E,
+    s: S,
+    allow_html: bool,
+) -> Result<MarkupDisplay<E, String>> {
+    let s = s.as_ref();
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS;
+    let parser = Parser::new_ext(s, options).map(|event| match event {
+        Event::Html(html) if !allow_html => Event::Text(html),
+        Event::Start(Tag::Link(kind, url, title)) if !allow_html => {
+            Event::Start(Tag::Link(kind, safe_url(url), title))
+        }
+        Event::Start(Tag::Image(kind, url, title)) if !allow_html => {
+            Event::Start(Tag::Image(kind, safe_url(url), title))
+        }
+        event => event,
+    });
+
+    let mut buf = String::with_capacity(s.len() + s.len() / 2);
+    html::push_html(&mut buf, parser);
+    Ok(MarkupDisplay::new_safe(buf, e))
+}
+
+// Keeps relative URLs and those with an allowed scheme, which is everything
+// before a `:` that isn't preceded by a `/`, `?` or `#`.
+fn safe_url(url: CowStr<'_>) -> CowStr<'_> {
+    let scheme = match url.find([':', '/', '?', '#']) {
+        Some(end) if url[end..].starts_with(':') => &url[..end],
+        _ => return url,
+    };
+    if ["http", "https", "mailto"]
+        .iter()
+        .any(|allowed| scheme.eq_ignore_ascii_case(allowed))
+    {
+        url
+    } else {
+        CowStr::Borrowed("")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use askama_escape::Html;
+
+    #[test]
+    fn test_markdown() {
+        assert_eq!(
+            markdown(Html, "# Hello\n\n*world*", false)
+                .unwrap()
+                .to_string(),
+            "<h1>Hello</h1>\n<p><em>world</em></p>\n"
+        );
+        assert_eq!(
+            markdown(Html, "a <b>tag</b>\n\n<script>x</script>", false)
+                .unwrap()
+                .to_string(),
+            "<p>a &lt;b&gt;tag&lt;/b&gt;</p>\n&lt;script&gt;x&lt;/script&gt;"
+        );
+        assert_eq!(
+            markdown(Html, "a <b>tag</b>", true).unwrap().to_string(),
+            "<p>a <b>tag</b></p>\n"
+        );
+    }
+
+    #[test]
+    fn test_markdown_urls() {
+        assert_eq!(
+            markdown(
+                Html,
+                "[a](https://example.com) [b](/c?d:e) [f](mailto:g@h) [i](jAvAsCrIpT:alert(1))",
+                false
+            )
+            .unwrap()
+            .to_string(),
+            "<p><a href=\"https://example.com\">a</a> <a href=\"/c?d:e\">b</a> \
+             <a href=\"mailto:g@h\">f</a> <a href=\"\">i</a></p>\n"
+        );
+        assert_eq!(
+            markdown(
+                Html,
+                "![x](javascript:alert(1)) <javascript:alert(1)>",
+                false
+            )
+            .unwrap()
+            .to_string(),
+            "<p><img src=\"\" alt=\"x\" /> <a href=\"\">javascript:alert(1)</a></p>\n"
+        );
+        assert_eq!(
+            markdown(Html, "[a](javascript:alert(1))", true)
+                .unwrap()
+                .to_string(),
+            "<p><a href=\"javascript:alert(1)\">a</a></p>\n"
+        );
+    }
+}
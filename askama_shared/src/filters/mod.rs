@@ -5,12 +5,28 @@
 //! For more information, read the [book](https://djc.github.io/askama/filters.html).
 #![allow(clippy::trivially_copy_pass_by_ref)]
 
-use std::fmt;
+use std::borrow::{Borrow, Cow};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+use std::ops::Add;
+use std::sync::OnceLock;
+use std::{fmt, iter};
+
+#[cfg(feature = "case")]
+mod case;
+#[cfg(feature = "case")]
+pub use self::case::{camelcase, kebabcase, pascalcase, shoutycase, snakecase};
 
 #[cfg(feature = "serde_json")]
 mod json;
 #[cfg(feature = "serde_json")]
-pub use self::json::json;
+pub use self::json::{json, json_pretty, json_script};
+
+#[cfg(feature = "markdown")]
+mod markdown;
+#[cfg(feature = "markdown")]
+pub use self::markdown::markdown;
 
 #[cfg(feature = "serde_yaml")]
 mod yaml;
@@ -20,12 +36,18 @@ pub use self::yaml::yaml;
 #[allow(unused_imports)]
 use crate::error::Error::Fmt;
 use askama_escape::{Escaper, MarkupDisplay};
+use chrono::format::{DelayedFormat, Item, Parsed, StrftimeItems};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
 #[cfg(feature = "humansize")]
 use humansize::{file_size_opts, FileSize};
 #[cfg(feature = "num-traits")]
-use num_traits::{cast::NumCast, Signed};
+use num_traits::cast::NumCast;
+#[cfg(feature = "num-traits")]
+use num_traits::Signed;
 #[cfg(feature = "percent-encoding")]
 use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+#[cfg(feature = "rand")]
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
 
 use super::Result;
 
@@ -47,34 +69,107 @@ const URLENCODE_SET: &AsciiSet = &URLENCODE_STRICT_SET.remove(b'/');
 // Askama or should refer to a local `filters` module. It should contain all the
 // filters shipped with Askama, even the optional ones (since optional inclusion
 // in the const vector based on features seems impossible right now).
-pub const BUILT_IN_FILTERS: [&str; 27] = [
+pub const BUILT_IN_FILTERS: [&str; 77] = [
     "abs",
+    "attr",
+    "batch",
+    "bytelength",
     "capitalize",
+    "ceil",
     "center",
+    "count",
+    "date",
+    "default",
+    "dictsort",
     "e",
     "escape",
     "filesizeformat",
+    "first",
+    "floor",
     "fmt",
     "format",
+    "groupby",
+    "highlight",
     "indent",
     "into_f64",
     "into_isize",
     "join",
+    "last",
+    "length",
     "linebreaks",
     "linebreaksbr",
+    "ljust",
     "paragraphbreaks",
+    "pluralize",
+    "replace",
+    "reject",
+    "reverse",
     "lower",
     "lowercase",
+    "map",
+    "matches",
+    "max",
+    "min",
+    "rjust",
+    "round",
     "safe",
+    "select",
+    "slice",
+    "sort",
+    "striptags",
+    "sum",
+    "sum_from",
+    "title",
     "trim",
+    "trim_end",
+    "trim_start",
     "truncate",
+    "truncatewords",
+    "unique",
     "upper",
     "uppercase",
     "urlencode",
     "urlencode_strict",
     "wordcount",
-    "json", // Optional feature; reserve the name anyway
-    "yaml", // Optional feature; reserve the name anyway
+    "wordwrap",
+    "json",          // Optional feature; reserve the name anyway
+    "json_pretty",   // Optional feature; reserve the name anyway
+    "json_script",   // Optional feature; reserve the name anyway
+    "markdown",      // Optional feature; reserve the name anyway
+    "yaml",          // Optional feature; reserve the name anyway
+    "base64",        // Optional feature; reserve the name anyway
+    "base64_decode", // Optional feature; reserve the name anyway
+    "camelcase",     // Optional feature; reserve the name anyway
+    "kebabcase",     // Optional feature; reserve the name anyway
+    "pascalcase",    // Optional feature; reserve the name anyway
+    "shoutycase",    // Optional feature; reserve the name anyway
+    "snakecase",     // Optional feature; reserve the name anyway
+    "random",        // Optional feature; reserve the name anyway
+    "random_seeded", // Optional feature; reserve the name anyway
+    "date_tz",       // Optional feature; reserve the name anyway
+];
+
+// Default values for the trailing arguments of built-in filters that take
+// optional arguments, as Rust code, after the number of required arguments
+// (not counting the filter's input). The code generator appends the defaults
+// for any arguments the template leaves out.
+pub const FILTER_DEFAULT_ARGS: &[(&str, usize, &[&str])] = &[
+    ("base64", 0, &["false"]),
+    ("base64_decode", 0, &["false"]),
+    ("center", 1, &["' '"]),
+    ("date", 0, &["()"]),
+    ("filesizeformat", 0, &["false"]),
+    ("highlight", 1, &["false"]),
+    ("json_pretty", 0, &["2"]),
+    ("ljust", 1, &["' '"]),
+    ("markdown", 0, &["false"]),
+    ("pluralize", 0, &["\"\"", "\"s\""]),
+    ("replace", 2, &["usize::MAX"]),
+    ("rjust", 1, &["' '"]),
+    ("round", 0, &["0"]),
+    ("truncate", 1, &["\"…\"", "false"]),
+    ("truncatewords", 1, &["\"…\""]),
+    ("wordwrap", 1, &["\"\\n\"", "false"]),
 ];
 
 /// Marks a string (or other `Display` type) as safe
@@ -108,9 +203,264 @@ where
 
 #[cfg(feature = "humansize")]
 /// Returns adequate string representation (in KB, ..) of number of bytes
-pub fn filesizeformat<B: FileSize>(b: &B) -> Result<String> {
-    b.file_size(file_size_opts::DECIMAL)
-        .map_err(|_| Fmt(fmt::Error))
+///
+/// Uses decimal (1000-based) units by default; pass `true` to use binary
+/// (1024-based, KiB, ..) units instead. Negative values are prefixed with `-`.
+pub fn filesizeformat<B: ByteCount>(b: B, binary: bool) -> Result<String> {
+    let opts = match binary {
+        true => file_size_opts::BINARY,
+        false => file_size_opts::DECIMAL,
+    };
+    let (negative, bytes) = b.sign_and_magnitude();
+    let size = bytes.file_size(opts).map_err(|_| Fmt(fmt::Error))?;
+    Ok(match negative {
+        true => format!("-{}", size),
+        false => size,
+    })
+}
+
+#[cfg(feature = "humansize")]
+/// Numbers which can be formatted by the [`filesizeformat`] filter
+pub trait ByteCount {
+    /// Returns whether the value is negative, and its magnitude in bytes
+    ///
+    /// Magnitudes that don't fit in a `u64` saturate.
+    fn sign_and_magnitude(&self) -> (bool, u64);
+}
+
+#[cfg(feature = "humansize")]
+impl<T: ByteCount + ?Sized> ByteCount for &T {
+    fn sign_and_magnitude(&self) -> (bool, u64) {
+        T::sign_and_magnitude(self)
+    }
+}
+
+macro_rules! impl_byte_count {
+    (unsigned: $($t:ty)*) => ($(
+        #[cfg(feature = "humansize")]
+        impl ByteCount for $t {
+            fn sign_and_magnitude(&self) -> (bool, u64) {
+                (false, *self as u64)
+            }
+        }
+    )*);
+    (signed: $($t:ty)*) => ($(
+        #[cfg(feature = "humansize")]
+        impl ByteCount for $t {
+            fn sign_and_magnitude(&self) -> (bool, u64) {
+                (*self < 0, self.unsigned_abs() as u64)
+            }
+        }
+    )*);
+    (float: $($t:ty)*) => ($(
+        #[cfg(feature = "humansize")]
+        impl ByteCount for $t {
+            fn sign_and_magnitude(&self) -> (bool, u64) {
+                // `as` saturates for out of range values and maps NaN to 0
+                (self.is_sign_negative() && *self != 0.0, self.abs() as u64)
+            }
+        }
+    )*);
+}
+
+impl_byte_count!(unsigned: u8 u16 u32 u64 u128 usize);
+impl_byte_count!(signed: i8 i16 i32 i64 i128 isize);
+impl_byte_count!(float: f32 f64);
+
+/// Formats a date and/or time with a strftime-like pattern
+///
+/// Without a pattern, the value is formatted as ISO 8601. See
+/// [`chrono::format::strftime`] for the supported specifiers; an invalid
+/// pattern results in an [`Error::Chrono`](crate::Error::Chrono).
+pub fn date<D: DateFormat, P: DatePattern>(d: D, pattern: P) -> Result<String> {
+    let pattern = pattern.pattern().unwrap_or(D::ISO_8601);
+    let items = StrftimeItems::new(pattern).collect::<Vec<_>>();
+    if items.contains(&Item::Error) {
+        // chrono's `ParseError` can't be constructed directly, but parsing
+        // with an invalid item yields the right kind of error.
+        let err = chrono::format::parse(&mut Parsed::new(), "", iter::once(Item::Error));
+        return Err(err.unwrap_err().into());
+    }
+    Ok(d.format_items(items.iter()).to_string())
+}
+
+#[cfg(feature = "chrono-tz")]
+/// Formats a date and time in the time zone named `tz`, like [`date()`]
+///
+/// The zone is one of the IANA time zone database, such as
+/// `"America/New_York"`; an unknown one results in an
+/// [`Error::TimeZone`](crate::Error::TimeZone).
+pub fn date_tz<D: DateTimeZone, P: DatePattern>(d: D, pattern: P, tz: &str) -> Result<String> {
+    let tz: chrono_tz::Tz = tz
+        .parse()
+        .map_err(|_| crate::Error::TimeZone(tz.to_string()))?;
+    date(d.with_time_zone(&tz), pattern)
+}
+
+#[cfg(feature = "chrono-tz")]
+/// Date and time types which the [`date_tz`] filter can convert to a time zone
+pub trait DateTimeZone {
+    fn with_time_zone(&self, tz: &chrono_tz::Tz) -> DateTime<chrono_tz::Tz>;
+}
+
+#[cfg(feature = "chrono-tz")]
+impl<T: DateTimeZone + ?Sized> DateTimeZone for &T {
+    fn with_time_zone(&self, tz: &chrono_tz::Tz) -> DateTime<chrono_tz::Tz> {
+        T::with_time_zone(self, tz)
+    }
+}
+
+#[cfg(feature = "chrono-tz")]
+impl<Tz: TimeZone> DateTimeZone for DateTime<Tz> {
+    fn with_time_zone(&self, tz: &chrono_tz::Tz) -> DateTime<chrono_tz::Tz> {
+        self.with_timezone(tz)
+    }
+}
+
+/// Date and time types which can be formatted by the [`date`] filter
+pub trait DateFormat {
+    /// The pattern used if the template doesn't specify one
+    const ISO_8601: &'static str;
+
+    fn format_items<'a, 'b>(
+        &self,
+        items: std::slice::Iter<'b, Item<'a>>,
+    ) -> DelayedFormat<std::slice::Iter<'b, Item<'a>>>;
+}
+
+impl<T: DateFormat + ?Sized> DateFormat for &T {
+    const ISO_8601: &'static str = T::ISO_8601;
+
+    fn format_items<'a, 'b>(
+        &self,
+        items: std::slice::Iter<'b, Item<'a>>,
+    ) -> DelayedFormat<std::slice::Iter<'b, Item<'a>>> {
+        T::format_items(self, items)
+    }
+}
+
+impl DateFormat for NaiveDate {
+    const ISO_8601: &'static str = "%Y-%m-%d";
+
+    fn format_items<'a, 'b>(
+        &self,
+        items: std::slice::Iter<'b, Item<'a>>,
+    ) -> DelayedFormat<std::slice::Iter<'b, Item<'a>>> {
+        self.format_with_items(items)
+    }
+}
+
+impl DateFormat for NaiveTime {
+    const ISO_8601: &'static str = "%H:%M:%S%.f";
+
+    fn format_items<'a, 'b>(
+        &self,
+        items: std::slice::Iter<'b, Item<'a>>,
+    ) -> DelayedFormat<std::slice::Iter<'b, Item<'a>>> {
+        self.format_with_items(items)
+    }
+}
+
+impl DateFormat for NaiveDateTime {
+    const ISO_8601: &'static str = "%Y-%m-%dT%H:%M:%S%.f";
+
+    fn format_items<'a, 'b>(
+        &self,
+        items: std::slice::Iter<'b, Item<'a>>,
+    ) -> DelayedFormat<std::slice::Iter<'b, Item<'a>>> {
+        self.format_with_items(items)
+    }
+}
+
+impl<Tz: TimeZone> DateFormat for DateTime<Tz>
+where
+    Tz::Offset: fmt::Display,
+{
+    const ISO_8601: &'static str = "%Y-%m-%dT%H:%M:%S%.f%:z";
+
+    fn format_items<'a, 'b>(
+        &self,
+        items: std::slice::Iter<'b, Item<'a>>,
+    ) -> DelayedFormat<std::slice::Iter<'b, Item<'a>>> {
+        self.format_with_items(items)
+    }
+}
+
+/// The pattern argument of the [`date`] filter
+///
+/// `()` stands for the default pattern, which is what the code generator
+/// passes if the template leaves out the argument.
+pub trait DatePattern {
+    fn pattern(&self) -> Option<&str>;
+}
+
+impl<T: DatePattern + ?Sized> DatePattern for &T {
+    fn pattern(&self) -> Option<&str> {
+        T::pattern(self)
+    }
+}
+
+impl DatePattern for str {
+    fn pattern(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl DatePattern for String {
+    fn pattern(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl DatePattern for () {
+    fn pattern(&self) -> Option<&str> {
+        None
+    }
+}
+
+#[cfg(feature = "base64")]
+/// Encodes bytes or a string as base64
+///
+/// Takes anything that can be viewed as bytes, like `&[u8]`, `Vec<u8>` or a
+/// string. With `urlsafe` set, the URL and filename safe alphabet is used,
+/// with `-` and `_` instead of `+` and `/`.
+///
+/// ```none,ignore
+/// <img src="data:image/png;base64,{{ png|base64 }}">
+/// <a href="/reset?token={{ token|base64(true) }}">Reset</a>
+/// ```
+///
+/// The output is marked as safe, since it can't contain any characters that
+/// would need escaping.
+pub fn base64<E: Escaper, T: AsRef<[u8]>>(
+    e: E,
+    input: T,
+    urlsafe: bool,
+) -> Result<MarkupDisplay<E, String>> {
+    let config = match urlsafe {
+        true => ::base64::URL_SAFE,
+        false => ::base64::STANDARD,
+    };
+    Ok(MarkupDisplay::new_safe(
+        ::base64::encode_config(input, config),
+        e,
+    ))
+}
+
+#[cfg(feature = "base64")]
+/// Decodes base64 into a string, the reverse of [`base64()`]
+///
+/// With `urlsafe` set, the input is expected to use the URL and filename safe
+/// alphabet. Fails with [`Error::Custom`](crate::Error::Custom) if the input
+/// isn't valid base64, and with [`Error::Utf8`](crate::Error::Utf8) if the
+/// decoded bytes aren't valid UTF-8.
+pub fn base64_decode<T: AsRef<[u8]>>(input: T, urlsafe: bool) -> Result<String> {
+    let config = match urlsafe {
+        true => ::base64::URL_SAFE,
+        false => ::base64::STANDARD,
+    };
+    let bytes = ::base64::decode_config(input, config).map_err(crate::Error::custom)?;
+    String::from_utf8(bytes).map_err(|err| err.utf8_error().into())
 }
 
 #[cfg(feature = "percent-encoding")]
@@ -132,10 +482,14 @@ pub fn filesizeformat<B: FileSize>(b: &B) -> Result<String> {
 ///
 /// To encode `/` as well, see [`urlencode_strict`](./fn.urlencode_strict.html).
 ///
+/// The output is marked as safe, since it can't contain any characters that
+/// would need escaping.
+///
 /// [`urlencode_strict`]: ./fn.urlencode_strict.html
-pub fn urlencode<T: fmt::Display>(s: T) -> Result<String> {
+pub fn urlencode<E: Escaper, T: fmt::Display>(e: E, s: T) -> Result<MarkupDisplay<E, String>> {
     let s = s.to_string();
-    Ok(utf8_percent_encode(&s, URLENCODE_SET).to_string())
+    let s = utf8_percent_encode(&s, URLENCODE_SET).to_string();
+    Ok(MarkupDisplay::new_safe(s, e))
 }
 
 #[cfg(feature = "percent-encoding")]
@@ -153,9 +507,13 @@ pub fn urlencode<T: fmt::Display>(s: T) -> Result<String> {
 /// ```
 ///
 /// If you want to preserve `/`, see [`urlencode`](./fn.urlencode.html).
-pub fn urlencode_strict<T: fmt::Display>(s: T) -> Result<String> {
+pub fn urlencode_strict<E: Escaper, T: fmt::Display>(
+    e: E,
+    s: T,
+) -> Result<MarkupDisplay<E, String>> {
     let s = s.to_string();
-    Ok(utf8_percent_encode(&s, URLENCODE_STRICT_SET).to_string())
+    let s = utf8_percent_encode(&s, URLENCODE_STRICT_SET).to_string();
+    Ok(MarkupDisplay::new_safe(s, e))
 }
 
 /// Formats arguments according to the specified format
@@ -191,29 +549,127 @@ pub fn format() {}
 ///
 /// A single newline becomes an HTML line break `<br>` and a new line
 /// followed by a blank line becomes a paragraph break `<p>`.
-pub fn linebreaks<T: fmt::Display>(s: T) -> Result<String> {
-    let s = s.to_string();
-    let linebroken = s.replace("\n\n", "</p><p>").replace("\n", "<br/>");
-
-    Ok(format!("<p>{}</p>", linebroken))
+///
+/// The text is escaped before the tags are inserted, and the output is
+/// marked as safe, so this filter can be used on untrusted input. Askama
+/// will automatically insert the first (`Escaper`) argument.
+pub fn linebreaks<E: Escaper, T: fmt::Display>(e: E, s: T) -> Result<MarkupDisplay<E, String>> {
+    let s = escape_text(&e, s)?;
+    let linebroken = s.replace("\n\n", "</p><p>").replace('\n', "<br/>");
+
+    Ok(MarkupDisplay::new_safe(format!("<p>{}</p>", linebroken), e))
 }
 
 /// Converts all newlines in a piece of plain text to HTML line breaks
-pub fn linebreaksbr<T: fmt::Display>(s: T) -> Result<String> {
+///
+/// Like [`linebreaks()`], the text is escaped and the output marked as safe.
+pub fn linebreaksbr<E: Escaper, T: fmt::Display>(e: E, s: T) -> Result<MarkupDisplay<E, String>> {
+    let s = escape_text(&e, s)?;
+    Ok(MarkupDisplay::new_safe(s.replace('\n', "<br/>"), e))
+}
+
+/// Wraps the case-insensitive matches of `query` in `<mark>` tags
+///
+/// The query is matched literally, unless `regex` is set. Matches are found
+/// in the text before it is escaped, so a query can't match inside an
+/// escaped character; the text and the matches are escaped, and the output
+/// marked as safe. An empty query, or a pattern only matching empty strings,
+/// marks nothing.
+pub fn highlight<E: Escaper, T: fmt::Display, Q: fmt::Display>(
+    e: E,
+    s: T,
+    query: Q,
+    regex: bool,
+) -> Result<MarkupDisplay<E, String>> {
     let s = s.to_string();
-    Ok(s.replace("\n", "<br/>"))
+    let query = query.to_string();
+    if query.is_empty() {
+        return Ok(MarkupDisplay::new_safe(escape_text(&e, s)?, e));
+    }
+
+    let pattern = match regex {
+        true => query,
+        false => regex::escape(&query),
+    };
+    let re = regex::RegexBuilder::new(&pattern)
+        .case_insensitive(true)
+        .build()?;
+
+    let mut highlighted = String::with_capacity(s.len());
+    let mut last = 0;
+    for m in re.find_iter(&s).filter(|m| !m.as_str().is_empty()) {
+        e.write_escaped(&mut highlighted, &s[last..m.start()])?;
+        highlighted.push_str("<mark>");
+        e.write_escaped(&mut highlighted, m.as_str())?;
+        highlighted.push_str("</mark>");
+        last = m.end();
+    }
+    e.write_escaped(&mut highlighted, &s[last..])?;
+    Ok(MarkupDisplay::new_safe(highlighted, e))
+}
+
+/// Checks whether the regular expression `pattern` matches somewhere in the
+/// text
+///
+/// The pattern is compiled on every call; a literal pattern in a template
+/// is compiled once instead, see [`StaticRegex`].
+pub fn matches<T: fmt::Display, P: fmt::Display>(s: T, pattern: P) -> Result<bool> {
+    let re = regex::Regex::new(&pattern.to_string())?;
+    Ok(re.is_match(&s.to_string()))
+}
+
+/// Checks whether a [`StaticRegex`] matches somewhere in the text, like
+/// [`matches()`]
+pub fn matches_static<T: fmt::Display>(s: T, regex: &StaticRegex) -> Result<bool> {
+    Ok(regex.get()?.is_match(&s.to_string()))
+}
+
+/// A regular expression compiled on first use
+///
+/// The code generator keeps one in a `static` for each `matches` filter with
+/// a literal pattern, so the pattern is compiled once per program rather than
+/// on every render.
+pub struct StaticRegex {
+    pattern: &'static str,
+    regex: OnceLock<std::result::Result<regex::Regex, regex::Error>>,
+}
+
+impl StaticRegex {
+    pub const fn new(pattern: &'static str) -> Self {
+        Self {
+            pattern,
+            regex: OnceLock::new(),
+        }
+    }
+
+    /// Gets the compiled regular expression, compiling it on the first call
+    pub fn get(&self) -> Result<&regex::Regex> {
+        let regex = self.regex.get_or_init(|| regex::Regex::new(self.pattern));
+        regex.as_ref().map_err(|err| err.clone().into())
+    }
 }
 
 /// Replaces only paragraph breaks in plain text with appropriate HTML
 ///
 /// A new line followed by a blank line becomes a paragraph break `<p>`.
 /// Paragraph tags only wrap content; empty paragraphs are removed.
-/// No `<br/>` tags are added.
-pub fn paragraphbreaks<T: fmt::Display>(s: T) -> Result<String> {
-    let s = s.to_string();
+/// No `<br/>` tags are added. Like [`linebreaks()`], the text is escaped and
+/// the output marked as safe.
+pub fn paragraphbreaks<E: Escaper, T: fmt::Display>(
+    e: E,
+    s: T,
+) -> Result<MarkupDisplay<E, String>> {
+    let s = escape_text(&e, s)?;
     let linebroken = s.replace("\n\n", "</p><p>").replace("<p></p>", "");
 
-    Ok(format!("<p>{}</p>", linebroken))
+    Ok(MarkupDisplay::new_safe(format!("<p>{}</p>", linebroken), e))
+}
+
+fn escape_text<E: Escaper, T: fmt::Display>(e: &E, s: T) -> Result<String> {
+    let s = s.to_string();
+    let mut escaped = String::with_capacity(s.len());
+    e.write_escaped(&mut escaped, &s)?;
+    Ok(escaped)
 }
 
 /// Converts to lowercase
@@ -227,6 +683,28 @@ pub fn lowercase<T: fmt::Display>(s: T) -> Result<String> {
     lower(s)
 }
 
+/// Capitalize every word: its first character will be uppercase, all others lowercase
+///
+/// As in Jinja, words are separated by whitespace, `-`, `(`, `{`, `[` and `<`.
+/// Their first character is converted to title case, like by [`capitalize()`].
+pub fn title<T: fmt::Display>(s: T) -> Result<String> {
+    let is_separator = |c: char| c.is_whitespace() || matches!(c, '-' | '(' | '{' | '[' | '<');
+
+    let s = s.to_string();
+    let mut titled = String::with_capacity(s.len());
+    let mut rest = s.as_str();
+    while !rest.is_empty() {
+        let start = rest.find(|c| !is_separator(c)).unwrap_or(rest.len());
+        titled.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find(is_separator)
+            .map_or(rest.len(), |i| start + i);
+        push_capitalized(&mut titled, &rest[start..end]);
+        rest = &rest[end..];
+    }
+    Ok(titled)
+}
+
 /// Converts to uppercase
 pub fn upper<T: fmt::Display>(s: T) -> Result<String> {
     let s = s.to_string();
@@ -238,40 +716,263 @@ pub fn uppercase<T: fmt::Display>(s: T) -> Result<String> {
     upper(s)
 }
 
-/// Strip leading and trailing whitespace
-pub fn trim<T: fmt::Display>(s: T) -> Result<String> {
-    let s = s.to_string();
-    Ok(s.trim().to_owned())
+/// Strip leading and trailing whitespace, or the given characters
+///
+/// The input is passed through [`TrimSource`], so a string is trimmed without
+/// allocating, and any other value is formatted first.
+pub fn trim<'a, C: TrimChars>(s: Cow<'a, str>, chars: C) -> Result<Cow<'a, str>> {
+    Ok(trim_cow(s, |s| s.trim_matches(|c| chars.is_trimmed(c))))
+}
+
+/// Strip leading whitespace, or the given characters
+pub fn trim_start<'a, C: TrimChars>(s: Cow<'a, str>, chars: C) -> Result<Cow<'a, str>> {
+    Ok(trim_cow(s, |s| {
+        s.trim_start_matches(|c| chars.is_trimmed(c))
+    }))
+}
+
+/// Strip trailing whitespace, or the given characters
+pub fn trim_end<'a, C: TrimChars>(s: Cow<'a, str>, chars: C) -> Result<Cow<'a, str>> {
+    Ok(trim_cow(s, |s| s.trim_end_matches(|c| chars.is_trimmed(c))))
+}
+
+fn trim_cow<'a>(s: Cow<'a, str>, trim: impl Fn(&str) -> &str) -> Cow<'a, str> {
+    match s {
+        Cow::Borrowed(s) => Cow::Borrowed(trim(s)),
+        Cow::Owned(s) if trim(&s).len() == s.len() => Cow::Owned(s),
+        Cow::Owned(s) => Cow::Owned(trim(&s).to_owned()),
+    }
+}
+
+/// The input of the [`trim`], [`trim_start`] and [`trim_end`] filters
+///
+/// `TrimSource(&value).trim_source()` borrows `value` if it is a string,
+/// through [`TrimStr`], and formats it otherwise, through [`TrimDisplay`].
+/// Both traits must be in scope.
+pub struct TrimSource<'a, T: ?Sized>(pub &'a T);
+
+pub trait TrimStr<'a> {
+    fn trim_source(self) -> Cow<'a, str>;
+}
+
+impl<'a, T: AsRef<str> + ?Sized> TrimStr<'a> for TrimSource<'a, T> {
+    fn trim_source(self) -> Cow<'a, str> {
+        Cow::Borrowed(self.0.as_ref())
+    }
+}
+
+pub trait TrimDisplay<'a> {
+    fn trim_source(self) -> Cow<'a, str>;
+}
+
+impl<'a, T: fmt::Display + ?Sized> TrimDisplay<'a> for &TrimSource<'a, T> {
+    fn trim_source(self) -> Cow<'a, str> {
+        Cow::Owned(self.0.to_string())
+    }
+}
+
+/// The characters stripped by the [`trim`], [`trim_start`] and [`trim_end`]
+/// filters: whitespace for `()`, or any of the characters of a string
+pub trait TrimChars {
+    fn is_trimmed(&self, c: char) -> bool;
 }
 
-/// Limit string length, appends '...' if truncated
-pub fn truncate<T: fmt::Display>(s: T, len: usize) -> Result<String> {
+impl<T: TrimChars + ?Sized> TrimChars for &T {
+    fn is_trimmed(&self, c: char) -> bool {
+        T::is_trimmed(self, c)
+    }
+}
+
+impl TrimChars for str {
+    fn is_trimmed(&self, c: char) -> bool {
+        self.contains(c)
+    }
+}
+
+impl TrimChars for String {
+    fn is_trimmed(&self, c: char) -> bool {
+        self.contains(c)
+    }
+}
+
+impl TrimChars for char {
+    fn is_trimmed(&self, c: char) -> bool {
+        *self == c
+    }
+}
+
+impl TrimChars for () {
+    fn is_trimmed(&self, c: char) -> bool {
+        c.is_whitespace()
+    }
+}
+
+/// Limit string length to `len` characters, appends `ellipsis` if truncated
+///
+/// The ellipsis defaults to `…`. If `at_word` is `true`, the string is cut
+/// at the last whitespace within the limit, or right after it, instead of in
+/// the middle of a word.
+pub fn truncate<T, E>(s: T, len: usize, ellipsis: E, at_word: bool) -> Result<String>
+where
+    T: fmt::Display,
+    E: AsRef<str>,
+{
     let mut s = s.to_string();
-    if s.len() > len {
-        let mut real_len = len;
-        while !s.is_char_boundary(real_len) {
-            real_len += 1;
+    if let Some((mut end, _)) = s.char_indices().nth(len) {
+        // The limit is at the end of a word already if whitespace follows it.
+        if at_word && !s[end..].starts_with(char::is_whitespace) {
+            if let Some((pos, _)) = s[..end]
+                .char_indices()
+                .rev()
+                .find(|(_, c)| c.is_whitespace())
+            {
+                end = pos;
+            }
         }
-        s.truncate(real_len);
-        s.push_str("...");
+        if at_word {
+            end = s[..end].trim_end().len();
+        }
+        s.truncate(end);
+        s.push_str(ellipsis.as_ref());
     }
     Ok(s)
 }
 
-/// Indent lines with `width` spaces
-pub fn indent<T: fmt::Display>(s: T, width: usize) -> Result<String> {
+/// Limit a string to its first `count` words, appends `ellipsis` if truncated
+///
+/// The ellipsis defaults to `…`. Words are separated by any whitespace, which
+/// is collapsed into single spaces in the result, whether it's truncated or
+/// not.
+pub fn truncatewords<T, E>(s: T, count: usize, ellipsis: E) -> Result<String>
+where
+    T: fmt::Display,
+    E: AsRef<str>,
+{
+    let s = s.to_string();
+    let mut words = s.split_whitespace();
+    let mut truncated = words.by_ref().take(count).collect::<Vec<_>>().join(" ");
+    if words.next().is_some() {
+        truncated.push_str(ellipsis.as_ref());
+    }
+    Ok(truncated)
+}
+
+/// Replace occurrences of `from` with `to`, at most `count` of them
+///
+/// All occurrences are replaced unless `count` is given. Like other filter
+/// output, the result is escaped, including any markup in `to`.
+pub fn replace<T, F, R>(s: T, from: F, to: R, count: usize) -> Result<String>
+where
+    T: fmt::Display,
+    F: AsRef<str>,
+    R: AsRef<str>,
+{
+    Ok(s.to_string().replacen(from.as_ref(), to.as_ref(), count))
+}
+
+/// Remove HTML tags and comments, and collapse whitespace
+///
+/// The text left is plain text: runs of whitespace are replaced by a single
+/// space, and the entities `&amp;`, `&lt;`, `&gt;`, `&quot;`, `&apos;`,
+/// `&nbsp;` and numeric character references are decoded, while other
+/// entities are kept as they are. Like in HTML, a `<` starts a tag only if
+/// it is followed by a letter, `/`, `!` or `?`; otherwise, or if the tag
+/// isn't closed, it is kept as text along with the rest of the string.
+pub fn striptags<T: fmt::Display>(s: T) -> Result<String> {
     let s = s.to_string();
+    let mut text = String::with_capacity(s.len());
+    let mut rest = s.as_str();
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest[1..].chars().next() {
+            _ if rest.starts_with("<!--") => rest.find("-->").map(|end| end + 3),
+            Some(c) if c.is_ascii_alphabetic() || matches!(c, '/' | '!' | '?') => {
+                rest.find('>').map(|end| end + 1)
+            }
+            _ => None,
+        };
+        match end {
+            Some(end) => rest = &rest[end..],
+            None => {
+                text.push('<');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    Ok(decode_entities(&text))
+}
+
+// Decodes the entities `striptags()` leaves as plain text
+fn decode_entities(s: &str) -> String {
+    let mut decoded = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..].find(';').map(|end| &rest[1..end + 1]);
+        let c = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            _ => {
+                let code = entity.strip_prefix('#')?;
+                let code = match code.strip_prefix(|c| c == 'x' || c == 'X') {
+                    Some(hex) => u32::from_str_radix(hex, 16),
+                    None => code.parse(),
+                };
+                char::from_u32(code.ok()?)
+            }
+        });
+        match (entity, c) {
+            (Some(entity), Some(c)) => {
+                decoded.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
 
-    let mut indented = String::new();
+/// Indent lines with `width` spaces
+///
+/// The first line and blank lines are left as they are, see [`indent_lines()`].
+pub fn indent<T: fmt::Display>(s: T, width: usize) -> Result<String> {
+    indent_lines(s, width, false, false)
+}
 
-    for (i, c) in s.char_indices() {
-        indented.push(c);
+/// Indent lines with `width` spaces, optionally including the first line and
+/// blank lines
+///
+/// Line endings, `\n` or `\r\n`, are kept as they are.
+pub fn indent_lines<T: fmt::Display>(
+    s: T,
+    width: usize,
+    first: bool,
+    blank: bool,
+) -> Result<String> {
+    let s = s.to_string();
 
-        if c == '\n' && i < s.len() - 1 {
+    let mut indented = String::with_capacity(s.len());
+    for (i, line) in s.split_inclusive('\n').enumerate() {
+        let is_blank = line.trim_end_matches(&['\r', '\n'][..]).is_empty();
+        if (i > 0 || first) && (blank || !is_blank) {
             for _ in 0..width {
                 indented.push(' ');
             }
         }
+        indented.push_str(line);
     }
 
     Ok(indented)
@@ -296,11 +997,36 @@ where
 }
 
 /// Joins iterable into a string separated by provided argument
-pub fn join<T, I, S>(input: I, separator: S) -> Result<String>
+///
+/// Each item is escaped with the template's escaper, while the separator is
+/// taken as is, so the result is marked as safe. Askama will automatically
+/// insert the first (`Escaper`) argument.
+pub fn join<E, T, I, S>(e: E, input: I, separator: S) -> Result<MarkupDisplay<E, String>>
 where
+    E: Escaper,
     T: fmt::Display,
     I: Iterator<Item = T>,
     S: AsRef<str>,
+{
+    join_by(e, input, separator, |item| item)
+}
+
+/// Like [`join()`], but joins the value `key` returns for each item
+///
+/// In templates, the key is given as the name of a field of the items, e.g.
+/// `users|join(", ", "name")`.
+pub fn join_by<E, T, I, S, F>(
+    e: E,
+    input: I,
+    separator: S,
+    key: F,
+) -> Result<MarkupDisplay<E, String>>
+where
+    E: Escaper,
+    T: fmt::Display + ?Sized,
+    I: Iterator,
+    S: AsRef<str>,
+    F: Fn(&I::Item) -> &T,
 {
     let separator: &str = separator.as_ref();
 
@@ -311,88 +1037,1113 @@ where
             rv.push_str(separator);
         }
 
-        rv.push_str(&format!("{}", item));
+        e.write_escaped(&mut rv, &key(&item).to_string())?;
     }
 
-    Ok(rv)
+    Ok(MarkupDisplay::new_safe(rv, e))
 }
 
-#[cfg(feature = "num-traits")]
-/// Absolute value
-pub fn abs<T>(number: T) -> Result<T>
+/// Sorts the entries of a map by key
+///
+/// In templates, `map|dictsort("value")` sorts by value instead, and a
+/// second argument of `true` reverses the order.
+pub fn dictsort<'a, I, K, V>(map: I, reverse: bool) -> Result<Vec<(&'a K, &'a V)>>
 where
-    T: Signed,
+    I: IntoIterator<Item = (&'a K, &'a V)>,
+    K: Ord + 'a,
+    V: 'a,
 {
-    Ok(number.abs())
+    let mut entries: Vec<_> = map.into_iter().collect();
+    entries.sort_by(|a, b| match reverse {
+        false => a.0.cmp(b.0),
+        true => b.0.cmp(a.0),
+    });
+    Ok(entries)
 }
 
-/// Capitalize a value. The first character will be uppercase, all others lowercase.
-pub fn capitalize<T: fmt::Display>(s: T) -> Result<String> {
-    let mut s = s.to_string();
+/// Sorts the entries of a map by value, then key; see [`dictsort()`]
+pub fn dictsort_by_value<'a, I, K, V>(map: I, reverse: bool) -> Result<Vec<(&'a K, &'a V)>>
+where
+    I: IntoIterator<Item = (&'a K, &'a V)>,
+    K: Ord + 'a,
+    V: Ord + 'a,
+{
+    let mut entries: Vec<_> = map.into_iter().collect();
+    entries.sort_by(|a, b| match reverse {
+        false => (a.1, a.0).cmp(&(b.1, b.0)),
+        true => (b.1, b.0).cmp(&(a.1, a.0)),
+    });
+    Ok(entries)
+}
 
-    match s.get_mut(0..1).map(|s| {
-        s.make_ascii_uppercase();
-        &*s
-    }) {
-        None => Ok(s),
-        _ => {
-            s.get_mut(1..).map(|s| {
-                s.make_ascii_lowercase();
-                &*s
-            });
-            Ok(s)
-        }
+/// Sorts the items of an iterable
+///
+/// The items are collected into a `Vec`, which for an iterable of references
+/// like `&Vec<T>` holds references to the items, not copies of them. The sort
+/// is stable, even when `reverse` is set. In templates, `seq|sort("name")`
+/// sorts by a field of the items instead, see [`sort_by()`].
+pub fn sort<I>(input: I, reverse: bool) -> Result<Vec<I::Item>>
+where
+    I: IntoIterator,
+    I::Item: Ord,
+{
+    sort_by(input, reverse, Ord::cmp)
+}
+
+/// Sorts the items of an iterable by the given comparison, see [`sort()`]
+pub fn sort_by<I, F>(input: I, reverse: bool, mut compare: F) -> Result<Vec<I::Item>>
+where
+    I: IntoIterator,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+    let mut items: Vec<_> = input.into_iter().collect();
+    match reverse {
+        false => items.sort_by(|a, b| compare(a, b)),
+        true => items.sort_by(|a, b| compare(b, a)),
     }
+    Ok(items)
 }
 
-/// Centers the value in a field of a given width
-pub fn center(src: &dyn fmt::Display, dst_len: usize) -> Result<String> {
-    let src = src.to_string();
-    let len = src.len();
+/// Compares strings case-insensitively, for `sort` with `case_sensitive` unset
+pub fn cmp_ignore_case<S: AsRef<str> + ?Sized>(a: &S, b: &S) -> Ordering {
+    let lower = |s: &S| {
+        s.as_ref()
+            .chars()
+            .flat_map(char::to_lowercase)
+            .collect::<Vec<_>>()
+    };
+    lower(a).cmp(&lower(b))
+}
 
-    if dst_len <= len {
-        Ok(src)
-    } else {
-        let diff = dst_len - len;
-        let mid = diff / 2;
-        let r = diff % 2;
-        let mut buf = String::with_capacity(dst_len);
-
-        for _ in 0..mid {
-            buf.push(' ');
-        }
+/// Values the `default` filter can be applied to
+///
+/// `Option<T>` is the main one, with `None` replaced by the fallback. The
+/// other implementations are never `None`, but their emptiness can be checked
+/// with [`default_if_empty()`].
+pub trait DefaultInput {
+    type Value: ?Sized;
 
-        buf.push_str(&src);
+    fn value(&self) -> Option<&Self::Value>;
+}
 
-        for _ in 0..mid + r {
-            buf.push(' ');
-        }
+impl<T> DefaultInput for Option<T> {
+    type Value = T;
 
-        Ok(buf)
+    fn value(&self) -> Option<&T> {
+        self.as_ref()
     }
 }
 
-/// Count the words in that string
-pub fn wordcount<T: fmt::Display>(s: T) -> Result<usize> {
-    let s = s.to_string();
+impl<T: DefaultInput + ?Sized> DefaultInput for &T {
+    type Value = T::Value;
 
-    Ok(s.split_whitespace().count())
+    fn value(&self) -> Option<&T::Value> {
+        T::value(self)
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    #[cfg(feature = "num-traits")]
-    use std::f64::INFINITY;
+macro_rules! impl_default_input {
+    ($($ty:ty $(: $param:ident)?),*) => { $(
+        impl$(<$param>)? DefaultInput for $ty {
+            type Value = Self;
 
-    #[cfg(feature = "humansize")]
-    #[test]
-    fn test_filesizeformat() {
-        assert_eq!(filesizeformat(&0).unwrap(), "0 B");
-        assert_eq!(filesizeformat(&999u64).unwrap(), "999 B");
-        assert_eq!(filesizeformat(&1000i32).unwrap(), "1 KB");
-        assert_eq!(filesizeformat(&1023).unwrap(), "1.02 KB");
-        assert_eq!(filesizeformat(&1024usize).unwrap(), "1.02 KB");
+            fn value(&self) -> Option<&Self> {
+                Some(self)
+            }
+        }
+    )* };
+}
+
+impl_default_input!(str, String, [T]: T, Vec<T>: T);
+
+/// Values which `default_if_empty()` can check for emptiness
+pub trait IsEmpty {
+    fn is_empty(&self) -> bool;
+}
+
+impl<T: IsEmpty + ?Sized> IsEmpty for &T {
+    fn is_empty(&self) -> bool {
+        T::is_empty(self)
+    }
+}
+
+macro_rules! impl_is_empty {
+    ($($ty:ty $(: $param:ident)?),*) => { $(
+        impl$(<$param>)? IsEmpty for $ty {
+            fn is_empty(&self) -> bool {
+                <$ty>::is_empty(self)
+            }
+        }
+    )* };
+}
+
+impl_is_empty!(str, String, [T]: T, Vec<T>: T);
+
+impl<K, V, S> IsEmpty for std::collections::HashMap<K, V, S> {
+    fn is_empty(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+impl<K, V> IsEmpty for std::collections::BTreeMap<K, V> {
+    fn is_empty(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+/// Replaces a `None` value with `fallback`
+///
+/// The fallback has to be of the same type as the value, or of a type the
+/// value can be borrowed as (e.g. a `&str` for a `String`).
+pub fn default<'a, V, T, F>(value: &'a V, fallback: &'a F) -> Result<&'a F>
+where
+    V: DefaultInput<Value = T> + ?Sized,
+    T: Borrow<F> + ?Sized + 'a,
+    F: ?Sized,
+{
+    Ok(value.value().map_or(fallback, |v| v.borrow()))
+}
+
+/// Like [`default()`], but also replaces an empty value if `if_empty` is set
+pub fn default_if_empty<'a, V, T, F>(value: &'a V, fallback: &'a F, if_empty: bool) -> Result<&'a F>
+where
+    V: DefaultInput<Value = T> + ?Sized,
+    T: Borrow<F> + ?Sized + 'a,
+    F: IsEmpty + ?Sized,
+{
+    Ok(match value.value().map(|v| v.borrow()) {
+        Some(v) if !(if_empty && v.is_empty()) => v,
+        _ => fallback,
+    })
+}
+
+/// Returns the first element of a sequence, or `None` if it is empty
+///
+/// Being an `Option`, the result can be used with `{% if let %}` and
+/// `{% match %}`.
+pub fn first<T, S>(s: &S) -> Result<Option<&T>>
+where
+    S: AsRef<[T]> + ?Sized,
+{
+    Ok(s.as_ref().first())
+}
+
+/// Returns the last element of a sequence, or `None` if it is empty
+pub fn last<T, S>(s: &S) -> Result<Option<&T>>
+where
+    S: AsRef<[T]> + ?Sized,
+{
+    Ok(s.as_ref().last())
+}
+
+/// Returns a random element of a sequence, or `None` if it is empty (requires
+/// `rand` feature)
+///
+/// The element is picked with the thread-local generator, so the output
+/// differs between renders; see [`random_seeded()`] for a reproducible one.
+#[cfg(feature = "rand")]
+pub fn random<T, S>(s: &S) -> Result<Option<&T>>
+where
+    S: AsRef<[T]> + ?Sized,
+{
+    Ok(s.as_ref().choose(&mut rand::thread_rng()))
+}
+
+/// Like [`random()`], but picks the same element for the same `seed` and
+/// sequence length (requires `rand` feature)
+///
+/// The element picked for a seed may change when the `rand` dependency is
+/// upgraded.
+#[cfg(feature = "rand")]
+pub fn random_seeded<T, S>(s: &S, seed: u64) -> Result<Option<&T>>
+where
+    S: AsRef<[T]> + ?Sized,
+{
+    Ok(s.as_ref().choose(&mut StdRng::seed_from_u64(seed)))
+}
+
+/// Values the `slice` filter can be applied to: strings, which are sliced by
+/// characters, and sequences
+pub trait Slice {
+    type Output: ?Sized;
+
+    /// Gets the part from `start` up to `end`, see [`slice()`]
+    fn slice(&self, start: isize, end: Option<isize>) -> &Self::Output;
+}
+
+impl<T: Slice + ?Sized> Slice for &T {
+    type Output = T::Output;
+
+    fn slice(&self, start: isize, end: Option<isize>) -> &Self::Output {
+        T::slice(self, start, end)
+    }
+}
+
+impl Slice for str {
+    type Output = str;
+
+    fn slice(&self, start: isize, end: Option<isize>) -> &str {
+        let (start, end) = slice_range(self.chars().count(), start, end);
+        let mut offsets = self.char_indices().map(|(i, _)| i).chain(Some(self.len()));
+        let start_offset = offsets.nth(start).unwrap_or(self.len());
+        let end_offset = match end - start {
+            0 => start_offset,
+            n => offsets.nth(n - 1).unwrap_or(self.len()),
+        };
+        &self[start_offset..end_offset]
+    }
+}
+
+impl Slice for String {
+    type Output = str;
+
+    fn slice(&self, start: isize, end: Option<isize>) -> &str {
+        self.as_str().slice(start, end)
+    }
+}
+
+impl<T> Slice for [T] {
+    type Output = [T];
+
+    fn slice(&self, start: isize, end: Option<isize>) -> &[T] {
+        let (start, end) = slice_range(self.len(), start, end);
+        &self[start..end]
+    }
+}
+
+impl<T> Slice for Vec<T> {
+    type Output = [T];
+
+    fn slice(&self, start: isize, end: Option<isize>) -> &[T] {
+        self.as_slice().slice(start, end)
+    }
+}
+
+// Resolves negative indices from the end and clamps them to `0..=len`.
+fn slice_range(len: usize, start: isize, end: Option<isize>) -> (usize, usize) {
+    let resolve = |i: isize| match i {
+        i if i < 0 => len.saturating_sub(i.unsigned_abs()),
+        i => len.min(i as usize),
+    };
+    let start = resolve(start);
+    let end = end.map_or(len, resolve);
+    (start, end.max(start))
+}
+
+/// Gets the characters of a string, or the items of a sequence, from `start`
+/// up to `end`
+///
+/// As in Python, negative indices count from the end, and indices out of
+/// range are clamped to it. Without `end`, the rest of the input is taken.
+pub fn slice<S: Slice + ?Sized>(s: &S, start: isize, end: Option<isize>) -> Result<&S::Output> {
+    Ok(s.slice(start, end))
+}
+
+/// Values the `reverse` filter can be applied to: strings, which are reversed
+/// by characters (or by graphemes, with the `unicode-segmentation` feature),
+/// and sequences
+pub trait Reverse<'a> {
+    type Output;
+
+    /// Gets the characters or items in reverse order, see [`reverse()`]
+    fn reverse(&'a self) -> Self::Output;
+}
+
+impl<'a, 'b: 'a, T: Reverse<'a> + ?Sized> Reverse<'a> for &'b T {
+    type Output = T::Output;
+
+    fn reverse(&'a self) -> Self::Output {
+        T::reverse(self)
+    }
+}
+
+impl<'a> Reverse<'a> for str {
+    type Output = String;
+
+    #[cfg(not(feature = "unicode-segmentation"))]
+    fn reverse(&self) -> String {
+        self.chars().rev().collect()
+    }
+
+    #[cfg(feature = "unicode-segmentation")]
+    fn reverse(&self) -> String {
+        unicode_segmentation::UnicodeSegmentation::graphemes(self, true)
+            .rev()
+            .collect()
+    }
+}
+
+impl<'a> Reverse<'a> for String {
+    type Output = String;
+
+    fn reverse(&self) -> String {
+        self.as_str().reverse()
+    }
+}
+
+impl<'a, T: 'a> Reverse<'a> for [T] {
+    type Output = Vec<&'a T>;
+
+    fn reverse(&'a self) -> Vec<&'a T> {
+        self.iter().rev().collect()
+    }
+}
+
+impl<'a, T: 'a> Reverse<'a> for Vec<T> {
+    type Output = Vec<&'a T>;
+
+    fn reverse(&'a self) -> Vec<&'a T> {
+        Reverse::reverse(self.as_slice())
+    }
+}
+
+/// Reverses a string, or the items of a sequence
+///
+/// A string is reversed by characters, so multibyte characters stay intact;
+/// with the `unicode-segmentation` feature, it is reversed by graphemes, which
+/// also keeps combining characters with the character they belong to. A
+/// sequence results in a `Vec` of references to its items.
+pub fn reverse<'a, T: Reverse<'a> + ?Sized>(input: &'a T) -> Result<T::Output> {
+    Ok(input.reverse())
+}
+
+/// Values the `length` filter can be applied to: strings, which are measured
+/// in characters, sequences, maps and sets
+pub trait Length {
+    /// Counts the characters, items or entries, see [`length()`]
+    fn length(&self) -> usize;
+}
+
+impl<T: Length + ?Sized> Length for &T {
+    fn length(&self) -> usize {
+        T::length(self)
+    }
+}
+
+impl Length for str {
+    fn length(&self) -> usize {
+        self.chars().count()
+    }
+}
+
+impl Length for String {
+    fn length(&self) -> usize {
+        self.as_str().length()
+    }
+}
+
+impl<T, const N: usize> Length for [T; N] {
+    fn length(&self) -> usize {
+        N
+    }
+}
+
+macro_rules! impl_length {
+    ($(<$($param:ident),*> $ty:ty),*) => { $(
+        impl<$($param),*> Length for $ty {
+            fn length(&self) -> usize {
+                self.len()
+            }
+        }
+    )* };
+}
+
+impl_length!(
+    <T> [T],
+    <T> Vec<T>,
+    <T> VecDeque<T>,
+    <K, V, S> HashMap<K, V, S>,
+    <K, V> BTreeMap<K, V>,
+    <T, S> HashSet<T, S>,
+    <T> BTreeSet<T>
+);
+
+/// Counts the characters of a string, the items of a sequence or set, or
+/// the entries of a map
+///
+/// A string is counted in characters (Unicode scalar values), not in bytes
+/// like `str::len()` does, so `"été"|length` is 3; see [`bytelength()`] for
+/// the number of bytes. The filter is also available as `count`.
+pub fn length<T: Length + ?Sized>(input: &T) -> Result<usize> {
+    Ok(input.length())
+}
+
+/// Counts the bytes of a string in UTF-8, like `str::len()`
+pub fn bytelength<S: AsRef<str> + ?Sized>(s: &S) -> Result<usize> {
+    Ok(s.as_ref().len())
+}
+
+/// Collects the value `f` returns for each item
+///
+/// In templates, the value is given as the name of a field of the items, e.g.
+/// `countries|map("name")`, which collects references to that field.
+pub fn map<I, T, F>(input: I, f: F) -> Result<Vec<T>>
+where
+    I: IntoIterator,
+    F: FnMut(I::Item) -> T,
+{
+    Ok(input.into_iter().map(f).collect())
+}
+
+/// Keeps the items of an iterable for which `predicate` returns `true`
+///
+/// In templates, the predicate is given as the name of a `bool` field of the
+/// items, e.g. `users|select("active")`, or as a field, a comparison operator
+/// and a value to compare the field to: `users|select("age", ">=", 18)`.
+pub fn select<I, F>(input: I, mut predicate: F) -> Result<Vec<I::Item>>
+where
+    I: IntoIterator,
+    F: FnMut(&I::Item) -> bool,
+{
+    Ok(input.into_iter().filter(|item| predicate(item)).collect())
+}
+
+/// Drops the items of an iterable for which `predicate` returns `true`, the
+/// opposite of [`select()`]
+pub fn reject<I, F>(input: I, mut predicate: F) -> Result<Vec<I::Item>>
+where
+    I: IntoIterator,
+    F: FnMut(&I::Item) -> bool,
+{
+    Ok(input.into_iter().filter(|item| !predicate(item)).collect())
+}
+
+/// Partitions iterable into groups of items with the same key
+///
+/// The groups are in the order their keys were first seen, and the items in
+/// each group keep their original order. In templates, the key is given as
+/// the name of a field of the items, e.g. `products|groupby("category")`.
+pub fn groupby<I, K, F>(input: I, key: F) -> Result<Vec<(K, Vec<I::Item>)>>
+where
+    I: IntoIterator,
+    K: PartialEq,
+    F: Fn(&I::Item) -> K,
+{
+    let mut groups: Vec<(K, Vec<I::Item>)> = Vec::new();
+    for item in input {
+        let k = key(&item);
+        match groups.iter_mut().find(|(group, _)| *group == k) {
+            Some((_, items)) => items.push(item),
+            None => groups.push((k, vec![item])),
+        }
+    }
+    Ok(groups)
+}
+
+/// Splits an iterable into rows of `size` items
+///
+/// The last row is short if the items don't fill it, see [`batch_fill()`]
+/// for padding it. In templates, the rows can be iterated in a nested loop:
+/// `{% for row in items|batch(3) %}{% for item in row %}…`. `items|batch(3,
+/// fill)` pads the last row with `fill`, which must have the type of the
+/// items.
+pub fn batch<I: IntoIterator>(input: I, size: usize) -> Result<Vec<Vec<I::Item>>> {
+    if size == 0 {
+        return Err(crate::Error::custom("the batch size must be at least 1"));
+    }
+    let mut rows = Vec::new();
+    let mut input = input.into_iter().peekable();
+    while input.peek().is_some() {
+        rows.push(input.by_ref().take(size).collect());
+    }
+    Ok(rows)
+}
+
+/// Splits an iterable into rows of `size` items, padding the last row with
+/// clones of `fill`
+pub fn batch_fill<I>(input: I, size: usize, fill: I::Item) -> Result<Vec<Vec<I::Item>>>
+where
+    I: IntoIterator,
+    I::Item: Clone,
+{
+    let mut rows = batch(input, size)?;
+    if let Some(last) = rows.last_mut() {
+        last.resize(size, fill);
+    }
+    Ok(rows)
+}
+
+/// Drops the items of an iterable that are equal to an earlier one
+///
+/// The remaining items keep their order. Each item is hashed, and its first
+/// occurrence is cloned to look up the ones after it; for the references
+/// templates iterate over, these clones are cheap.
+pub fn unique<I>(input: I) -> Result<Vec<I::Item>>
+where
+    I: IntoIterator,
+    I::Item: Eq + Hash + Clone,
+{
+    unique_by(input, |item| item.clone())
+}
+
+/// Drops the items of an iterable with the same key as an earlier one
+///
+/// In templates, the key is given as the name of a field of the items, e.g.
+/// `users|unique("country")`; the key of each item is cloned.
+pub fn unique_by<I, K, F>(input: I, key: F) -> Result<Vec<I::Item>>
+where
+    I: IntoIterator,
+    K: Eq + Hash,
+    F: Fn(&I::Item) -> K,
+{
+    let mut seen = HashSet::new();
+    Ok(input
+        .into_iter()
+        .filter(|item| seen.insert(key(item)))
+        .collect())
+}
+
+/// Adds up the items of an iterable, starting from zero
+///
+/// The total has the type of the items, or of the values they refer to, e.g.
+/// `u32` for the `&u32` that `prices|map("amount")` collects. An empty
+/// iterable sums up to zero. In templates, `seq|sum(start)` starts from
+/// `start` instead, see [`sum_from()`].
+pub fn sum<I>(input: I) -> Result<<I::Item as Summable>::Total>
+where
+    I: IntoIterator,
+    I::Item: Summable,
+{
+    sum_from(input, Default::default())
+}
+
+/// Adds up the items of an iterable to `start`, see [`sum()`]
+///
+/// The total has the type of `start`, which has to support adding the items.
+pub fn sum_from<I, T>(input: I, start: T) -> Result<T>
+where
+    I: IntoIterator,
+    I::Item: Summable,
+    T: Add<<I::Item as Summable>::Total, Output = T>,
+{
+    Ok(input
+        .into_iter()
+        .fold(start, |total, item| total + item.to_total()))
+}
+
+/// Returns the largest item of an iterable, or `None` if it is empty
+///
+/// Like [`Iterator::max()`], the last of several largest items is returned.
+/// Items that can't be compared, like a `NaN` float, are skipped unless they
+/// come first.
+pub fn max<I>(input: I) -> Result<Option<I::Item>>
+where
+    I: IntoIterator,
+    I::Item: PartialOrd,
+{
+    let mut max = None;
+    for item in input {
+        let replace = match &max {
+            Some(max) => item >= *max,
+            None => true,
+        };
+        if replace {
+            max = Some(item);
+        }
+    }
+    Ok(max)
+}
+
+/// Returns the smallest item of an iterable, or `None` if it is empty
+///
+/// Like [`Iterator::min()`], the first of several smallest items is returned.
+pub fn min<I>(input: I) -> Result<Option<I::Item>>
+where
+    I: IntoIterator,
+    I::Item: PartialOrd,
+{
+    let mut min = None;
+    for item in input {
+        let replace = match &min {
+            Some(min) => item < *min,
+            None => true,
+        };
+        if replace {
+            min = Some(item);
+        }
+    }
+    Ok(min)
+}
+
+/// Absolute value
+///
+/// The absolute value of the smallest integer of a type, like `i32::MIN`,
+/// doesn't fit the type, which results in an error.
+///
+/// The input is passed through [`AbsSource`], so with the `num-traits`
+/// feature, any type implementing `num_traits::Signed` works as well.
+pub fn abs<T: Abs>(number: T) -> Result<T::Output> {
+    number
+        .checked_abs()
+        .ok_or_else(|| crate::Error::custom("abs overflowed"))
+}
+
+/// Rounds a floating point number to `precision` decimal places
+///
+/// Halfway cases are rounded away from zero. A negative precision rounds to
+/// tens, hundreds and so on.
+pub fn round<T: Round>(number: T, precision: i32) -> Result<T::Output> {
+    Ok(number.round(precision))
+}
+
+/// Rounds a floating point number down to the nearest integer
+pub fn floor<T: Round>(number: T) -> Result<T::Output> {
+    Ok(number.floor())
+}
+
+/// Rounds a floating point number up to the nearest integer
+pub fn ceil<T: Round>(number: T) -> Result<T::Output> {
+    Ok(number.ceil())
+}
+
+/// Numbers the [`abs`] filter can be applied to: signed integers and floats
+pub trait Abs {
+    type Output;
+
+    /// The absolute value, or `None` if it doesn't fit the type
+    fn checked_abs(&self) -> Option<Self::Output>;
+}
+
+impl<T: Abs + ?Sized> Abs for &T {
+    type Output = T::Output;
+
+    fn checked_abs(&self) -> Option<Self::Output> {
+        T::checked_abs(self)
+    }
+}
+
+/// The input of the [`abs`] filter
+///
+/// `AbsSource(&value).abs_source()` is `value` itself if it implements
+/// [`Abs`], through [`AbsNumber`], and otherwise a [`SignedNumber`] using its
+/// `num_traits::Signed` implementation, through `AbsSigned`. Both traits must
+/// be in scope.
+pub struct AbsSource<'a, T: ?Sized>(pub &'a T);
+
+pub trait AbsNumber<'a, T: ?Sized> {
+    fn abs_source(self) -> &'a T;
+}
+
+impl<'a, T: Abs + ?Sized> AbsNumber<'a, T> for AbsSource<'a, T> {
+    fn abs_source(self) -> &'a T {
+        self.0
+    }
+}
+
+#[cfg(feature = "num-traits")]
+pub trait AbsSigned<'a, T> {
+    fn abs_source(self) -> SignedNumber<'a, T>;
+}
+
+#[cfg(feature = "num-traits")]
+impl<'a, T: Signed> AbsSigned<'a, T> for &AbsSource<'a, T> {
+    fn abs_source(self) -> SignedNumber<'a, T> {
+        SignedNumber(self.0)
+    }
+}
+
+/// A number taking its absolute value from `num_traits::Signed`
+///
+/// Unlike for the types implementing [`Abs`] themselves, an overflow isn't
+/// detected: `Signed::abs()` decides what happens.
+#[cfg(feature = "num-traits")]
+pub struct SignedNumber<'a, T>(pub &'a T);
+
+#[cfg(feature = "num-traits")]
+impl<T: Signed> Abs for SignedNumber<'_, T> {
+    type Output = T;
+
+    fn checked_abs(&self) -> Option<T> {
+        Some(self.0.abs())
+    }
+}
+
+/// Items the [`sum`] filter can add up: numbers, and references to them
+pub trait Summable {
+    type Total: Add<Output = Self::Total> + Default;
+
+    /// The value added to the total
+    fn to_total(self) -> Self::Total;
+}
+
+impl<T: Summable + Copy> Summable for &T {
+    type Total = T::Total;
+
+    fn to_total(self) -> Self::Total {
+        T::to_total(*self)
+    }
+}
+
+macro_rules! impl_summable {
+    ($($t:ty)*) => ($(
+        impl Summable for $t {
+            type Total = $t;
+
+            fn to_total(self) -> $t {
+                self
+            }
+        }
+    )*);
+}
+
+impl_summable!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize f32 f64);
+
+/// Numbers the [`round`], [`floor`] and [`ceil`] filters can be applied to
+pub trait Round {
+    type Output;
+
+    fn round(&self, precision: i32) -> Self::Output;
+    fn floor(&self) -> Self::Output;
+    fn ceil(&self) -> Self::Output;
+}
+
+impl<T: Round + ?Sized> Round for &T {
+    type Output = T::Output;
+
+    fn round(&self, precision: i32) -> Self::Output {
+        T::round(self, precision)
+    }
+
+    fn floor(&self) -> Self::Output {
+        T::floor(self)
+    }
+
+    fn ceil(&self) -> Self::Output {
+        T::ceil(self)
+    }
+}
+
+macro_rules! impl_number {
+    (int: $($t:ty)*) => ($(
+        impl Abs for $t {
+            type Output = $t;
+
+            fn checked_abs(&self) -> Option<$t> {
+                <$t>::checked_abs(*self)
+            }
+        }
+    )*);
+    (float: $($t:ty)*) => ($(
+        impl Abs for $t {
+            type Output = $t;
+
+            fn checked_abs(&self) -> Option<$t> {
+                Some(<$t>::abs(*self))
+            }
+        }
+
+        impl Round for $t {
+            type Output = $t;
+
+            fn round(&self, precision: i32) -> $t {
+                // Scaling in `f64` keeps `f32` values like 2.5 exact
+                let factor = 10_f64.powi(precision);
+                ((*self as f64 * factor).round() / factor) as $t
+            }
+
+            fn floor(&self) -> $t {
+                <$t>::floor(*self)
+            }
+
+            fn ceil(&self) -> $t {
+                <$t>::ceil(*self)
+            }
+        }
+    )*);
+}
+
+impl_number!(int: i8 i16 i32 i64 i128 isize);
+impl_number!(float: f32 f64);
+
+/// Capitalize a value. The first character will be uppercase, all others lowercase.
+///
+/// As in Jinja, the first character is converted to title case: if its
+/// uppercase form has several characters, only the first of them stays
+/// uppercase, so `ß` becomes `Ss`.
+pub fn capitalize<T: fmt::Display>(s: T) -> Result<String> {
+    let s = s.to_string();
+    let mut capitalized = String::with_capacity(s.len());
+    push_capitalized(&mut capitalized, &s);
+    Ok(capitalized)
+}
+
+fn push_capitalized(out: &mut String, s: &str) {
+    let mut chars = s.chars();
+    if let Some(first) = chars.next() {
+        let mut upper = first.to_uppercase();
+        out.extend(upper.next());
+        out.extend(upper.flat_map(char::to_lowercase));
+        out.push_str(&chars.as_str().to_lowercase());
+    }
+}
+
+/// Centers the value in a field of a given width, filled with `fill`
+///
+/// Widths are counted in characters; with the `unicode-width` feature, they are
+/// counted in columns instead, so wide characters line up. A value that is
+/// already wider than the field is returned unchanged.
+pub fn center(src: &dyn fmt::Display, dst_len: usize, fill: char) -> Result<String> {
+    pad(src, dst_len, fill, |diff| (diff / 2, diff - diff / 2))
+}
+
+/// Aligns the value to the left of a field of a given width, see [`center()`]
+pub fn ljust(src: &dyn fmt::Display, dst_len: usize, fill: char) -> Result<String> {
+    pad(src, dst_len, fill, |diff| (0, diff))
+}
+
+/// Aligns the value to the right of a field of a given width, see [`center()`]
+pub fn rjust(src: &dyn fmt::Display, dst_len: usize, fill: char) -> Result<String> {
+    pad(src, dst_len, fill, |diff| (diff, 0))
+}
+
+// Pads `src` to `dst_len`, splitting the padding into what goes before and
+// after it with `split`.
+fn pad(
+    src: &dyn fmt::Display,
+    dst_len: usize,
+    fill: char,
+    split: impl Fn(usize) -> (usize, usize),
+) -> Result<String> {
+    let src = src.to_string();
+    let len = display_width(&src);
+    if dst_len <= len {
+        return Ok(src);
+    }
+
+    let (before, after) = split(dst_len - len);
+    let mut buf = String::with_capacity(src.len() + (before + after) * fill.len_utf8());
+    for _ in 0..before {
+        buf.push(fill);
+    }
+    buf.push_str(&src);
+    for _ in 0..after {
+        buf.push(fill);
+    }
+    Ok(buf)
+}
+
+#[cfg(feature = "unicode-width")]
+fn display_width(s: &str) -> usize {
+    unicode_width::UnicodeWidthStr::width(s)
+}
+
+#[cfg(not(feature = "unicode-width"))]
+fn display_width(s: &str) -> usize {
+    s.chars().count()
+}
+
+/// Returns a plural suffix if the count is not 1
+///
+/// The suffixes default to `""` for the singular and `"s"` for the plural
+/// form, and can be overridden: `{{ n|pluralize("y", "ies") }}`.
+pub fn pluralize<C, S, P>(count: C, singular: S, plural: P) -> Result<String>
+where
+    C: PluralCount,
+    S: fmt::Display,
+    P: fmt::Display,
+{
+    Ok(match count.is_singular() {
+        true => singular.to_string(),
+        false => plural.to_string(),
+    })
+}
+
+/// Integers which can be used as the count for the [`pluralize`] filter
+pub trait PluralCount {
+    /// Whether the count calls for the singular form, i.e. if it is 1
+    fn is_singular(&self) -> bool;
+}
+
+impl<T: PluralCount + ?Sized> PluralCount for &T {
+    fn is_singular(&self) -> bool {
+        T::is_singular(self)
+    }
+}
+
+macro_rules! impl_plural_count {
+    ($($t:ty)*) => ($(
+        impl PluralCount for $t {
+            fn is_singular(&self) -> bool {
+                *self == 1
+            }
+        }
+    )*);
+}
+
+impl_plural_count!(u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize);
+
+/// Count the words in that string, which are separated by any whitespace
+pub fn wordcount<T: fmt::Display>(s: T) -> Result<usize> {
+    let s = s.to_string();
+
+    Ok(s.split_whitespace().count())
+}
+
+/// Wrap text to lines of at most `width` characters, breaking at whitespace
+///
+/// Lines are joined with `wrapstring`, which defaults to a newline. Newlines
+/// in the input are kept, so paragraphs are wrapped separately. Words longer
+/// than `width` are left on a line of their own, unless `break_long_words`
+/// is `true`, in which case they are split.
+pub fn wordwrap<T, S>(s: T, width: usize, wrapstring: S, break_long_words: bool) -> Result<String>
+where
+    T: fmt::Display,
+    S: AsRef<str>,
+{
+    let s = s.to_string();
+    let wrapstring = wrapstring.as_ref();
+    let width = width.max(1);
+
+    let mut rv = String::with_capacity(s.len());
+    for (i, paragraph) in s.split('\n').enumerate() {
+        if i > 0 {
+            rv.push('\n');
+        }
+
+        let mut line_len = 0;
+        for word in paragraph.split_whitespace() {
+            let mut word = word;
+            let mut word_len = word.chars().count();
+            if line_len > 0 {
+                if line_len + 1 + word_len <= width {
+                    rv.push(' ');
+                    rv.push_str(word);
+                    line_len += 1 + word_len;
+                    continue;
+                }
+                rv.push_str(wrapstring);
+            }
+
+            while break_long_words && word_len > width {
+                let (end, _) = word.char_indices().nth(width).unwrap();
+                rv.push_str(&word[..end]);
+                rv.push_str(wrapstring);
+                word = &word[end..];
+                word_len -= width;
+            }
+            rv.push_str(word);
+            line_len = word_len;
+        }
+    }
+    Ok(rv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(any(feature = "base64", feature = "percent-encoding"))]
+    use askama_escape::{Html, Text};
+    use std::collections::HashMap;
+    #[cfg(feature = "num-traits")]
+    use std::f64::INFINITY;
+
+    #[cfg(feature = "humansize")]
+    #[test]
+    fn test_filesizeformat() {
+        assert_eq!(filesizeformat(0, false).unwrap(), "0 B");
+        assert_eq!(filesizeformat(999u64, false).unwrap(), "999 B");
+        assert_eq!(filesizeformat(1000i32, false).unwrap(), "1 KB");
+        assert_eq!(filesizeformat(1023, false).unwrap(), "1.02 KB");
+        assert_eq!(filesizeformat(1024usize, false).unwrap(), "1.02 KB");
+        assert_eq!(filesizeformat(1500000, false).unwrap(), "1.50 MB");
+        assert_eq!(filesizeformat(1024, true).unwrap(), "1 KiB");
+        assert_eq!(filesizeformat(1536u32, true).unwrap(), "1.50 KiB");
+        assert_eq!(filesizeformat(-2000i64, false).unwrap(), "-2 KB");
+        assert_eq!(filesizeformat(i64::MIN, false).unwrap(), "-9.22 EB");
+        assert_eq!(filesizeformat(u64::MAX, true).unwrap(), "16 EiB");
+        assert_eq!(filesizeformat(u128::MAX, false).unwrap(), "18.45 EB");
+        assert_eq!(filesizeformat(2.5e9, false).unwrap(), "2.50 GB");
+        assert_eq!(filesizeformat(-0.0, false).unwrap(), "0 B");
+        assert_eq!(filesizeformat(1e300f64, false).unwrap(), "18.45 EB");
+    }
+
+    #[test]
+    fn test_date() {
+        let d = NaiveDate::from_ymd_opt(2021, 3, 7).unwrap();
+        let t = NaiveTime::from_hms_milli_opt(9, 5, 30, 250).unwrap();
+        let dt = d.and_time(t);
+        assert_eq!(date(d, ()).unwrap(), "2021-03-07");
+        assert_eq!(date(t, ()).unwrap(), "09:05:30.250");
+        assert_eq!(date(dt, ()).unwrap(), "2021-03-07T09:05:30.250");
+        assert_eq!(date(d, "%d/%m/%Y").unwrap(), "07/03/2021");
+        assert_eq!(
+            date(dt, String::from("%b %-d, %H:%M")).unwrap(),
+            "Mar 7, 09:05"
+        );
+
+        let utc = chrono::Utc.from_utc_datetime(&dt);
+        assert_eq!(date(utc, ()).unwrap(), "2021-03-07T09:05:30.250+00:00");
+        let offset = chrono::FixedOffset::east_opt(3600).unwrap();
+        assert_eq!(
+            date(utc.with_timezone(&offset), "%H:%M %z").unwrap(),
+            "10:05 +0100"
+        );
+
+        assert!(matches!(date(d, "%Y-%Q"), Err(crate::Error::Chrono(_))));
+    }
+
+    #[cfg(feature = "chrono-tz")]
+    #[test]
+    fn test_date_tz() {
+        let dt = NaiveDate::from_ymd_opt(2021, 7, 1)
+            .unwrap()
+            .and_hms_opt(12, 30, 0)
+            .unwrap();
+        let utc = chrono::Utc.from_utc_datetime(&dt);
+        assert_eq!(
+            date_tz(utc, "%H:%M %Z", "America/New_York").unwrap(),
+            "08:30 EDT"
+        );
+        let by_ref = &utc;
+        assert_eq!(
+            date_tz(by_ref, (), "Asia/Kolkata").unwrap(),
+            "2021-07-01T18:00:00+05:30"
+        );
+        let offset = chrono::FixedOffset::east_opt(3600).unwrap();
+        assert_eq!(
+            date_tz(utc.with_timezone(&offset), "%H:%M", "UTC").unwrap(),
+            "12:30"
+        );
+
+        let err = date_tz(utc, (), "Mars/Olympus_Mons").unwrap_err();
+        assert_eq!(err.to_string(), "unknown time zone: \"Mars/Olympus_Mons\"");
+        assert!(matches!(
+            date_tz(utc, "%Q", "UTC"),
+            Err(crate::Error::Chrono(_))
+        ));
+    }
+
+    #[cfg(feature = "base64")]
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(Html, "", false).unwrap().to_string(), "");
+        assert_eq!(
+            base64(Html, "hello", false).unwrap().to_string(),
+            "aGVsbG8="
+        );
+        assert_eq!(
+            base64(Html, vec![0xfb, 0xff, 0xfe], false)
+                .unwrap()
+                .to_string(),
+            "+//+"
+        );
+        assert_eq!(
+            base64(Html, vec![0xfb, 0xff, 0xfe], true)
+                .unwrap()
+                .to_string(),
+            "-__-"
+        );
+
+        assert_eq!(base64_decode("aGVsbG8=", false).unwrap(), "hello");
+        assert_eq!(base64_decode("LV9f", true).unwrap(), "-__");
+        assert!(matches!(
+            base64_decode("-__-", false),
+            Err(crate::Error::Custom(_))
+        ));
+        assert!(matches!(
+            base64_decode("+//+", false),
+            Err(crate::Error::Utf8(_))
+        ));
     }
 
     #[cfg(feature = "percent-encoding")]
@@ -400,77 +2151,151 @@ mod tests {
     fn test_urlencoding() {
         // Unreserved (https://tools.ietf.org/html/rfc3986.html#section-2.3)
         // alpha / digit
-        assert_eq!(urlencode(&"AZaz09").unwrap(), "AZaz09");
-        assert_eq!(urlencode_strict(&"AZaz09").unwrap(), "AZaz09");
+        assert_eq!(urlencode(Html, "AZaz09").unwrap().to_string(), "AZaz09");
+        assert_eq!(
+            urlencode_strict(Html, "AZaz09").unwrap().to_string(),
+            "AZaz09"
+        );
         // other
-        assert_eq!(urlencode(&"_.-~").unwrap(), "_.-~");
-        assert_eq!(urlencode_strict(&"_.-~").unwrap(), "_.-~");
+        assert_eq!(urlencode(Html, "_.-~").unwrap().to_string(), "_.-~");
+        assert_eq!(urlencode_strict(Html, "_.-~").unwrap().to_string(), "_.-~");
 
         // Reserved (https://tools.ietf.org/html/rfc3986.html#section-2.2)
         // gen-delims
-        assert_eq!(urlencode(&":/?#[]@").unwrap(), "%3A/%3F%23%5B%5D%40");
         assert_eq!(
-            urlencode_strict(&":/?#[]@").unwrap(),
+            urlencode(Html, ":/?#[]@").unwrap().to_string(),
+            "%3A/%3F%23%5B%5D%40"
+        );
+        assert_eq!(
+            urlencode_strict(Html, ":/?#[]@").unwrap().to_string(),
             "%3A%2F%3F%23%5B%5D%40"
         );
         // sub-delims
         assert_eq!(
-            urlencode(&"!$&'()*+,;=").unwrap(),
+            urlencode(Html, "!$&'()*+,;=").unwrap().to_string(),
             "%21%24%26%27%28%29%2A%2B%2C%3B%3D"
         );
         assert_eq!(
-            urlencode_strict(&"!$&'()*+,;=").unwrap(),
+            urlencode_strict(Html, "!$&'()*+,;=").unwrap().to_string(),
             "%21%24%26%27%28%29%2A%2B%2C%3B%3D"
         );
 
         // Other
         assert_eq!(
-            urlencode(&"žŠďŤňĚáÉóŮ").unwrap(),
+            urlencode(Html, "žŠďŤňĚáÉóŮ").unwrap().to_string(),
             "%C5%BE%C5%A0%C4%8F%C5%A4%C5%88%C4%9A%C3%A1%C3%89%C3%B3%C5%AE"
         );
         assert_eq!(
-            urlencode_strict(&"žŠďŤňĚáÉóŮ").unwrap(),
+            urlencode_strict(Html, "žŠďŤňĚáÉóŮ").unwrap().to_string(),
             "%C5%BE%C5%A0%C4%8F%C5%A4%C5%88%C4%9A%C3%A1%C3%89%C3%B3%C5%AE"
         );
 
         // Ferris
-        assert_eq!(urlencode(&"🦀").unwrap(), "%F0%9F%A6%80");
-        assert_eq!(urlencode_strict(&"🦀").unwrap(), "%F0%9F%A6%80");
+        assert_eq!(urlencode(Html, "🦀").unwrap().to_string(), "%F0%9F%A6%80");
+        assert_eq!(
+            urlencode_strict(Html, "🦀").unwrap().to_string(),
+            "%F0%9F%A6%80"
+        );
     }
 
     #[test]
     fn test_linebreaks() {
         assert_eq!(
-            linebreaks(&"Foo\nBar Baz").unwrap(),
+            linebreaks(Html, &"Foo\nBar Baz").unwrap().to_string(),
             "<p>Foo<br/>Bar Baz</p>"
         );
         assert_eq!(
-            linebreaks(&"Foo\nBar\n\nBaz").unwrap(),
+            linebreaks(Html, &"Foo\nBar\n\nBaz").unwrap().to_string(),
             "<p>Foo<br/>Bar</p><p>Baz</p>"
         );
+        assert_eq!(
+            linebreaks(Html, "<script>\n\nx").unwrap().to_string(),
+            "<p>&lt;script&gt;</p><p>x</p>"
+        );
+        assert_eq!(
+            linebreaks(Text, "<b>\nx").unwrap().to_string(),
+            "<p><b><br/>x</p>"
+        );
+    }
+
+    #[test]
+    fn test_highlight() {
+        let h = |s: &str, q: &str, regex| highlight(Html, s, q, regex).unwrap().to_string();
+        assert_eq!(
+            h("Askama and askama", "askama", false),
+            "<mark>Askama</mark> and <mark>askama</mark>"
+        );
+        assert_eq!(
+            h("a+b <a+B>", "A+b", false),
+            "<mark>a+b</mark> &lt;<mark>a+B</mark>&gt;"
+        );
+        assert_eq!(h("fish & chips", "amp", false), "fish &amp; chips");
+        assert_eq!(h("<b>", "", false), "&lt;b&gt;");
+        assert_eq!(h("aaa", "aa", false), "<mark>aa</mark>a");
+        assert_eq!(
+            h("cat, cot, cut", "c[ao]t", true),
+            "<mark>cat</mark>, <mark>cot</mark>, cut"
+        );
+        assert_eq!(h("abc", "x*", true), "abc");
+        assert_eq!(h("c[ao]t", "c[ao]t", false), "<mark>c[ao]t</mark>");
+        assert!(matches!(
+            highlight(Html, "abc", "(", true),
+            Err(crate::Error::RegEx(_))
+        ));
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("ann@example.com", "^[^@]+@[^@]+$").unwrap());
+        assert!(!matches("ann", "^[^@]+@[^@]+$").unwrap());
+        assert!(matches(1234, r"^\d+$").unwrap());
+        assert!(matches!(matches("a", "("), Err(crate::Error::RegEx(_))));
+
+        static RE: StaticRegex = StaticRegex::new("^a+$");
+        assert!(matches_static("aaa", &RE).unwrap());
+        assert!(!matches_static("aab", &RE).unwrap());
+        // Compiled once, on the first use.
+        assert!(std::ptr::eq(RE.get().unwrap(), RE.get().unwrap()));
+
+        static INVALID: StaticRegex = StaticRegex::new("(");
+        assert!(matches!(
+            matches_static("a", &INVALID),
+            Err(crate::Error::RegEx(_))
+        ));
     }
 
     #[test]
     fn test_linebreaksbr() {
-        assert_eq!(linebreaksbr(&"Foo\nBar").unwrap(), "Foo<br/>Bar");
         assert_eq!(
-            linebreaksbr(&"Foo\nBar\n\nBaz").unwrap(),
+            linebreaksbr(Html, &"Foo\nBar").unwrap().to_string(),
+            "Foo<br/>Bar"
+        );
+        assert_eq!(
+            linebreaksbr(Html, &"Foo\nBar\n\nBaz").unwrap().to_string(),
             "Foo<br/>Bar<br/><br/>Baz"
         );
+        assert_eq!(
+            linebreaksbr(Html, "<b>\n&").unwrap().to_string(),
+            "&lt;b&gt;<br/>&amp;"
+        );
     }
 
     #[test]
     fn test_paragraphbreaks() {
         assert_eq!(
-            paragraphbreaks(&"Foo\nBar Baz").unwrap(),
+            paragraphbreaks(Html, &"Foo\nBar Baz").unwrap().to_string(),
             "<p>Foo\nBar Baz</p>"
         );
         assert_eq!(
-            paragraphbreaks(&"Foo\nBar\n\nBaz").unwrap(),
+            paragraphbreaks(Html, &"Foo\nBar\n\nBaz")
+                .unwrap()
+                .to_string(),
             "<p>Foo\nBar</p><p>Baz</p>"
         );
         assert_eq!(
-            paragraphbreaks(&"Foo\n\n\n\n\nBar\n\nBaz").unwrap(),
+            paragraphbreaks(Html, &"Foo\n\n\n\n\nBar\n\nBaz")
+                .unwrap()
+                .to_string(),
             "<p>Foo</p><p>\nBar</p><p>Baz</p>"
         );
     }
@@ -493,32 +2318,123 @@ mod tests {
 
     #[test]
     fn test_trim() {
-        assert_eq!(trim(&" Hello\tworld\t").unwrap(), "Hello\tworld");
+        assert_eq!(trim(" Hello\tworld\t".into(), ()).unwrap(), "Hello\tworld");
+        assert_eq!(trim(String::from("/a/b/").into(), "/").unwrap(), "a/b");
+        assert_eq!(trim("-+a+-".into(), "+-").unwrap(), "a");
+        assert_eq!(trim("  ".into(), ()).unwrap(), "");
+    }
+
+    #[test]
+    fn test_trim_start_end() {
+        assert_eq!(trim_start(" \thello \n".into(), ()).unwrap(), "hello \n");
+        assert_eq!(trim_end(" \thello \n".into(), ()).unwrap(), " \thello");
+        assert_eq!(trim_start("//path/".into(), '/').unwrap(), "path/");
+        assert_eq!(trim_end("//path/".into(), "/").unwrap(), "//path");
+    }
+
+    #[test]
+    fn test_trim_source() {
+        let s = String::from(" a ");
+        assert!(matches!(
+            trim(TrimSource(&s).trim_source(), ()).unwrap(),
+            Cow::Borrowed("a")
+        ));
+        assert!(matches!(
+            trim(TrimSource(&"b ").trim_source(), ()).unwrap(),
+            Cow::Borrowed("b")
+        ));
+        assert_eq!(trim(TrimSource(&42u32).trim_source(), ()).unwrap(), "42");
+        let escaped = MarkupDisplay::new_unsafe(" <c> ", Html);
+        assert_eq!(
+            trim(TrimSource(&escaped).trim_source(), ()).unwrap(),
+            "&lt;c&gt;"
+        );
     }
 
     #[test]
     fn test_truncate() {
-        assert_eq!(truncate(&"hello", 2).unwrap(), "he...");
+        assert_eq!(truncate("hello", 2, "…", false).unwrap(), "he…");
+        assert_eq!(truncate("hello", 2, "...", false).unwrap(), "he...");
+        assert_eq!(truncate("hello", 5, "…", false).unwrap(), "hello");
+        assert_eq!(truncate("hello", 0, "…", false).unwrap(), "…");
         let a = String::from("您好");
         assert_eq!(a.len(), 6);
         assert_eq!(String::from("您").len(), 3);
-        assert_eq!(truncate(&"您好", 1).unwrap(), "您...");
-        assert_eq!(truncate(&"您好", 2).unwrap(), "您...");
-        assert_eq!(truncate(&"您好", 3).unwrap(), "您...");
-        assert_eq!(truncate(&"您好", 4).unwrap(), "您好...");
-        assert_eq!(truncate(&"您好", 6).unwrap(), "您好");
-        assert_eq!(truncate(&"您好", 7).unwrap(), "您好");
+        assert_eq!(truncate(&"您好", 1, "…", false).unwrap(), "您…");
+        assert_eq!(truncate(&"您好", 2, "…", false).unwrap(), "您好");
+        assert_eq!(truncate(&"您好", 3, "…", false).unwrap(), "您好");
         let s = String::from("🤚a🤚");
         assert_eq!(s.len(), 9);
         assert_eq!(String::from("🤚").len(), 4);
-        assert_eq!(truncate(&"🤚a🤚", 1).unwrap(), "🤚...");
-        assert_eq!(truncate(&"🤚a🤚", 2).unwrap(), "🤚...");
-        assert_eq!(truncate(&"🤚a🤚", 3).unwrap(), "🤚...");
-        assert_eq!(truncate(&"🤚a🤚", 4).unwrap(), "🤚...");
-        assert_eq!(truncate(&"🤚a🤚", 5).unwrap(), "🤚a...");
-        assert_eq!(truncate(&"🤚a🤚", 6).unwrap(), "🤚a🤚...");
-        assert_eq!(truncate(&"🤚a🤚", 9).unwrap(), "🤚a🤚");
-        assert_eq!(truncate(&"🤚a🤚", 10).unwrap(), "🤚a🤚");
+        assert_eq!(truncate(&"🤚a🤚", 1, "…", false).unwrap(), "🤚…");
+        assert_eq!(truncate(&"🤚a🤚", 2, "…", false).unwrap(), "🤚a…");
+        assert_eq!(truncate(&"🤚a🤚", 3, "…", false).unwrap(), "🤚a🤚");
+        assert_eq!(truncate(&"🤚a🤚", 10, "…", false).unwrap(), "🤚a🤚");
+    }
+
+    #[test]
+    fn test_truncatewords() {
+        let s = "The  quick\tbrown\n fox";
+        assert_eq!(truncatewords(s, 2, "…").unwrap(), "The quick…");
+        assert_eq!(
+            truncatewords(s, 3, " [...]").unwrap(),
+            "The quick brown [...]"
+        );
+        assert_eq!(truncatewords(s, 4, "…").unwrap(), "The quick brown fox");
+        assert_eq!(truncatewords(s, 10, "…").unwrap(), "The quick brown fox");
+        assert_eq!(truncatewords(s, 0, "…").unwrap(), "…");
+        assert_eq!(truncatewords("  ", 0, "…").unwrap(), "");
+        assert_eq!(truncatewords(" fox ", 1, "…").unwrap(), "fox");
+    }
+
+    #[test]
+    fn test_replace() {
+        assert_eq!(replace("a-b-c", "-", "+", usize::MAX).unwrap(), "a+b+c");
+        assert_eq!(replace("a-b-c", "-", "", 1).unwrap(), "ab-c");
+        assert_eq!(replace("a-b-c", "-", "+", 0).unwrap(), "a-b-c");
+        assert_eq!(replace("a-b-c", "x", "+", usize::MAX).unwrap(), "a-b-c");
+        assert_eq!(replace("aaa", "aa", "b", usize::MAX).unwrap(), "ba");
+        assert_eq!(
+            replace(42, "2", String::from("3"), usize::MAX).unwrap(),
+            "43"
+        );
+    }
+
+    #[test]
+    fn test_striptags() {
+        assert_eq!(
+            striptags("<p>Hello,\n  <b>world</b>!</p>").unwrap(),
+            "Hello, world!"
+        );
+        assert_eq!(
+            striptags("  <div class=\"a\">a</div>\n\n<div>b</div>  ").unwrap(),
+            "a b"
+        );
+        assert_eq!(striptags("a<!-- <b> > c -->d").unwrap(), "ad");
+        assert_eq!(striptags("a < b and <i>c</i>").unwrap(), "a < b and c");
+        assert_eq!(striptags("x <y").unwrap(), "x <y");
+        assert_eq!(
+            striptags("Tom &amp; Jerry &lt;3 &#39;&#x41;&quot; &copy; &#xZZ; &").unwrap(),
+            "Tom & Jerry <3 'A\" &copy; &#xZZ; &"
+        );
+        assert_eq!(striptags("").unwrap(), "");
+    }
+
+    #[test]
+    fn test_truncate_at_word() {
+        let s = "The quick brown fox";
+        assert_eq!(truncate(s, 12, "…", true).unwrap(), "The quick…");
+        assert_eq!(truncate(s, 10, "…", true).unwrap(), "The quick…");
+        assert_eq!(truncate(s, 9, "…", true).unwrap(), "The quick…");
+        assert_eq!(truncate(s, 8, "…", true).unwrap(), "The…");
+        assert_eq!(truncate(s, 12, "…", false).unwrap(), "The quick br…");
+        assert_eq!(truncate(s, 19, "…", true).unwrap(), s);
+        // a single long word still gets cut
+        assert_eq!(
+            truncate("Supercalifragilistic", 5, "…", true).unwrap(),
+            "Super…"
+        );
+        assert_eq!(truncate("ab  cd ef", 5, "...", true).unwrap(), "ab...");
     }
 
     #[test]
@@ -530,6 +2446,18 @@ mod tests {
             indent(&"hello\nfoo\n bar", 4).unwrap(),
             "hello\n    foo\n     bar"
         );
+        assert_eq!(indent("a\n\nb\n", 2).unwrap(), "a\n\n  b\n");
+        assert_eq!(indent("a\r\nb\r\n", 2).unwrap(), "a\r\n  b\r\n");
+    }
+
+    #[test]
+    fn test_indent_lines() {
+        assert_eq!(indent_lines("a\nb", 2, true, false).unwrap(), "  a\n  b");
+        assert_eq!(
+            indent_lines("a\n\r\nb", 2, false, true).unwrap(),
+            "a\n  \r\n  b"
+        );
+        assert_eq!(indent_lines("\na", 1, true, false).unwrap(), "\n a");
     }
 
     #[cfg(feature = "num-traits")]
@@ -557,41 +2485,346 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_dictsort() {
+        let map: HashMap<_, _> = vec![("b", 1), ("c", 3), ("a", 2)].into_iter().collect();
+        assert_eq!(
+            dictsort(&map, false).unwrap(),
+            vec![(&"a", &2), (&"b", &1), (&"c", &3)]
+        );
+        assert_eq!(
+            dictsort(&map, true).unwrap(),
+            vec![(&"c", &3), (&"b", &1), (&"a", &2)]
+        );
+        assert_eq!(
+            dictsort_by_value(&map, false).unwrap(),
+            vec![(&"b", &1), (&"a", &2), (&"c", &3)]
+        );
+
+        let map: HashMap<_, _> = vec![("b", 0), ("a", 0), ("c", 1)].into_iter().collect();
+        assert_eq!(
+            dictsort_by_value(&map, true).unwrap(),
+            vec![(&"c", &1), (&"b", &0), (&"a", &0)]
+        );
+    }
+
+    #[test]
+    fn test_wordwrap() {
+        assert_eq!(wordwrap("", 10, "\n", false).unwrap(), "");
+        assert_eq!(
+            wordwrap(
+                "the quick brown fox jumps over the lazy dog",
+                10,
+                "\n",
+                false
+            )
+            .unwrap(),
+            "the quick\nbrown fox\njumps over\nthe lazy\ndog"
+        );
+        assert_eq!(
+            wordwrap("one two\n\nthree  four", 7, "<br>", false).unwrap(),
+            "one two\n\nthree<br>four"
+        );
+        assert_eq!(
+            wordwrap("a verylongword b", 4, "\n", false).unwrap(),
+            "a\nverylongword\nb"
+        );
+        assert_eq!(
+            wordwrap("a verylongword b", 4, "\n", true).unwrap(),
+            "a\nvery\nlong\nword\nb"
+        );
+        assert_eq!(
+            wordwrap("ünïcödé wörds", 7, "\n", false).unwrap(),
+            "ünïcödé\nwörds"
+        );
+    }
+
+    #[test]
+    fn test_groupby() {
+        let words = ["apple", "bean", "avocado", "cherry", "banana"];
+        assert_eq!(
+            groupby(words, |w| w.chars().next()).unwrap(),
+            vec![
+                (Some('a'), vec!["apple", "avocado"]),
+                (Some('b'), vec!["bean", "banana"]),
+                (Some('c'), vec!["cherry"]),
+            ]
+        );
+
+        let empty: &[u32] = &[];
+        assert!(groupby(empty, |n| *n % 2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_default() {
+        let name: Option<String> = None;
+        assert_eq!(default(&name, "anon").unwrap(), "anon");
+        let name = Some(String::from("Ann"));
+        assert_eq!(default(&name, "anon").unwrap(), "Ann");
+        assert_eq!(default(&Some(2), &1).unwrap(), &2);
+        assert_eq!(default(&None::<i32>, &1).unwrap(), &1);
+
+        let empty = Some(String::new());
+        assert_eq!(default(&empty, "-").unwrap(), "");
+        assert_eq!(default_if_empty(&empty, "-", false).unwrap(), "");
+        assert_eq!(default_if_empty(&empty, "-", true).unwrap(), "-");
+        assert_eq!(default_if_empty("", "-", true).unwrap(), "-");
+        assert_eq!(default_if_empty("x", "-", true).unwrap(), "x");
+        let v: Vec<u8> = vec![];
+        assert_eq!(default_if_empty(&v, &vec![1], true).unwrap(), &[1]);
+    }
+
+    #[test]
+    fn test_sort() {
+        assert_eq!(sort(&[3, 1, 2], false).unwrap(), vec![&1, &2, &3]);
+        assert_eq!(sort(vec![3, 1, 2], true).unwrap(), vec![3, 2, 1]);
+
+        let pairs = [(2, "a"), (1, "b"), (2, "c")];
+        let by_first = |a: &&(i32, &str), b: &&(i32, &str)| a.0.cmp(&b.0);
+        assert_eq!(
+            sort_by(&pairs, false, by_first).unwrap(),
+            vec![&(1, "b"), &(2, "a"), &(2, "c")]
+        );
+        assert_eq!(
+            sort_by(&pairs, true, by_first).unwrap(),
+            vec![&(2, "a"), &(2, "c"), &(1, "b")]
+        );
+
+        let words = ["b", "C", "a"];
+        assert_eq!(sort(&words, false).unwrap(), vec![&"C", &"a", &"b"]);
+        assert_eq!(
+            sort_by(&words, false, cmp_ignore_case).unwrap(),
+            vec![&"a", &"b", &"C"]
+        );
+    }
+
+    #[test]
+    fn test_reverse() {
+        assert_eq!(reverse("").unwrap(), "");
+        assert_eq!(reverse("abc").unwrap(), "cba");
+        assert_eq!(
+            reverse(&String::from("héllo wörld")).unwrap(),
+            "dlröw olléh"
+        );
+        assert_eq!(reverse("日本語").unwrap(), "語本日");
+        assert_eq!(reverse(&vec![1, 2, 3]).unwrap(), vec![&3, &2, &1]);
+        assert_eq!(reverse(&["a", "b"][..]).unwrap(), vec![&"b", &"a"]);
+        assert!(reverse(&Vec::<u8>::new()).unwrap().is_empty());
+    }
+
+    #[cfg(not(feature = "unicode-segmentation"))]
+    #[test]
+    fn test_reverse_chars() {
+        // the combining accent follows the character it belonged to
+        assert_eq!(reverse("ae\u{301}").unwrap(), "\u{301}ea");
+    }
+
+    #[cfg(feature = "unicode-segmentation")]
+    #[test]
+    fn test_reverse_graphemes() {
+        assert_eq!(reverse("ae\u{301}").unwrap(), "e\u{301}a");
+        assert_eq!(reverse("🇳🇴🇸🇪").unwrap(), "🇸🇪🇳🇴");
+    }
+
+    #[test]
+    fn test_unique() {
+        assert_eq!(unique(vec![3, 1, 3, 2, 1]).unwrap(), vec![3, 1, 2]);
+        assert_eq!(unique(&["b", "a", "b"]).unwrap(), vec![&"b", &"a"]);
+        assert!(unique(Vec::<u8>::new()).unwrap().is_empty());
+
+        let users = vec![("ann", "no"), ("bob", "se"), ("cid", "no")];
+        assert_eq!(
+            unique_by(&users, |user| user.1).unwrap(),
+            vec![&("ann", "no"), &("bob", "se")]
+        );
+    }
+
+    #[test]
+    fn test_map() {
+        let pairs = [(1, "a"), (2, "b")];
+        assert_eq!(map(&pairs, |p| &p.1).unwrap(), vec![&"a", &"b"]);
+        assert_eq!(map(0..3, |n| n * 2).unwrap(), vec![0, 2, 4]);
+    }
+
+    #[test]
+    fn test_select_reject() {
+        let users = [("ann", true), ("bob", false), ("cid", true)];
+        assert_eq!(
+            select(&users, |user| user.1).unwrap(),
+            vec![&("ann", true), &("cid", true)]
+        );
+        assert_eq!(
+            reject(&users, |user| user.1).unwrap(),
+            vec![&("bob", false)]
+        );
+        assert_eq!(select(1..=6, |n| n % 3 == 0).unwrap(), vec![3, 6]);
+        assert!(reject(Vec::<u8>::new(), |_| false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_batch() {
+        assert_eq!(
+            batch(1..=7, 3).unwrap(),
+            vec![vec![1, 2, 3], vec![4, 5, 6], vec![7]]
+        );
+        assert_eq!(batch(&[1, 2], 2).unwrap(), vec![vec![&1, &2]]);
+        assert!(batch(Vec::<u8>::new(), 2).unwrap().is_empty());
+        assert!(batch(vec![1], 0).is_err());
+
+        assert_eq!(
+            batch_fill(vec!["a", "b", "c"], 2, "-").unwrap(),
+            vec![vec!["a", "b"], vec!["c", "-"]]
+        );
+        assert!(batch_fill(Vec::<u8>::new(), 2, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sum() {
+        assert_eq!(sum(vec![1, 2, 3]).unwrap(), 6);
+        assert_eq!(sum(&[1.5, 2.0]).unwrap(), 3.5);
+        assert_eq!(sum(&vec![&1u8, &2]).unwrap(), 3u8);
+        assert_eq!(sum(Vec::<u32>::new()).unwrap(), 0);
+        assert_eq!(sum_from(vec![1.0, 2.0], 0.5).unwrap(), 3.5);
+        assert_eq!(sum_from(vec![1, 2], 10).unwrap(), 13);
+    }
+
+    #[test]
+    fn test_max_min() {
+        assert_eq!(max(vec![3, 1, 4, 1]).unwrap(), Some(4));
+        assert_eq!(min(vec![3, 1, 4, 1]).unwrap(), Some(1));
+        assert_eq!(max(&["b", "c", "a"]).unwrap(), Some(&"c"));
+        assert_eq!(min(&[2.5, f64::NAN, -1.0]).unwrap(), Some(&-1.0));
+        assert_eq!(max(Vec::<u8>::new()).unwrap(), None);
+        assert_eq!(min(Vec::<u8>::new()).unwrap(), None);
+
+        let pairs = [(1, "a"), (2, "b"), (2, "c")];
+        assert_eq!(max(&pairs).unwrap(), Some(&(2, "c")));
+        assert_eq!(min(map(&pairs, |p| &p.0).unwrap()).unwrap(), Some(&1));
+    }
+
+    #[test]
+    fn test_slice() {
+        assert_eq!(slice("hello", 1, Some(3)).unwrap(), "el");
+        assert_eq!(slice("hello", 2, None).unwrap(), "llo");
+        assert_eq!(slice("hello", -3, Some(-1)).unwrap(), "ll");
+        assert_eq!(slice("hello", -10, Some(10)).unwrap(), "hello");
+        assert_eq!(slice("hello", 4, Some(2)).unwrap(), "");
+        assert_eq!(slice("hello", 5, None).unwrap(), "");
+        assert_eq!(slice("héllø wörld", 1, Some(4)).unwrap(), "éll");
+        assert_eq!(slice("héllø wörld", -5, None).unwrap(), "wörld");
+        assert_eq!(slice(&"ab".to_string(), 1, None).unwrap(), "b");
+
+        let v = vec![1, 2, 3, 4];
+        assert_eq!(slice(&v, 1, Some(3)).unwrap(), &[2, 3]);
+        assert_eq!(slice(&v, -2, None).unwrap(), &[3, 4]);
+        assert_eq!(slice(&v[..], 3, Some(100)).unwrap(), &[4]);
+        assert_eq!(slice(&v, 10, None).unwrap(), &[] as &[i32]);
+    }
+
+    #[test]
+    fn test_first_last() {
+        let v = vec![1, 2, 3];
+        assert_eq!(first(&v).unwrap(), Some(&1));
+        assert_eq!(last(&v).unwrap(), Some(&3));
+        assert_eq!(first(&["a"]).unwrap(), Some(&"a"));
+
+        let empty: &[u32] = &[];
+        assert_eq!(first(&empty).unwrap(), None);
+        assert_eq!(last(empty).unwrap(), None);
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_random() {
+        let v = vec![1, 2, 3];
+        for _ in 0..10 {
+            assert!(v.contains(random(&v).unwrap().unwrap()));
+        }
+        let picked = random_seeded(&v, 42).unwrap();
+        assert!(picked.is_some());
+        for _ in 0..10 {
+            assert_eq!(random_seeded(&v, 42).unwrap(), picked);
+        }
+        assert_eq!(random(&["a"]).unwrap(), Some(&"a"));
+
+        let empty: &[u32] = &[];
+        assert_eq!(random(empty).unwrap(), None);
+        assert_eq!(random_seeded(empty, 42).unwrap(), None);
+    }
+
     #[allow(clippy::needless_borrow)]
     #[test]
     fn test_join() {
         assert_eq!(
-            join((&["hello", "world"]).iter(), ", ").unwrap(),
+            join(Text, (&["hello", "world"]).iter(), ", ")
+                .unwrap()
+                .to_string(),
             "hello, world"
         );
-        assert_eq!(join((&["hello"]).iter(), ", ").unwrap(), "hello");
+        assert_eq!(
+            join(Text, (&["hello"]).iter(), ", ").unwrap().to_string(),
+            "hello"
+        );
 
         let empty: &[&str] = &[];
-        assert_eq!(join(empty.iter(), ", ").unwrap(), "");
+        assert_eq!(join(Text, empty.iter(), ", ").unwrap().to_string(), "");
 
         let input: Vec<String> = vec!["foo".into(), "bar".into(), "bazz".into()];
         assert_eq!(
-            join((&input).iter(), ":".to_string()).unwrap(),
+            join(Text, (&input).iter(), ":".to_string())
+                .unwrap()
+                .to_string(),
+            "foo:bar:bazz"
+        );
+        assert_eq!(
+            join(Text, input.iter(), ":").unwrap().to_string(),
+            "foo:bar:bazz"
+        );
+        assert_eq!(
+            join(Text, input.iter(), ":".to_string())
+                .unwrap()
+                .to_string(),
             "foo:bar:bazz"
         );
-        assert_eq!(join(input.iter(), ":").unwrap(), "foo:bar:bazz");
-        assert_eq!(join(input.iter(), ":".to_string()).unwrap(), "foo:bar:bazz");
 
         let input: &[String] = &["foo".into(), "bar".into()];
-        assert_eq!(join(input.iter(), ":").unwrap(), "foo:bar");
-        assert_eq!(join(input.iter(), ":".to_string()).unwrap(), "foo:bar");
+        assert_eq!(
+            join(Text, input.iter(), ":").unwrap().to_string(),
+            "foo:bar"
+        );
+        assert_eq!(
+            join(Text, input.iter(), ":".to_string())
+                .unwrap()
+                .to_string(),
+            "foo:bar"
+        );
 
         let real: String = "blah".into();
         let input: Vec<&str> = vec![&real];
-        assert_eq!(join(input.iter(), ";").unwrap(), "blah");
+        assert_eq!(join(Text, input.iter(), ";").unwrap().to_string(), "blah");
 
         assert_eq!(
-            join((&&&&&["foo", "bar"]).iter(), ", ").unwrap(),
+            join(Text, (&&&&&["foo", "bar"]).iter(), ", ")
+                .unwrap()
+                .to_string(),
             "foo, bar"
         );
+
+        assert_eq!(
+            join(Html, ["<a>", "b&c"].iter(), "<br>")
+                .unwrap()
+                .to_string(),
+            "&lt;a&gt;<br>b&amp;c"
+        );
+        let pairs = [(1, "x"), (2, "<y>")];
+        assert_eq!(
+            join_by(Html, pairs.iter(), ", ", |p| &p.1)
+                .unwrap()
+                .to_string(),
+            "x, &lt;y&gt;"
+        );
     }
 
-    #[cfg(feature = "num-traits")]
     #[test]
     #[allow(clippy::float_cmp)]
     fn test_abs() {
@@ -601,6 +2834,44 @@ mod tests {
         assert_eq!(abs(-1.0).unwrap(), 1.0);
         assert_eq!(abs(1.0_f64).unwrap(), 1.0_f64);
         assert_eq!(abs(-1.0_f64).unwrap(), 1.0_f64);
+        assert_eq!(abs(&-3_i8).unwrap(), 3);
+        assert_eq!(abs(i32::MIN + 1).unwrap(), i32::MAX);
+        assert_eq!(
+            abs(i32::MIN).unwrap_err().to_string(),
+            "custom error: abs overflowed"
+        );
+    }
+
+    #[cfg(feature = "num-traits")]
+    #[test]
+    fn test_abs_source() {
+        use std::num::Wrapping;
+
+        assert_eq!(abs(AbsSource(&-2_i64).abs_source()).unwrap(), 2);
+        assert_eq!(abs(AbsSource(&-0.5).abs_source()).unwrap(), 0.5);
+        // Not one of the types implementing `Abs`, but `Signed`.
+        let wrapping = Wrapping(-3_i32);
+        assert_eq!(abs(AbsSource(&wrapping).abs_source()).unwrap(), Wrapping(3));
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn test_round() {
+        assert_eq!(round(2.5, 0).unwrap(), 3.0);
+        assert_eq!(round(-2.5, 0).unwrap(), -3.0);
+        assert_eq!(round(&1.23456, 2).unwrap(), 1.23);
+        assert_eq!(round(2.5_f32, 0).unwrap(), 3.0_f32);
+        assert_eq!(round(1.005_f32, 1).unwrap(), 1.0_f32);
+        assert_eq!(round(1234.5, -2).unwrap(), 1200.0);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn test_floor_ceil() {
+        assert_eq!(floor(2.7).unwrap(), 2.0);
+        assert_eq!(floor(-2.2).unwrap(), -3.0);
+        assert_eq!(ceil(2.2_f32).unwrap(), 3.0_f32);
+        assert_eq!(ceil(&-2.7).unwrap(), -2.0);
     }
 
     #[test]
@@ -611,14 +2882,76 @@ mod tests {
         assert_eq!(capitalize(&"").unwrap(), "".to_string());
         assert_eq!(capitalize(&"FoO").unwrap(), "Foo".to_string());
         assert_eq!(capitalize(&"foO BAR").unwrap(), "Foo bar".to_string());
+        assert_eq!(capitalize("ÉCOLE").unwrap(), "École".to_string());
+        assert_eq!(capitalize("über ÄLLES").unwrap(), "Über älles".to_string());
+        assert_eq!(capitalize("ßTRASSE").unwrap(), "Sstrasse".to_string());
+        assert_eq!(capitalize("ΣΟΦΟΣ").unwrap(), "Σοφος".to_string());
+        assert_eq!(capitalize(" foo").unwrap(), " foo".to_string());
+    }
+
+    #[test]
+    fn test_title() {
+        assert_eq!(title("").unwrap(), "".to_string());
+        assert_eq!(title("foo").unwrap(), "Foo".to_string());
+        assert_eq!(title("foO BAR").unwrap(), "Foo Bar".to_string());
+        assert_eq!(
+            title("  two  spaces ").unwrap(),
+            "  Two  Spaces ".to_string()
+        );
+        assert_eq!(
+            title("jean-luc (PICARD) <é>").unwrap(),
+            "Jean-Luc (Picard) <É>".to_string()
+        );
+        assert_eq!(title("élan vital").unwrap(), "Élan Vital".to_string());
+        assert_eq!(title("ßa straße").unwrap(), "Ssa Straße".to_string());
+        assert_eq!(title("it's o'neil").unwrap(), "It's O'neil".to_string());
     }
 
     #[test]
     fn test_center() {
-        assert_eq!(center(&"f", 3).unwrap(), " f ".to_string());
-        assert_eq!(center(&"f", 4).unwrap(), " f  ".to_string());
-        assert_eq!(center(&"foo", 1).unwrap(), "foo".to_string());
-        assert_eq!(center(&"foo bar", 8).unwrap(), "foo bar ".to_string());
+        assert_eq!(center(&"f", 3, ' ').unwrap(), " f ".to_string());
+        assert_eq!(center(&"f", 4, ' ').unwrap(), " f  ".to_string());
+        assert_eq!(center(&"foo", 1, ' ').unwrap(), "foo".to_string());
+        assert_eq!(center(&"foo bar", 8, ' ').unwrap(), "foo bar ".to_string());
+        assert_eq!(center(&"é", 5, '*').unwrap(), "**é**".to_string());
+    }
+
+    #[test]
+    fn test_ljust_rjust() {
+        assert_eq!(ljust(&"ab", 5, ' ').unwrap(), "ab   ");
+        assert_eq!(rjust(&"ab", 5, ' ').unwrap(), "   ab");
+        assert_eq!(rjust(&42, 5, '0').unwrap(), "00042");
+        assert_eq!(ljust(&"äöü", 4, '·').unwrap(), "äöü·");
+        assert_eq!(ljust(&"toolong", 3, ' ').unwrap(), "toolong");
+    }
+
+    #[cfg(feature = "unicode-width")]
+    #[test]
+    fn test_unicode_width() {
+        assert_eq!(center(&"日本", 6, ' ').unwrap(), " 日本 ");
+        assert_eq!(ljust(&"日本", 5, '.').unwrap(), "日本.");
+    }
+
+    #[test]
+    fn test_pluralize() {
+        assert_eq!(pluralize(0, "", "s").unwrap(), "s");
+        assert_eq!(pluralize(1u8, "", "s").unwrap(), "");
+        assert_eq!(pluralize(2i64, "", "s").unwrap(), "s");
+        assert_eq!(pluralize(-1, "", "s").unwrap(), "s");
+        assert_eq!(pluralize(1usize, "y", "ies").unwrap(), "y");
+        assert_eq!(pluralize(u128::MAX, "y", "ies").unwrap(), "ies");
+    }
+
+    #[test]
+    fn test_length() {
+        assert_eq!(length("été").unwrap(), 3);
+        assert_eq!(bytelength("été").unwrap(), 5);
+        assert_eq!(length(&String::from("ok")).unwrap(), 2);
+        assert_eq!(length(&[1, 2, 3]).unwrap(), 3);
+        assert_eq!(length(&vec!["a"][..]).unwrap(), 1);
+        let map: HashMap<_, _> = vec![("a", 1), ("b", 2)].into_iter().collect();
+        assert_eq!(length(&&map).unwrap(), 2);
+        assert_eq!(length(&BTreeSet::<u8>::new()).unwrap(), 0);
     }
 
     #[test]
@@ -627,5 +2960,7 @@ mod tests {
         assert_eq!(wordcount(&" \n\t").unwrap(), 0);
         assert_eq!(wordcount(&"foo").unwrap(), 1);
         assert_eq!(wordcount(&"foo bar").unwrap(), 2);
+        assert_eq!(wordcount("  foo \t bar\n\nbaz  ").unwrap(), 3);
+        assert_eq!(wordcount("l'été—est chaud").unwrap(), 2);
     }
 }
@@ -1,16 +1,21 @@
-use super::{get_template_source, CompileError, Integrations};
+use super::{CompileError, Integrations, WhitespaceHandling};
 use crate::filters;
 use crate::heritage::{Context, Heritage};
-use crate::input::{Source, TemplateInput};
-use crate::parser::{parse, Cond, CondTest, Expr, Loop, Node, Target, When, Ws};
+use crate::input::{IncludeMode, Source, TemplateInput};
+use crate::parser::{
+    parse_with_path, source_message, Cond, CondTest, Expr, Loop, Node, Target, When, Ws,
+};
 
 use proc_macro2::Span;
 
 use quote::{quote, ToTokens};
 
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
-use std::path::PathBuf;
-use std::{cmp, hash, mem, str};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::{cmp, fs, hash, mem, str};
 
 pub fn generate<S: std::hash::BuildHasher>(
     input: &TemplateInput<'_>,
@@ -40,12 +45,46 @@ struct Generator<'a, S: std::hash::BuildHasher> {
     // Whitespace suppression from the previous non-literal. Will be used to
     // determine whether to flush prefix whitespace from the next literal.
     skip_ws: bool,
+    // With `trim_blocks`, whether the previous non-literal was a block tag,
+    // so that a newline right after it is removed from the next literal.
+    trim_newline: bool,
+    // With `lstrip_blocks`, the length of the spaces and tabs ending `next_ws`
+    // after a newline, which are removed if a block tag follows.
+    next_ws_indent: Option<usize>,
     // If currently in a block, this will contain the name of a potential parent block
     super_block: Option<(&'a str, usize)>,
     // buffer for writable
     buf_writable: Vec<Writable<'a>>,
-    // Counter for write! hash named arguments
-    named: usize,
+    // Number of `for` loops the code being generated is nested in
+    loop_depth: usize,
+    // Render-time additions to `Template::size_hint()`, for loops over fields
+    // of the template struct (whose length is known before rendering)
+    dynamic_size_hints: Vec<String>,
+    // Paths of the template struct's fields referenced so far, such as `user.name`
+    fields: Vec<String>,
+    // How whitespace is handled; minimization is turned off inside raw blocks
+    whitespace: WhitespaceHandling,
+    // Whether the literals so far left an HTML `<pre>` element open, inside
+    // which whitespace is never minimized
+    in_pre: bool,
+    // Path of the template whose nodes are being generated, which may be an
+    // included or parent template; used to name it in render errors
+    template: Option<&'a Path>,
+    // Methods rendering included templates, by name, with
+    // `include_mode = "function"`
+    include_fns: Vec<(String, String)>,
+    // Parameters of the closures the expression being generated is in, which
+    // shadow the template's variables
+    closure_params: Vec<String>,
+    // Sources of the templates included so far, to locate errors in; the
+    // others are found in `contexts`
+    include_sources: Vec<(Rc<str>, PathBuf)>,
+    // Warnings and notes (`true`) held back while trying out code that may be
+    // thrown away
+    deferred_diagnostics: Option<Vec<(CompileError, bool)>>,
+    // Whether the expression visited next may be a path into the `dynamic`
+    // field: it is written to the output, or formatted by a filter
+    dynamic_allowed: bool,
 }
 
 impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
@@ -64,21 +103,37 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
             locals,
             next_ws: None,
             skip_ws: false,
+            trim_newline: false,
+            next_ws_indent: None,
             super_block: None,
             buf_writable: vec![],
-            named: 0,
+            loop_depth: 0,
+            dynamic_size_hints: vec![],
+            fields: vec![],
+            whitespace: input.whitespace,
+            in_pre: false,
+            template: None,
+            include_fns: vec![],
+            closure_params: vec![],
+            include_sources: vec![],
+            deferred_diagnostics: None,
+            dynamic_allowed: false,
         }
     }
 
     fn child(&mut self) -> Generator<'_, S> {
         let locals = MapChain::with_parent(&self.locals);
-        Self::new(
+        let mut child = Self::new(
             self.input,
             self.contexts,
             self.heritage,
             self.integrations,
             locals,
-        )
+        );
+        child.loop_depth = self.loop_depth;
+        child.in_pre = self.in_pre;
+        child.include_sources = self.include_sources.clone();
+        child
     }
 
     // Takes a Context and generates the relevant implementations.
@@ -90,7 +145,13 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
             }
         };
 
+        if self.input.is_async {
+            self.impl_render_async(ctx, &mut buf)?;
+            return Ok(buf.buf);
+        }
+
         self.impl_template(ctx, &mut buf)?;
+        self.impl_include_fns(&mut buf)?;
         self.impl_display(&mut buf)?;
 
         if self.integrations.actix {
@@ -131,14 +192,131 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
             "fn render_into(&self, writer: &mut dyn ::std::fmt::Write) -> \
              ::askama::Result<()> {",
         )?;
+        let size_hint = self.write_render_body(ctx, buf)?;
+        buf.writeln("Ok(())")?;
+        buf.writeln("}")?;
+
+        buf.writeln("fn extension(&self) -> Option<&'static str> {")?;
+        buf.writeln(&format!("{:?}", self.input.extension()))?;
+        buf.writeln("}")?;
+
+        buf.writeln("fn mime_type(&self) -> &'static str {")?;
+        buf.writeln(&format!("{:?}", self.input.mime_type))?;
+        buf.writeln("}")?;
+
+        buf.writeln("fn size_hint(&self) -> usize {")?;
+        buf.writeln(&self.size_hint_expr(size_hint))?;
+        buf.writeln("}")?;
+
+        buf.writeln("}")?;
+
+        self.write_header(buf, "::askama::SizedTemplate", None)?;
+
+        buf.writeln("fn size_hint() -> usize {")?;
+        buf.writeln(&format!("{}", size_hint))?;
+        buf.writeln("}")?;
+
+        buf.writeln("fn extension() -> Option<&'static str> {")?;
+        buf.writeln(&format!("{:?}", self.input.extension()))?;
+        buf.writeln("}")?;
+
+        buf.writeln("fn mime_type() -> &'static str {")?;
+        buf.writeln(&format!("{:?}", self.input.mime_type))?;
+        buf.writeln("}")?;
+
+        let mut fields = self.fields.clone();
+        fields.sort();
+        fields.dedup();
+        buf.writeln(&format!(
+            "const TEMPLATE_FIELDS: &'static [&'static str] = &{:?};",
+            fields
+        ))?;
+
+        buf.writeln("}")?;
+        Ok(())
+    }
+
+    // Implement the methods rendering included templates, which are shared
+    // by all include sites that generated the same code.
+    fn impl_include_fns(&mut self, buf: &mut Buffer) -> Result<(), CompileError> {
+        if self.include_fns.is_empty() {
+            return Ok(());
+        }
+        self.include_fns.sort();
+        self.include_fns.dedup();
+
+        let (impl_generics, ty_generics, where_clause) = self.input.ast.generics.split_for_impl();
+        buf.writeln(ALLOWED_LINTS)?;
+        buf.writeln(&format!(
+            "{} {}{} {{",
+            quote!(impl#impl_generics),
+            self.input.ast.ident,
+            quote!(#ty_generics #where_clause),
+        ))?;
+        for (name, body) in &self.include_fns {
+            buf.writeln(&format!(
+                "fn {}(&self, writer: &mut dyn ::std::fmt::Write) -> ::askama::Result<()> {{",
+                name
+            ))?;
+            buf.writeln("use ::askama::helpers::{WriteDisplay as _, WriteFast as _};")?;
+            buf.write(body);
+            buf.writeln("Ok(())")?;
+            buf.writeln("}")?;
+        }
+        buf.writeln("}")?;
+        Ok(())
+    }
+
+    // Implement an inherent `render_async()` for templates declared with
+    // `async = true`, whose expressions may contain `.await`. Such templates
+    // don't implement `Template`, as they can't be rendered synchronously.
+    fn impl_render_async(
+        &mut self,
+        ctx: &'a Context<'_>,
+        buf: &mut Buffer,
+    ) -> Result<(), CompileError> {
+        let (impl_generics, ty_generics, where_clause) = self.input.ast.generics.split_for_impl();
+        buf.writeln(ALLOWED_LINTS)?;
+        buf.writeln(&format!(
+            "{} {}{} {{",
+            quote!(impl#impl_generics),
+            self.input.ast.ident,
+            quote!(#ty_generics #where_clause),
+        ))?;
+        buf.writeln(
+            "pub async fn render_async(&self) -> ::askama::Result<::std::string::String> {",
+        )?;
+
+        // The capacity depends on the size hint, which is only known once
+        // the body has been generated.
+        let mut body = Buffer::new(buf.indent);
+        body.writeln("let writer = &mut _buf;")?;
+        let size_hint = self.write_render_body(ctx, &mut body)?;
+        buf.writeln("use ::std::fmt::Write as _;")?;
+        buf.writeln(&format!(
+            "let mut _buf = ::std::string::String::with_capacity({});",
+            self.size_hint_expr(size_hint)
+        ))?;
+        buf.write(&body.buf);
+        buf.writeln("Ok(_buf)")?;
+        buf.writeln("}")?;
+        buf.writeln("}")
+    }
 
+    // Write the code rendering the template into `writer`, returning the
+    // static part of its size hint.
+    fn write_render_body(
+        &mut self,
+        ctx: &'a Context<'_>,
+        buf: &mut Buffer,
+    ) -> Result<usize, CompileError> {
         // Make sure the compiler understands that the generated code depends on the template files.
         for path in self.contexts.keys() {
             // Skip the fake path of templates defined in rust source.
             let path_is_valid = match self.input.source {
                 Source::Path(_) => true,
                 Source::Source(_) => *path != &self.input.path,
-            };
+            } && !self.input.config.sources.contains_key(*path);
             if path_is_valid {
                 let path = path.to_str().unwrap();
                 buf.writeln(
@@ -150,38 +328,113 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
             }
         }
 
-        let size_hint = if let Some(heritage) = self.heritage {
-            self.handle(heritage.root, heritage.root.nodes, buf, AstLevel::Top)
-        } else {
-            self.handle(ctx, ctx.nodes, buf, AstLevel::Top)
-        }?;
-
-        self.flush_ws(Ws(false, false));
-        buf.writeln("Ok(())")?;
-        buf.writeln("}")?;
+        buf.writeln("use ::askama::helpers::{WriteDisplay as _, WriteFast as _};")?;
+        if !self.input.minify {
+            return self.write_render_extends(ctx, buf);
+        }
 
-        buf.writeln("fn extension(&self) -> Option<&'static str> {")?;
-        buf.writeln(&format!("{:?}", self.input.extension()))?;
-        buf.writeln("}")?;
+        // The minifier needs the whole output, so it is rendered into a
+        // buffer first.
+        #[cfg(not(feature = "minify"))]
+        return Err("the `minify` option requires the `minify` feature to be enabled".into());
+        #[cfg(feature = "minify")]
+        {
+            buf.writeln("let mut _minify_source = ::std::string::String::new();")?;
+            buf.writeln("{")?;
+            buf.writeln("let writer: &mut dyn ::std::fmt::Write = &mut _minify_source;")?;
+            let size_hint = self.write_render_extends(ctx, buf)?;
+            buf.writeln("}")?;
+            buf.writeln("writer.write_str(&::askama::helpers::minify(&_minify_source)?)?;")?;
+            Ok(size_hint)
+        }
+    }
 
-        buf.writeln("fn size_hint(&self) -> usize {")?;
-        buf.writeln(&format!("{}", size_hint))?;
-        buf.writeln("}")?;
+    // Write the code rendering the template with the parent it extends,
+    // which may depend on a condition.
+    fn write_render_extends(
+        &mut self,
+        ctx: &'a Context<'_>,
+        buf: &mut Buffer,
+    ) -> Result<usize, CompileError> {
+        if ctx.extends_if.is_empty() {
+            return self.write_render_nodes(ctx, buf);
+        }
 
+        // With a conditional `extends`, the template is rendered with each
+        // of its parents in a branch of its own.
+        let mut size_hint = 0;
+        for (i, (cond, parent)) in ctx.extends_if.iter().enumerate() {
+            buf.write(if i == 0 { "if " } else { "} else if " });
+            buf.write("*(&(");
+            let expr_code = self.visit_expr_root(cond)?;
+            buf.write(&expr_code);
+            buf.writeln(") as &bool) {")?;
+
+            let ctx = ctx.with_parent(parent);
+            let heritage = Some(Heritage::new(&ctx, self.contexts));
+            let mut gen = Generator::new(
+                self.input,
+                self.contexts,
+                &heritage,
+                self.integrations,
+                MapChain::with_parent(&self.locals),
+            );
+            size_hint = cmp::max(size_hint, gen.write_render_nodes(&ctx, buf)?);
+            let dynamic_size_hints = mem::take(&mut gen.dynamic_size_hints);
+            let fields = mem::take(&mut gen.fields);
+            let include_fns = mem::take(&mut gen.include_fns);
+            self.dynamic_size_hints.extend(dynamic_size_hints);
+            self.fields.extend(fields);
+            self.include_fns.extend(include_fns);
+        }
+        buf.writeln("} else {")?;
+        size_hint = cmp::max(size_hint, self.write_render_nodes(ctx, buf)?);
         buf.writeln("}")?;
+        Ok(size_hint)
+    }
 
-        self.write_header(buf, "::askama::SizedTemplate", None)?;
-
-        buf.writeln("fn size_hint() -> usize {")?;
-        buf.writeln(&format!("{}", size_hint))?;
-        buf.writeln("}")?;
+    // Write the code rendering the nodes of the template, or of the one block
+    // to render, as extended from its parents.
+    fn write_render_nodes(
+        &mut self,
+        ctx: &'a Context<'_>,
+        buf: &mut Buffer,
+    ) -> Result<usize, CompileError> {
+        let size_hint = if let Some(block) = &self.input.block {
+            // Render only the block, as defined by the most derived template.
+            let has_block = matches!(
+                self.heritage,
+                Some(heritage) if heritage.blocks.contains_key(block.as_str())
+            );
+            if !has_block {
+                return Err(format!("no block named '{}' found in the template", block).into());
+            }
+            let size_hint = self.write_block(buf, Some(block), Ws(false, false))?;
+            self.write_buf_writable(buf).map(|hint| size_hint + hint)
+        } else if let Some(heritage) = self.heritage {
+            self.handle_in(
+                &heritage.root.path,
+                heritage.root,
+                heritage.root.nodes,
+                buf,
+                AstLevel::Top,
+            )
+        } else {
+            self.handle_in(&ctx.path, ctx, ctx.nodes, buf, AstLevel::Top)
+        }?;
 
-        buf.writeln("fn extension() -> Option<&'static str> {")?;
-        buf.writeln(&format!("{:?}", self.input.extension()))?;
-        buf.writeln("}")?;
+        self.flush_ws_at(Ws(false, false), false);
+        Ok(size_hint)
+    }
 
-        buf.writeln("}")?;
-        Ok(())
+    // The render-time size hint: the static estimate plus the lengths of
+    // loops over fields of the template struct.
+    fn size_hint_expr(&self, size_hint: usize) -> String {
+        let mut expr = size_hint.to_string();
+        for hint in &self.dynamic_size_hints {
+            expr.push_str(&format!(" + {}", hint));
+        }
+        expr
     }
 
     // Implement `Deref<Parent>` for an inheriting context struct.
@@ -393,6 +646,7 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
         }
         let (_, orig_ty_generics, _) = self.input.ast.generics.split_for_impl();
         let (impl_generics, _, where_clause) = generics.split_for_impl();
+        buf.writeln(ALLOWED_LINTS)?;
         buf.writeln(
             format!(
                 "{} {} for {}{} {{",
@@ -407,6 +661,22 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
 
     /* Helper methods for handling node types */
 
+    // Handles nodes coming from the template at `path`, which is not
+    // necessarily the one `ctx` belongs to for included templates
+    fn handle_in(
+        &mut self,
+        path: &'a Path,
+        ctx: &'a Context<'_>,
+        nodes: &'a [Node<'_>],
+        buf: &mut Buffer,
+        level: AstLevel,
+    ) -> Result<usize, CompileError> {
+        let template = self.template.replace(path);
+        let size_hint = self.handle(ctx, nodes, buf, level);
+        self.template = template;
+        size_hint
+    }
+
     fn handle(
         &mut self,
         ctx: &'a Context<'_>,
@@ -423,6 +693,9 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
                 Node::Comment(ws) => {
                     self.write_comment(ws);
                 }
+                Node::OutputComment(ws, text) => {
+                    size_hint += self.write_output_comment(buf, ws, text)?;
+                }
                 Node::Expr(ws, ref val) => {
                     self.write_expr(ws, val);
                 }
@@ -433,19 +706,34 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
                     self.write_let(buf, ws, var, val)?;
                 }
                 Node::Cond(ref conds, ws) => {
-                    self.write_cond(ctx, buf, conds, ws)?;
+                    size_hint += self.write_cond(ctx, buf, conds, ws)?;
                 }
                 Node::Match(ws1, ref expr, ref arms, ws2) => {
-                    self.write_match(ctx, buf, ws1, expr, arms, ws2)?;
+                    size_hint += self.write_match(ctx, buf, ws1, expr, arms, ws2)?;
                 }
                 Node::Loop(ref loop_block) => {
-                    self.write_loop(ctx, buf, loop_block)?;
+                    size_hint += self.write_loop(ctx, buf, loop_block)?;
+                }
+                Node::FilterBlock(ws1, ref filter, ref nodes, ws2) => {
+                    size_hint += self.write_filter_block(ctx, buf, ws1, filter, nodes, ws2)?;
+                }
+                Node::Capture(ws1, ref var, ref nodes, ws2) => {
+                    self.write_capture(ctx, buf, ws1, var, nodes, ws2)?;
+                }
+                Node::Spaceless(ws1, ref nodes, ws2) => {
+                    size_hint += self.write_spaceless(ctx, buf, ws1, nodes, ws2)?;
                 }
-                Node::BlockDef(ws1, name, _, ws2) => {
-                    self.write_block(buf, Some(name), Ws(ws1.0, ws2.1))?;
+                Node::BlockDef(ws1, name, _, _, ws2) => {
+                    size_hint += self.write_block(buf, Some(name), Ws(ws1.0, ws2.1))?;
                 }
-                Node::Include(ws, path) => {
-                    size_hint += self.handle_include(ctx, buf, ws, path)?;
+                Node::Include(ws, ref paths, None, escape_as_parent) => {
+                    size_hint += self.handle_include(ctx, buf, ws, paths, escape_as_parent)?;
+                }
+                Node::Include(ws, ref paths, Some(ref include_ctx), _) => {
+                    self.write_include_with(buf, ws, paths, include_ctx)?;
+                }
+                Node::IncludeStr(ws, path) => {
+                    size_hint += self.write_include_str(buf, ws, path)?;
                 }
                 Node::Call(ws, scope, name, ref args) => {
                     size_hint += self.write_call(ctx, buf, ws, scope, name, args)?;
@@ -459,8 +747,13 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
                 }
                 Node::Raw(ws1, lws, val, rws, ws2) => {
                     self.handle_ws(ws1);
+                    let whitespace = self.whitespace;
+                    if whitespace == WhitespaceHandling::Minimize {
+                        self.whitespace = WhitespaceHandling::Suppress;
+                    }
                     self.visit_lit(lws, val, rws);
                     self.handle_ws(ws2);
+                    self.whitespace = whitespace;
                 }
                 Node::Import(ws, _, _) => {
                     if level != AstLevel::Top {
@@ -485,6 +778,9 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
                     self.write_buf_writable(buf)?;
                     buf.writeln("continue;")?;
                 }
+                Node::Debug(ws, ref expr) => {
+                    self.write_debug(buf, ws, expr)?;
+                }
             }
         }
 
@@ -582,7 +878,7 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
 
         let mut arm_size = 0;
         for (i, arm) in arms.iter().enumerate() {
-            let &(ws, ref target, ref body) = arm;
+            let &(ws, ref target, ref guard, ref body) = arm;
             self.handle_ws(ws);
 
             if i > 0 {
@@ -594,6 +890,10 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
 
             self.locals.push();
             self.visit_target(buf, true, true, target);
+            if let Some(guard) = guard {
+                buf.write(" if ");
+                self.visit_expr(buf, guard)?;
+            }
             buf.writeln(" => {")?;
 
             arm_size = self.handle(ctx, body, buf, AstLevel::Nested)?;
@@ -630,7 +930,10 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
             // If `iter` is a call then we assume it's something that returns
             // an iterator. If not then the user can explicitly add the needed
             // call without issues.
-            Expr::MethodCall(..) | Expr::PathCall(..) | Expr::Index(..) => {
+            Expr::MethodCall(..)
+            | Expr::PathCall(..)
+            | Expr::Index(..)
+            | Expr::Attr(_, "await") => {
                 buf.writeln(&format!("let _iter = ({}).into_iter();", expr_code))
             }
             // If accessing `self` then it most likely needs to be
@@ -654,15 +957,32 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
             self.locals.pop();
         }
 
+        // Only pay for the loop metadata if the body actually uses it;
+        // `loop.length` needs the items to be buffered up front.
+        let usage = LoopUsage::of(&loop_block.body);
+        if usage.length {
+            buf.writeln("let _iter: ::std::vec::Vec<_> = _iter.collect();")?;
+            buf.writeln("let _loop_length = _iter.len();")?;
+            buf.writeln("let _iter = _iter.into_iter();")?;
+        }
+
         self.locals.push();
-        buf.write("for (");
-        self.visit_target(buf, true, true, &loop_block.var);
-        buf.writeln(", _loop_item) in ::askama::helpers::TemplateLoop::new(_iter) {")?;
+        buf.write("for ");
+        if usage.item {
+            buf.write("(");
+            self.visit_target(buf, true, true, &loop_block.var);
+            buf.writeln(", _loop_item) in ::askama::helpers::TemplateLoop::new(_iter) {")?;
+        } else {
+            self.visit_target(buf, true, true, &loop_block.var);
+            buf.writeln(" in _iter {")?;
+        }
 
         buf.writeln("_did_loop = true;")?;
+        self.loop_depth += 1;
         let mut size_hint1 = self.handle(ctx, &loop_block.body, buf, AstLevel::Nested)?;
         self.handle_ws(loop_block.ws2);
         size_hint1 += self.write_buf_writable(buf)?;
+        self.loop_depth -= 1;
         self.locals.pop();
         buf.writeln("}")?;
 
@@ -676,7 +996,127 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
 
         buf.writeln("}")?;
 
-        Ok(flushed + ((size_hint1 * 3) + size_hint2) / 2)
+        // The number of iterations is estimated with the configured factor. If
+        // the loop iterates over a field of the template struct, its length can
+        // be used instead, at least when rendering a value of the template.
+        let factor = self.input.config.loop_size_factor;
+        if self.loop_depth == 0 && size_hint1 > 0 && is_self_field(&loop_block.iter, &expr_code) {
+            self.dynamic_size_hints.push(format!(
+                "(&{}).into_iter().size_hint().0.saturating_sub({}) * {}",
+                expr_code, factor, size_hint1
+            ));
+        }
+        Ok(flushed + cmp::max(size_hint1 * factor, size_hint2))
+    }
+
+    fn write_filter_block(
+        &mut self,
+        ctx: &'a Context<'_>,
+        buf: &mut Buffer,
+        ws1: Ws,
+        filter: &Expr<'_>,
+        nodes: &'a [Node<'_>],
+        ws2: Ws,
+    ) -> Result<usize, CompileError> {
+        self.flush_ws(ws1);
+        let mut size_hint = self.write_buf_writable(buf)?;
+
+        // Render the block's contents, escaped as usual, into a buffer, which
+        // the filters are then applied to, as they would be to a value.
+        buf.writeln("{")?;
+        buf.writeln("let mut _filter_source = ::std::string::String::new();")?;
+        buf.writeln("{")?;
+        buf.writeln("let writer: &mut dyn ::std::fmt::Write = &mut _filter_source;")?;
+        self.prepare_ws(ws1);
+        self.locals.push();
+        size_hint += self.handle(ctx, nodes, buf, AstLevel::Nested)?;
+        self.flush_ws(ws2);
+        size_hint += self.write_buf_writable(buf)?;
+        self.locals.pop();
+        buf.writeln("}")?;
+
+        // The contents have already been escaped, so the filtered result isn't
+        // escaped a second time.
+        let mut expr_buf = Buffer::new(0);
+        self.visit_expr(&mut expr_buf, filter)?;
+        buf.writeln(&format!(
+            "::std::write!(writer, \"{{}}\", ::askama::MarkupDisplay::new_safe(&({}), ::askama::Text))?;",
+            expr_buf.buf
+        ))?;
+        buf.writeln("}")?;
+        self.prepare_ws(ws2);
+        Ok(size_hint)
+    }
+
+    // The contents are rendered with the template's escaper into a string,
+    // which is bound as safe, so that it isn't escaped again when written.
+    // This happens where the block is, so the size hint doesn't count it.
+    fn write_capture(
+        &mut self,
+        ctx: &'a Context<'_>,
+        buf: &mut Buffer,
+        ws1: Ws,
+        var: &'a Target<'_>,
+        nodes: &'a [Node<'_>],
+        ws2: Ws,
+    ) -> Result<(), CompileError> {
+        self.flush_ws(ws1);
+        self.write_buf_writable(buf)?;
+
+        let mut body = Buffer::new(buf.indent);
+        body.writeln("let mut _capture = ::std::string::String::new();")?;
+        body.writeln("{")?;
+        body.writeln("let writer: &mut dyn ::std::fmt::Write = &mut _capture;")?;
+        self.prepare_ws(ws1);
+        self.locals.push();
+        self.handle(ctx, nodes, &mut body, AstLevel::Nested)?;
+        self.flush_ws(ws2);
+        self.write_buf_writable(&mut body)?;
+        self.locals.pop();
+        body.writeln("}")?;
+
+        if self.is_shadowing_variable(var)
+            || matches!(var, Target::Name(name) if self.locals.get(name).is_none())
+        {
+            buf.write("let ");
+        }
+        self.visit_target(buf, true, true, var);
+        buf.writeln(" = {")?;
+        buf.write(&body.buf);
+        buf.writeln("::askama::MarkupDisplay::new_safe(_capture, ::askama::Text)")?;
+        buf.writeln("};")?;
+        self.prepare_ws(ws2);
+        Ok(())
+    }
+
+    fn write_spaceless(
+        &mut self,
+        ctx: &'a Context<'_>,
+        buf: &mut Buffer,
+        ws1: Ws,
+        nodes: &'a [Node<'_>],
+        ws2: Ws,
+    ) -> Result<usize, CompileError> {
+        self.flush_ws(ws1);
+        let mut size_hint = self.write_buf_writable(buf)?;
+
+        // Like a filter block, the contents are rendered into a buffer,
+        // which is then written without the whitespace between tags.
+        buf.writeln("{")?;
+        buf.writeln("let mut _spaceless_source = ::std::string::String::new();")?;
+        buf.writeln("{")?;
+        buf.writeln("let writer: &mut dyn ::std::fmt::Write = &mut _spaceless_source;")?;
+        self.prepare_ws(ws1);
+        self.locals.push();
+        size_hint += self.handle(ctx, nodes, buf, AstLevel::Nested)?;
+        self.flush_ws(ws2);
+        size_hint += self.write_buf_writable(buf)?;
+        self.locals.pop();
+        buf.writeln("}")?;
+        buf.writeln("writer.write_str(&::askama::helpers::spaceless(&_spaceless_source))?;")?;
+        buf.writeln("}")?;
+        self.prepare_ws(ws2);
+        Ok(size_hint)
     }
 
     fn write_call(
@@ -701,15 +1141,18 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
             })?;
             (
                 mctx.macros.get(name).ok_or_else(|| {
-                    CompileError::String(format!("macro '{}' not found in scope '{}'", name, s))
+                    self.locate(
+                        name,
+                        format!("macro '{}' not found in scope '{}'", name, s).into(),
+                    )
                 })?,
                 mctx,
             )
         } else {
             (
-                ctx.macros
-                    .get(name)
-                    .ok_or_else(|| CompileError::String(format!("macro '{}' not found", name)))?,
+                ctx.macros.get(name).ok_or_else(|| {
+                    self.locate(name, format!("macro '{}' not found", name).into())
+                })?,
                 ctx,
             )
         };
@@ -720,13 +1163,73 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
         buf.writeln("{")?;
         self.prepare_ws(def.ws1);
 
+        // Positional arguments come first, named ones after them.
+        let mut positional = Vec::new();
+        let mut named = HashMap::new();
+        for arg in args {
+            match arg {
+                Expr::NamedArgument(arg_name, value) => {
+                    if !def.args.iter().any(|(param, _)| param == arg_name) {
+                        return Err(format!(
+                            "macro '{}' has no argument named '{}'",
+                            name, arg_name
+                        )
+                        .into());
+                    }
+                    if named.insert(*arg_name, &**value).is_some() {
+                        return Err(format!(
+                            "argument '{}' passed more than once to macro '{}'",
+                            arg_name, name
+                        )
+                        .into());
+                    }
+                }
+                _ if !named.is_empty() => {
+                    return Err(format!(
+                        "positional arguments must come before named arguments \
+                         in call to macro '{}'",
+                        name
+                    )
+                    .into());
+                }
+                _ => positional.push(arg),
+            }
+        }
+
         let mut names = Buffer::new(0);
         let mut values = Buffer::new(0);
         let mut is_first_variable = true;
-        for (i, arg) in def.args.iter().enumerate() {
-            let expr = args.get(i).ok_or_else(|| {
-                CompileError::String(format!("macro '{}' takes more than {} arguments", name, i))
-            })?;
+        let mut defaults = Vec::new();
+        for (i, (arg, default)) in def.args.iter().enumerate() {
+            let expr = match (positional.get(i), named.get(arg)) {
+                (Some(_), Some(_)) => {
+                    return Err(format!(
+                        "argument '{}' passed more than once to macro '{}'",
+                        arg, name
+                    )
+                    .into());
+                }
+                (Some(expr), None) => *expr,
+                (None, Some(expr)) => *expr,
+                (None, None) => match default {
+                    // Defaults are bound after the other arguments, so they
+                    // can refer to them.
+                    Some(default) => {
+                        defaults.push((arg, default));
+                        continue;
+                    }
+                    None if named.is_empty() => {
+                        return Err(
+                            format!("macro '{}' takes more than {} arguments", name, i).into()
+                        );
+                    }
+                    None => {
+                        return Err(
+                            format!("macro '{}' is missing argument '{}'", name, arg).into()
+                        );
+                    }
+                },
+            };
 
             match expr {
                 // If `expr` is already a form of variable then
@@ -768,8 +1271,14 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
         if !names.buf.is_empty() {
             buf.writeln(&format!("let ({}) = ({});", names.buf, values.buf))?;
         }
+        for (arg, default) in defaults {
+            let value = self.visit_expr_root(default)?;
+            buf.writeln(&format!("let {} = {};", arg, value))?;
+            self.locals.insert_with_default(arg);
+        }
 
-        let mut size_hint = self.handle(own_ctx, &def.nodes, buf, AstLevel::Nested)?;
+        let mut size_hint =
+            self.handle_in(&own_ctx.path, own_ctx, &def.nodes, buf, AstLevel::Nested)?;
 
         self.flush_ws(def.ws2);
         size_hint += self.write_buf_writable(buf)?;
@@ -784,19 +1293,25 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
         ctx: &'a Context<'_>,
         buf: &mut Buffer,
         ws: Ws,
-        path: &str,
+        paths: &[&str],
+        escape_as_parent: bool,
     ) -> Result<usize, CompileError> {
         self.flush_ws(ws);
         self.write_buf_writable(buf)?;
         let path = self
             .input
             .config
-            .find_template(path, Some(&self.input.path))?;
-        let src = get_template_source(&path)?;
-        let nodes = parse(&src, self.input.syntax)?;
+            .find_first_template(paths, Some(&self.input.path))?;
+        if !escape_as_parent {
+            self.check_include_escaper(paths[0], &path)?;
+        }
+        let src: Rc<str> = self.input.config.template_source(&path)?.into();
+        let is_file = !self.input.config.sources.contains_key(&path);
+        let file = if is_file { Some(path.as_path()) } else { None };
+        let nodes = parse_with_path(&src, &self.input.syntax, file)?;
 
         // Make sure the compiler understands that the generated code depends on the template file.
-        {
+        if is_file {
             let path = path.to_str().unwrap();
             buf.writeln(
                 &quote! {
@@ -806,35 +1321,238 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
             )?;
         }
 
+        if self.input.include_mode == IncludeMode::Function && !self.input.is_async {
+            if let Some(size_hint) = self.write_include_fn_call(ctx, buf, &path, &src, &nodes)? {
+                self.prepare_ws(ws);
+                return Ok(size_hint);
+            }
+        }
+
         let size_hint = {
             // Since nodes must not outlive the Generator, we instantiate
             // a nested Generator here to handle the include's nodes.
             let mut gen = self.child();
-            let mut size_hint = gen.handle(ctx, &nodes, buf, AstLevel::Nested)?;
+            gen.include_sources.push((Rc::clone(&src), path.clone()));
+            let mut size_hint = gen.handle_in(&path, ctx, &nodes, buf, AstLevel::Nested)?;
             size_hint += gen.write_buf_writable(buf)?;
+            let dynamic_size_hints = mem::take(&mut gen.dynamic_size_hints);
+            let fields = mem::take(&mut gen.fields);
+            let include_fns = mem::take(&mut gen.include_fns);
+            self.dynamic_size_hints.extend(dynamic_size_hints);
+            self.fields.extend(fields);
+            self.include_fns.extend(include_fns);
             size_hint
         };
         self.prepare_ws(ws);
         Ok(size_hint)
     }
 
-    fn write_let_decl(
+    // Generates the included template as a method of its own, and writes a
+    // call to it. An include using variables of the including scope, which
+    // the method can't access, is left to be inlined instead: `None` is
+    // returned, and nothing is written.
+    fn write_include_fn_call(
+        &mut self,
+        ctx: &'a Context<'_>,
+        buf: &mut Buffer,
+        path: &Path,
+        src: &Rc<str>,
+        nodes: &[Node<'_>],
+    ) -> Result<Option<usize>, CompileError> {
+        if self.loop_depth > 0 && LoopUsage::of(nodes).item {
+            return Ok(None);
+        }
+
+        // Without any locals, all variables refer to fields of the template.
+        let mut gen = Generator::new(
+            self.input,
+            self.contexts,
+            self.heritage,
+            self.integrations,
+            MapChain::new(),
+        );
+        gen.in_pre = self.in_pre;
+        gen.include_sources = self.include_sources.clone();
+        gen.include_sources
+            .push((Rc::clone(src), path.to_path_buf()));
+        // Variables of the including scope look like missing fields here.
+        gen.deferred_diagnostics = Some(vec![]);
+        let mut body = Buffer::new(0);
+        let mut size_hint = gen.handle_in(path, ctx, nodes, &mut body, AstLevel::Nested)?;
+        size_hint += gen.write_buf_writable(&mut body)?;
+
+        let uses_locals = gen.fields.iter().any(|field| {
+            let name = field.split('.').next().unwrap_or(field);
+            self.locals.get(&name).is_some()
+        });
+        if uses_locals {
+            return Ok(None);
+        }
+        for (diag, note) in gen.deferred_diagnostics.take().unwrap_or_default() {
+            self.emit_diagnostic(diag, note)?;
+        }
+
+        let name = include_fn_name(&self.relative_path(path), &body.buf);
+        buf.writeln(&format!("self.{}(writer)?;", name))?;
+        self.dynamic_size_hints.append(&mut gen.dynamic_size_hints);
+        self.fields.append(&mut gen.fields);
+        self.include_fns.append(&mut gen.include_fns);
+        self.include_fns.push((name, body.buf));
+        Ok(Some(size_hint))
+    }
+
+    // Warns when the included template is escaped differently on its own: its
+    // expressions are escaped with the escaper of the including template.
+    fn check_include_escaper(&mut self, at: &str, path: &Path) -> Result<(), CompileError> {
+        let own = match crate::input::extension(path).and_then(|ext| self.find_escaper(ext)) {
+            Some(own) if own != self.input.escaper => own,
+            _ => return Ok(()),
+        };
+        let message = format!(
+            "template {:?} is included with the escaper `{}` of the including template \
+             instead of its own `{}`, add `escape_as_parent` to the include if that is intended",
+            self.relative_path(path),
+            self.input.escaper,
+            own
+        );
+        self.warn_at(at, message)
+    }
+
+    // Render a template of its own, given by an expression, in place of the
+    // include. The path is checked to exist and tracked as a dependency, but
+    // what is rendered is up to the expression's `Template` implementation,
+    // which can't be tied to the path here.
+    fn write_include_with(
         &mut self,
         buf: &mut Buffer,
         ws: Ws,
-        var: &'a Target<'_>,
+        paths: &[&str],
+        include_ctx: &Expr<'_>,
     ) -> Result<(), CompileError> {
-        self.handle_ws(ws);
+        self.flush_ws(ws);
         self.write_buf_writable(buf)?;
-        buf.write("let ");
-        self.visit_target(buf, false, true, var);
-        buf.writeln(";")
-    }
+        let path = self
+            .input
+            .config
+            .find_first_template(paths, Some(&self.input.path))?;
 
-    fn is_shadowing_variable(&self, var: &Target<'a>) -> bool {
-        match var {
-            Target::Name(name) => {
-                let name = normalize_identifier(name);
+        // Depend on the template file, as for a regular include.
+        if !self.input.config.sources.contains_key(&path) {
+            let path = path.to_str().unwrap();
+            buf.writeln(
+                &quote! {
+                    include_bytes!(#path);
+                }
+                .to_string(),
+            )?;
+        }
+
+        // Use method call syntax, so references to templates work as well.
+        let expr_code = self.visit_expr_root(include_ctx)?;
+        buf.writeln("{")?;
+        buf.writeln("use ::askama::Template as _;")?;
+        buf.writeln(&format!("({}).render_into(writer)?;", expr_code))?;
+        buf.writeln("}")?;
+        self.prepare_ws(ws);
+        Ok(())
+    }
+
+    fn write_output_comment(
+        &mut self,
+        buf: &mut Buffer,
+        ws: Ws,
+        text: &str,
+    ) -> Result<usize, CompileError> {
+        self.flush_ws(ws);
+        let size_hint = self.write_buf_writable(buf)?;
+        let ext = self.input.extension().unwrap_or_default();
+        let syntax = self
+            .input
+            .config
+            .comments
+            .iter()
+            .find_map(|(exts, start, end)| exts.contains(ext).then_some((start, end)));
+        let (start, end) = match syntax {
+            Some(syntax) => syntax,
+            None => {
+                let message = format!(
+                    "no comment syntax for the extension {:?} of the template, \
+                     add one to the configuration file",
+                    ext
+                );
+                return Err(self.locate(text, message.into()));
+            }
+        };
+        let comment = if end.is_empty() {
+            let lines: Vec<_> = text
+                .lines()
+                .map(|line| format!("{}{}", start, line.trim()).trim_end().to_string())
+                .collect();
+            lines.join("\n")
+        } else if text.contains(end.trim()) {
+            let message = format!("a comment can't contain its end {:?}", end.trim());
+            return Err(self.locate(text, message.into()));
+        } else {
+            format!("{}{}{}", start, text, end)
+        };
+        buf.writeln(&format!("writer.write_str({:#?})?;", comment))?;
+        self.prepare_ws(ws);
+        Ok(size_hint + comment.len())
+    }
+
+    // Unlike a template source, the file keeps its trailing newline.
+    fn write_include_str(
+        &mut self,
+        buf: &mut Buffer,
+        ws: Ws,
+        path: &str,
+    ) -> Result<usize, CompileError> {
+        self.flush_ws(ws);
+        self.write_buf_writable(buf)?;
+        let path = self
+            .input
+            .config
+            .find_template(path, Some(&self.input.path))?;
+        let len = match self.input.config.sources.get(&path) {
+            Some(source) => {
+                buf.writeln(&format!("writer.write_str({:#?})?;", source))?;
+                source.len()
+            }
+            None => {
+                let len = fs::metadata(&path)
+                    .map_err(|_| format!("unable to open file '{}'", path.display()))?
+                    .len();
+                let path = path.to_str().unwrap();
+                buf.writeln(
+                    &quote! {
+                        writer.write_str(include_str!(#path))?;
+                    }
+                    .to_string(),
+                )?;
+                len as usize
+            }
+        };
+        self.prepare_ws(ws);
+        Ok(len)
+    }
+
+    fn write_let_decl(
+        &mut self,
+        buf: &mut Buffer,
+        ws: Ws,
+        var: &'a Target<'_>,
+    ) -> Result<(), CompileError> {
+        self.handle_ws(ws);
+        self.write_buf_writable(buf)?;
+        buf.write("let ");
+        self.visit_target(buf, false, true, var);
+        buf.writeln(";")
+    }
+
+    fn is_shadowing_variable(&self, var: &Target<'a>) -> bool {
+        match var {
+            Target::Name(name) => {
+                let name = normalize_identifier(name);
                 match self.locals.get(&name) {
                     // declares a new variable
                     None => false,
@@ -844,16 +1562,45 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
                     _ => false,
                 }
             }
-            Target::Tuple(_, targets) => targets
+            Target::Tuple(_, targets) | Target::Array(targets) => targets
                 .iter()
                 .any(|target| self.is_shadowing_variable(target)),
             Target::Struct(_, named_targets) => named_targets
                 .iter()
                 .any(|(_, target)| self.is_shadowing_variable(target)),
+            Target::Or(targets) => targets
+                .iter()
+                .any(|target| self.is_shadowing_variable(target)),
+            Target::Binding(name, target) => {
+                self.is_shadowing_variable(&Target::Name(name))
+                    || self.is_shadowing_variable(target)
+            }
+            Target::Rest => false,
             _ => panic!("Cannot have literals on the left-hand-side of an assignment."),
         }
     }
 
+    // Writes the escaped `Debug` representation of `expr`, which is left out
+    // of release builds entirely.
+    fn write_debug(
+        &mut self,
+        buf: &mut Buffer,
+        ws: Ws,
+        expr: &Expr<'_>,
+    ) -> Result<(), CompileError> {
+        self.handle_ws(ws);
+        self.write_buf_writable(buf)?;
+        let mut expr_buf = Buffer::new(0);
+        self.visit_expr(&mut expr_buf, expr)?;
+        buf.writeln("#[cfg(debug_assertions)]")?;
+        buf.writeln(&format!(
+            "::std::write!(writer, \"{{}}\", ::askama::MarkupDisplay::new_unsafe(\
+             ::std::format!(\"{{:?}}\", &({})), {}))?;",
+            expr_buf.buf, self.input.escaper
+        ))?;
+        Ok(())
+    }
+
     fn write_let(
         &mut self,
         buf: &mut Buffer,
@@ -863,7 +1610,18 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
     ) -> Result<(), CompileError> {
         self.handle_ws(ws);
         let mut expr_buf = Buffer::new(0);
-        self.visit_expr(&mut expr_buf, val)?;
+        let wrapped = self.visit_expr(&mut expr_buf, val)?;
+        // Escaped filter output can borrow temporaries of the expression, so
+        // it is rendered into a string, which is then not escaped again.
+        if let DisplayWrap::Wrapped = wrapped {
+            expr_buf.buf = format!(
+                "::askama::MarkupDisplay::new_safe(\
+                 ::std::string::ToString::to_string(&({})), ::askama::Text)",
+                expr_buf.buf
+            );
+        } else if borrows_temporary(val) {
+            expr_buf.buf = format!("::std::borrow::ToOwned::to_owned(&*{})", expr_buf.buf);
+        }
 
         let shadowed = self.is_shadowing_variable(var);
         if shadowed {
@@ -918,22 +1676,35 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
             .ok_or(CompileError::Static("no block ancestors available"))?;
         let (ctx, def) = heritage.blocks[cur.0].get(cur.1).ok_or_else(|| {
             CompileError::from(match name {
-                None => format!("no super() block found for block '{}'", cur.0),
+                None => format!(
+                    "no super() block found for block '{}': no parent template defines it",
+                    cur.0
+                ),
                 Some(name) => format!("no block found for name '{}'", name),
             })
         })?;
 
         // Get the nodes and whitespace suppression data from the block definition
-        let (ws1, nodes, ws2) = if let Node::BlockDef(ws1, _, nodes, ws2) = def {
-            (ws1, nodes, ws2)
+        let (ws1, required, nodes, ws2) = if let Node::BlockDef(ws1, _, required, nodes, ws2) = def
+        {
+            (ws1, *required, nodes, ws2)
         } else {
             unreachable!()
         };
+        // Only `super()` may render a required block that isn't overridden.
+        if required && cur.1 == 0 {
+            let message = format!(
+                "required block '{}' of {} is not overridden",
+                cur.0,
+                self.relative_path(&ctx.path)
+            );
+            return Err(self.locate(cur.0, message.into()));
+        }
 
         // Handle inner whitespace suppression spec and process block nodes
         self.prepare_ws(*ws1);
         self.locals.push();
-        let size_hint = self.handle(ctx, nodes, buf, AstLevel::Block)?;
+        let size_hint = self.handle_in(&ctx.path, ctx, nodes, buf, AstLevel::Block)?;
 
         if !self.locals.is_current_empty() {
             // Need to flush the buffer before popping the variable stack
@@ -951,8 +1722,9 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
     }
 
     fn write_expr(&mut self, ws: Ws, s: &'a Expr<'a>) {
-        self.handle_ws(ws);
-        self.buf_writable.push(Writable::Expr(s));
+        self.flush_ws_at(ws, false);
+        self.prepare_ws_at(ws, false);
+        self.buf_writable.push(Writable::Expr(s, self.template));
     }
 
     // Write expression buffer and empty
@@ -977,78 +1749,74 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
         }
 
         let mut size_hint = 0;
-        let mut buf_format = Buffer::new(0);
-        let mut buf_expr = Buffer::new(buf.indent + 1);
-        let mut expr_cache = HashMap::with_capacity(self.buf_writable.len());
+        let mut buf_lit = Buffer::new(0);
         for s in mem::take(&mut self.buf_writable) {
             match s {
                 Writable::Lit(s) => {
-                    buf_format.write(&s.replace("{", "{{").replace("}", "}}"));
+                    buf_lit.write(s);
                     size_hint += s.len();
                 }
-                Writable::Expr(s) => {
+                Writable::Expr(s, template) => {
+                    if !buf_lit.buf.is_empty() {
+                        buf.writeln(&format!("writer.write_str({:#?})?;", &buf_lit.buf))?;
+                        buf_lit.buf.clear();
+                    }
+
                     use self::DisplayWrap::*;
                     let mut expr_buf = Buffer::new(0);
-                    let wrapped = self.visit_expr(&mut expr_buf, s)?;
-                    let expression = match wrapped {
-                        Wrapped => expr_buf.buf,
-                        Unwrapped => format!(
-                            "::askama::MarkupDisplay::new_unsafe(&({}), {})",
-                            expr_buf.buf, self.input.escaper
-                        ),
+                    let template = mem::replace(&mut self.template, template);
+                    self.dynamic_allowed = true;
+                    let wrapped = self.visit_expr(&mut expr_buf, s);
+                    self.template = template;
+                    let escaper = match wrapped? {
+                        Wrapped => "::askama::Text",
+                        Unwrapped => self.input.escaper,
                     };
-
-                    use std::collections::hash_map::Entry;
-                    let id = match expr_cache.entry(expression.clone()) {
-                        Entry::Occupied(e) => *e.get(),
-                        Entry::Vacant(e) => {
-                            let id = self.named;
-                            self.named += 1;
-
-                            buf_expr.write(&format!("expr{} = ", id));
-                            buf_expr.write("&");
-                            buf_expr.write(&expression);
-                            buf_expr.writeln(",")?;
-
-                            e.insert(id);
-                            id
-                        }
-                    };
-
-                    buf_format.write(&format!("{{expr{}}}", id));
+                    // Integers and strings are written without going through
+                    // `fmt`, see `askama::helpers::FastWritable`.
+                    buf.writeln(&format!(
+                        "(&::askama::helpers::Writable(&({}))).askama_write(writer, {})?;",
+                        expr_buf.buf, escaper
+                    ))?;
                     size_hint += 3;
                 }
             }
         }
-
-        buf.writeln("write!(")?;
-        buf.indent();
-        buf.writeln("writer,")?;
-        buf.writeln(&format!("{:#?},", &buf_format.buf))?;
-        buf.writeln(buf_expr.buf.trim())?;
-        buf.dedent()?;
-        buf.writeln(")?;")?;
+        if !buf_lit.buf.is_empty() {
+            buf.writeln(&format!("writer.write_str({:#?})?;", &buf_lit.buf))?;
+        }
         Ok(size_hint)
     }
 
     fn visit_lit(&mut self, lws: &'a str, val: &'a str, rws: &'a str) {
         assert!(self.next_ws.is_none());
+        let trim_newline = mem::take(&mut self.trim_newline);
         if !lws.is_empty() {
             if self.skip_ws {
                 self.skip_ws = false;
             } else if val.is_empty() {
                 assert!(rws.is_empty());
-                self.next_ws = Some(lws);
+                self.next_ws_indent = line_indent(lws);
+                self.next_ws = Some(trim_first_newline(lws, trim_newline));
             } else {
+                let lws = trim_first_newline(lws, trim_newline);
+                let lws = self.minimize_ws(lws);
                 self.buf_writable.push(Writable::Lit(lws));
             }
         }
 
         if !val.is_empty() {
             self.buf_writable.push(Writable::Lit(val));
+            self.in_pre = match (find_pre_open(val), val.to_ascii_lowercase().rfind("</pre")) {
+                (Some(open), Some(close)) => open > close,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => self.in_pre,
+            };
         }
 
         if !rws.is_empty() {
+            self.next_ws_indent = line_indent(rws);
             self.next_ws = Some(rws);
         }
     }
@@ -1070,12 +1838,39 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
         buf: &mut Buffer,
         expr: &Expr<'_>,
     ) -> Result<DisplayWrap, CompileError> {
+        self.visit_expr_unlocated(buf, expr)
+            .map_err(|err| match expr_name(expr) {
+                Some(name) => self.locate(name, err),
+                None => err,
+            })
+    }
+
+    fn visit_expr_unlocated(
+        &mut self,
+        buf: &mut Buffer,
+        expr: &Expr<'_>,
+    ) -> Result<DisplayWrap, CompileError> {
+        let dynamic_allowed = mem::take(&mut self.dynamic_allowed);
+        if self.is_dynamic_path(expr) {
+            if !dynamic_allowed {
+                return Err(format!(
+                    "a path into the dynamic field `{}` can only be written to the output, \
+                     or given to the `default` filter or a filter formatting its input",
+                    self.input.dynamic.as_deref().unwrap_or_default(),
+                )
+                .into());
+            }
+            buf.write("::askama::helpers::JsonValue(");
+            self.visit_dynamic_path(buf, expr)?;
+            buf.write(")");
+            return Ok(DisplayWrap::Unwrapped);
+        }
         Ok(match *expr {
             Expr::BoolLit(s) => self.visit_bool_lit(buf, s),
             Expr::NumLit(s) => self.visit_num_lit(buf, s),
             Expr::StrLit(s) => self.visit_str_lit(buf, s),
             Expr::CharLit(s) => self.visit_char_lit(buf, s),
-            Expr::Var(s) => self.visit_var(buf, s),
+            Expr::Var(s) => self.visit_var(buf, s)?,
             Expr::VarCall(var, ref args) => self.visit_var_call(buf, var, args)?,
             Expr::Path(ref path) => self.visit_path(buf, path),
             Expr::PathCall(ref path, ref args) => self.visit_path_call(buf, path, args)?,
@@ -1091,9 +1886,105 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
                 self.visit_method_call(buf, obj, method, args)?
             }
             Expr::RustMacro(name, args) => self.visit_rust_macro(buf, name, args),
+            Expr::If(ref cond, ref then, ref otherwise) => {
+                self.visit_if(buf, cond, then, otherwise)?
+            }
+            Expr::Closure(ref params, ref body) => self.visit_closure(buf, params, body)?,
+            Expr::Try(ref expr) => self.visit_try(buf, expr)?,
+            Expr::FilterSource => {
+                buf.write("_filter_source");
+                DisplayWrap::Unwrapped
+            }
+            Expr::NamedArgument(name, _) => {
+                return Err(
+                    format!("named argument `{}` is only allowed in macro calls", name).into(),
+                )
+            }
         })
     }
 
+    fn visit_closure(
+        &mut self,
+        buf: &mut Buffer,
+        params: &[Target<'_>],
+        body: &Expr<'_>,
+    ) -> Result<DisplayWrap, CompileError> {
+        let outer = self.closure_params.len();
+        buf.write("|");
+        for (i, param) in params.iter().enumerate() {
+            if i > 0 {
+                buf.write(", ");
+            }
+            self.visit_closure_param(buf, param)?;
+        }
+        buf.write("| ");
+        let res = self.visit_expr(buf, body);
+        self.closure_params.truncate(outer);
+        res?;
+        Ok(DisplayWrap::Unwrapped)
+    }
+
+    // Closure parameters are written like `let` targets, but only hold
+    // irrefutable patterns
+    fn visit_closure_param(
+        &mut self,
+        buf: &mut Buffer,
+        target: &Target<'_>,
+    ) -> Result<(), CompileError> {
+        match target {
+            Target::Name("_") => buf.write("_"),
+            Target::Name(name) => {
+                let name = normalize_identifier(name);
+                self.closure_params.push(name.to_string());
+                buf.write(name);
+            }
+            Target::Tuple(path, targets) => {
+                buf.write(&path.join("::"));
+                buf.write("(");
+                for target in targets {
+                    self.visit_closure_param(buf, target)?;
+                    buf.write(",");
+                }
+                buf.write(")");
+            }
+            Target::Array(targets) => {
+                buf.write("[");
+                for target in targets {
+                    self.visit_closure_param(buf, target)?;
+                    buf.write(",");
+                }
+                buf.write("]");
+            }
+            Target::Struct(path, targets) => {
+                buf.write(&path.join("::"));
+                buf.write(" { ");
+                for (name, target) in targets {
+                    if let Target::Rest = target {
+                        buf.write("..");
+                        continue;
+                    }
+                    buf.write(normalize_identifier(name));
+                    buf.write(": ");
+                    self.visit_closure_param(buf, target)?;
+                    buf.write(",");
+                }
+                buf.write(" }");
+            }
+            Target::Binding(name, target) => {
+                self.visit_closure_param(buf, &Target::Name(name))?;
+                buf.write(" @ ");
+                self.visit_closure_param(buf, target)?;
+            }
+            Target::Rest => buf.write(".."),
+            _ => {
+                return Err(
+                    "closure parameters can only be names, tuples, arrays or structs".into(),
+                )
+            }
+        }
+        Ok(())
+    }
+
     fn visit_rust_macro(&mut self, buf: &mut Buffer, name: &str, args: &str) -> DisplayWrap {
         buf.write(name);
         buf.write("!(");
@@ -1104,14 +1995,46 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
     }
 
     fn visit_filter(
+        &mut self,
+        buf: &mut Buffer,
+        name: &str,
+        args: &[Expr<'_>],
+    ) -> Result<DisplayWrap, CompileError> {
+        // The input of custom filters may be a dynamic path too, any mismatch
+        // is then reported in the filter's signature.
+        self.dynamic_allowed =
+            DISPLAY_FILTERS.contains(&name) || !filters::BUILT_IN_FILTERS.contains(&name);
+        let wrapped = self.visit_filter_unchecked(buf, name, args);
+        self.dynamic_allowed = false;
+        wrapped
+    }
+
+    fn visit_filter_unchecked(
         &mut self,
         buf: &mut Buffer,
         mut name: &str,
         args: &[Expr<'_>],
     ) -> Result<DisplayWrap, CompileError> {
+        self.check_escaped_filter(name, args)?;
+        let filter = name;
+
+        if name == "date" && matches!(args.last(), Some(Expr::NamedArgument("tz", _))) {
+            self._visit_date_tz_filter(buf, args)?;
+            return Ok(DisplayWrap::Unwrapped);
+        }
+        if let Some(Expr::NamedArgument(arg, _)) = args
+            .iter()
+            .find(|arg| matches!(arg, Expr::NamedArgument(..)))
+        {
+            return Err(format!("the `{}` filter has no named argument `{}`", name, arg).into());
+        }
+
         if matches!(name, "escape" | "e") {
             self._visit_escape_filter(buf, args)?;
             return Ok(DisplayWrap::Wrapped);
+        } else if name == "attr" {
+            self._visit_attr_filter(buf, args)?;
+            return Ok(DisplayWrap::Wrapped);
         } else if name == "format" {
             self._visit_format_filter(buf, args)?;
             return Ok(DisplayWrap::Unwrapped);
@@ -1120,23 +2043,123 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
             return Ok(DisplayWrap::Unwrapped);
         } else if name == "join" {
             self._visit_join_filter(buf, args)?;
+            return Ok(DisplayWrap::Wrapped);
+        } else if name == "groupby" {
+            self._visit_groupby_filter(buf, args)?;
+            return Ok(DisplayWrap::Unwrapped);
+        } else if name == "dictsort" {
+            self._visit_dictsort_filter(buf, args)?;
+            return Ok(DisplayWrap::Unwrapped);
+        } else if name == "abs" {
+            self._visit_abs_filter(buf, args)?;
+            return Ok(DisplayWrap::Unwrapped);
+        } else if matches!(name, "trim" | "trim_start" | "trim_end") {
+            self._visit_trim_filter(buf, name, args)?;
+            return Ok(DisplayWrap::Unwrapped);
+        } else if name == "slice" {
+            self._visit_slice_filter(buf, args)?;
+            return Ok(DisplayWrap::Unwrapped);
+        } else if name == "sort" {
+            self._visit_sort_filter(buf, args)?;
+            return Ok(DisplayWrap::Unwrapped);
+        } else if name == "map" {
+            self._visit_map_filter(buf, args)?;
+            return Ok(DisplayWrap::Unwrapped);
+        } else if name == "unique" {
+            self._visit_unique_filter(buf, args)?;
+            return Ok(DisplayWrap::Unwrapped);
+        } else if name == "select" || name == "reject" {
+            self._visit_select_filter(buf, name, args)?;
+            return Ok(DisplayWrap::Unwrapped);
+        } else if name == "batch" {
+            self._visit_batch_filter(buf, args)?;
+            return Ok(DisplayWrap::Unwrapped);
+        } else if name == "default" {
+            self._visit_default_filter(buf, args)?;
+            return Ok(DisplayWrap::Unwrapped);
+        } else if name == "matches" && matches!(args, [_, Expr::StrLit(_)]) {
+            self._visit_matches_filter(buf, args)?;
+            return Ok(DisplayWrap::Unwrapped);
+        } else if name == "indent" && args.len() > 2 {
+            self._visit_indent_filter(buf, args)?;
             return Ok(DisplayWrap::Unwrapped);
         }
 
         if name == "tojson" {
             name = "json";
+        } else if name == "count" {
+            name = "length";
+        } else if name == "sum" && args.len() == 2 {
+            name = "sum_from";
+        } else if name == "random" && args.len() == 2 {
+            name = "random_seeded";
         }
 
         #[cfg(not(feature = "json"))]
-        if name == "json" {
-            return Err("the `json` filter requires the `serde-json` feature to be enabled".into());
+        if matches!(name, "json" | "json_pretty" | "json_script") {
+            return Err(format!(
+                "the `{}` filter requires the `serde-json` feature to be enabled",
+                name
+            )
+            .into());
+        }
+        #[cfg(not(feature = "markdown"))]
+        if name == "markdown" {
+            return Err(
+                "the `markdown` filter requires the `markdown` feature to be enabled".into(),
+            );
+        }
+        #[cfg(not(feature = "percent-encoding"))]
+        if matches!(name, "urlencode" | "urlencode_strict") {
+            return Err(format!(
+                "the `{}` filter requires the `urlencode` feature to be enabled",
+                name
+            )
+            .into());
+        }
+        #[cfg(not(feature = "rand"))]
+        if matches!(name, "random" | "random_seeded") {
+            return Err("the `random` filter requires the `rand` feature to be enabled".into());
         }
         #[cfg(not(feature = "yaml"))]
         if name == "yaml" {
             return Err("the `yaml` filter requires the `serde-yaml` feature to be enabled".into());
         }
+        #[cfg(not(feature = "base64"))]
+        if matches!(name, "base64" | "base64_decode") {
+            return Err(format!(
+                "the `{}` filter requires the `base64` feature to be enabled",
+                name
+            )
+            .into());
+        }
+        #[cfg(not(feature = "case"))]
+        if matches!(
+            name,
+            "camelcase" | "kebabcase" | "pascalcase" | "shoutycase" | "snakecase"
+        ) {
+            return Err(format!(
+                "the `{}` filter requires the `case` feature to be enabled",
+                name
+            )
+            .into());
+        }
 
-        const FILTERS: [&str; 3] = ["safe", "json", "yaml"];
+        const FILTERS: [&str; 13] = [
+            "safe",
+            "base64",
+            "highlight",
+            "json",
+            "json_pretty",
+            "json_script",
+            "linebreaks",
+            "linebreaksbr",
+            "markdown",
+            "paragraphbreaks",
+            "urlencode",
+            "urlencode_strict",
+            "yaml",
+        ];
         if FILTERS.contains(&name) {
             buf.write(&format!(
                 "::askama::filters::{}({}, ",
@@ -1145,104 +2168,890 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
         } else if filters::BUILT_IN_FILTERS.contains(&name) {
             buf.write(&format!("::askama::filters::{}(", name));
         } else {
-            buf.write(&format!("filters::{}(", name));
+            // Likely a typo, unless there is a custom filter of that name.
+            let builtin = filters::BUILT_IN_FILTERS
+                .iter()
+                .find(|builtin| name.len() > 3 && edit_distance(name, builtin) == 1);
+            if let Some(builtin) = builtin {
+                self.note_at(
+                    filter,
+                    format!("`{}` is not a built-in filter", name),
+                    Some(format!("did you mean `{}`?", builtin)),
+                );
+            }
+            buf.write(&format!("{}::{}(", self.input.filters, name));
         }
 
         self._visit_args(buf, args)?;
-        buf.write(")?");
+        self._visit_default_args(buf, name, args.len());
+        buf.write(&format!(
+            ").map_err(|err| ::askama::Error::render({:?}, {:?}, err))?",
+            self.template_name(),
+            filter
+        ));
         Ok(match FILTERS.contains(&name) {
             true => DisplayWrap::Wrapped,
             false => DisplayWrap::Unwrapped,
         })
     }
 
-    fn _visit_escape_filter(
+    // Fill in the defaults for optional arguments of built-in filters; `given`
+    // includes the filter's input.
+    fn _visit_default_args(&mut self, buf: &mut Buffer, name: &str, given: usize) {
+        let (required, defaults) = filters::FILTER_DEFAULT_ARGS
+            .iter()
+            .find_map(|(filter, required, defaults)| {
+                (*filter == name).then_some((*required, *defaults))
+            })
+            .unwrap_or_default();
+        for default in defaults.iter().skip(given.saturating_sub(1 + required)) {
+            buf.write(", ");
+            buf.write(default);
+        }
+    }
+
+    fn find_escaper(&self, name: &str) -> Option<&'a str> {
+        self.input
+            .config
+            .escapers
+            .iter()
+            .find_map(|(escapers, escaper)| escapers.contains(name).then_some(escaper.as_str()))
+    }
+
+    // The escaper named in `escape(name)`: a configured one, or the one of the
+    // `attr` filter, which isn't picked by any extension
+    fn find_named_escaper(&self, name: &str) -> Option<&'a str> {
+        match self.find_escaper(name) {
+            None if name == "attr" => Some(ATTR_ESCAPER),
+            escaper => escaper,
+        }
+    }
+
+    // The name of the template being generated for use in render errors
+    fn template_name(&self) -> String {
+        self.relative_path(self.template.unwrap_or(&self.input.path))
+    }
+
+    // The path of a template relative to the template directory it was found in
+    fn relative_path(&self, path: &Path) -> String {
+        let path = self
+            .input
+            .config
+            .dirs
+            .iter()
+            .find_map(|dir| path.strip_prefix(dir).ok())
+            .unwrap_or(path);
+        path.to_string_lossy().into_owned()
+    }
+
+    // Warns about filters applied to output that is already escaped: escaping
+    // it again, which escapes it twice, or marking it `safe`, which does
+    // nothing. A text escaper does not change the output, so it is left out.
+    fn check_escaped_filter(&mut self, name: &str, args: &[Expr<'_>]) -> Result<(), CompileError> {
+        const ESCAPED_FILTERS: [&str; 14] = [
+            "attr",
+            "e",
+            "escape",
+            "highlight",
+            "join",
+            "json",
+            "json_pretty",
+            "json_script",
+            "linebreaks",
+            "linebreaksbr",
+            "markdown",
+            "paragraphbreaks",
+            "tojson",
+            "yaml",
+        ];
+
+        let inner = match args.first() {
+            Some(Expr::Filter(inner, _)) if ESCAPED_FILTERS.contains(inner) => inner,
+            _ => return Ok(()),
+        };
+        let escaper = match (name, args.get(1)) {
+            ("e" | "escape", Some(Expr::StrLit(escaper))) => self.find_named_escaper(escaper),
+            ("attr", _) => Some(ATTR_ESCAPER),
+            _ => Some(self.input.escaper),
+        };
+        if escaper == Some("::askama::Text") {
+            return Ok(());
+        }
+
+        let message = match name {
+            "e" | "escape" | "attr" => {
+                format!("the output of `{}` is escaped again by `{}`", inner, name)
+            }
+            "safe" => format!(
+                "`safe` has no effect on the output of `{}`, which is already escaped",
+                inner
+            ),
+            _ => return Ok(()),
+        };
+        self.warn_at(name, message)
+    }
+
+    // Prints a warning at compile time, or fails if the `strict` option is set.
+    // The warning points at `at` in the template source if it's part of it.
+    fn warn_at(&mut self, at: &str, message: String) -> Result<(), CompileError> {
+        let warning = self.locate(at, message.into());
+        self.emit_diagnostic(warning, false)
+    }
+
+    // Prints a note at compile time, pointing at `at` like `warn_at()`, with
+    // the `help` next to it. Notes point out likely causes of the errors the
+    // generated code then fails to compile with, so they never fail.
+    fn note_at(&mut self, at: &str, message: String, help: Option<String>) {
+        let note = match (self.locate(at, message.into()), help) {
+            (CompileError::Located(msg), Some(help)) => {
+                CompileError::Located(format!("{} {}", msg, help))
+            }
+            (note, Some(help)) => format!("{}, {}", note, help).into(),
+            (note, None) => note,
+        };
+        // Can't fail for a note.
+        let _ = self.emit_diagnostic(note, true);
+    }
+
+    fn emit_diagnostic(&mut self, diag: CompileError, note: bool) -> Result<(), CompileError> {
+        if let Some(deferred) = &mut self.deferred_diagnostics {
+            deferred.push((diag, note));
+            return Ok(());
+        }
+        if self.input.config.strict && !note {
+            return Err(diag);
+        }
+        eprint!(
+            "   --> in struct {}\n   = {}: {}\n",
+            self.input.ast.ident,
+            if note { "note" } else { "warning" },
+            diag
+        );
+        Ok(())
+    }
+
+    // Notes a variable that is neither declared in the template nor a field of
+    // the template struct, for which the generated code fails to compile
+    // without pointing at the template
+    fn check_field(&mut self, name: &str) {
+        let fields = match self.input.ast.data {
+            syn::Data::Struct(syn::DataStruct {
+                fields: syn::Fields::Named(ref fields),
+                ..
+            }) => &fields.named,
+            _ => return,
+        };
+        // The fields of the parent template are reached through `Deref`.
+        if self.input.parent.is_some() {
+            return;
+        }
+        let mut names = fields
+            .iter()
+            .filter_map(|field| field.ident.as_ref())
+            .map(|ident| ident.to_string());
+        let field = normalize_identifier(name);
+        if names.any(|name| name == field) {
+            return;
+        }
+
+        let names = fields.iter().filter_map(|field| field.ident.as_ref());
+        let help = closest_name(name, names.map(|ident| ident.to_string()))
+            .map(|similar| format!("did you mean `{}`?", similar));
+        let message = format!("no field `{}` in struct {}", name, self.input.ast.ident);
+        self.note_at(name, message, help);
+    }
+
+    // Adds the location of `at` to the error, if it is a part of the source
+    // of one of the templates and the error has no location yet
+    fn locate(&self, at: &str, err: CompileError) -> CompileError {
+        if matches!(err, CompileError::Located(_)) || at.is_empty() {
+            return err;
+        }
+        let sources = self
+            .contexts
+            .values()
+            .map(|ctx| (ctx.source, ctx.path.as_path()))
+            .chain(
+                self.include_sources
+                    .iter()
+                    .map(|(src, path)| (&**src, path.as_path())),
+            );
+        for (src, path) in sources {
+            let offset = (at.as_ptr() as usize).wrapping_sub(src.as_ptr() as usize);
+            if offset < src.len() && at.len() <= src.len() - offset {
+                let path = self.input.file_path(path);
+                let message = source_message(&err.to_string(), src, offset, at.len(), path);
+                return CompileError::Located(message);
+            }
+        }
+        err
+    }
+
+    fn _visit_escape_filter(
+        &mut self,
+        buf: &mut Buffer,
+        args: &[Expr<'_>],
+    ) -> Result<(), CompileError> {
+        if args.len() > 2 {
+            return Err("only two arguments allowed to escape filter".into());
+        }
+        let opt_escaper = match args.get(1) {
+            Some(Expr::StrLit(name)) => Some(*name),
+            Some(_) => return Err("invalid escaper type for escape filter".into()),
+            None => None,
+        };
+        let escaper = match opt_escaper {
+            Some(name) => self.find_named_escaper(name).ok_or_else(|| {
+                let mut names: Vec<_> = (self.input.config.escapers.iter())
+                    .flat_map(|(names, _)| names.iter().map(String::as_str))
+                    .filter(|name| !name.is_empty())
+                    .chain(Some("attr"))
+                    .collect();
+                names.sort_unstable();
+                names.dedup();
+                CompileError::String(format!(
+                    "invalid escaper '{}' for escape filter, expected one of: {}",
+                    name,
+                    names.join(", ")
+                ))
+            })?,
+            None => self.input.escaper,
+        };
+        buf.write("::askama::filters::escape(");
+        buf.write(escaper);
+        buf.write(", ");
+        self._visit_args(buf, &args[..1])?;
+        buf.write(")?");
+        Ok(())
+    }
+
+    // Escapes with `askama::Attr`, whatever the template's escaper is.
+    fn _visit_attr_filter(
+        &mut self,
+        buf: &mut Buffer,
+        args: &[Expr<'_>],
+    ) -> Result<(), CompileError> {
+        if args.len() > 1 {
+            return Err("the `attr` filter takes no arguments".into());
+        }
+        buf.write("::askama::filters::escape(");
+        buf.write(ATTR_ESCAPER);
+        buf.write(", ");
+        self._visit_args(buf, args)?;
+        buf.write(")?");
+        Ok(())
+    }
+
+    // A string literal value is the format string, as for `format!()`;
+    // otherwise the argument is a format spec applied to the value, like
+    // with `fmt`.
+    fn _visit_format_filter(
+        &mut self,
+        buf: &mut Buffer,
+        args: &[Expr<'_>],
+    ) -> Result<(), CompileError> {
+        match args.first() {
+            Some(Expr::StrLit(v)) => {
+                buf.write("format!(");
+                self.visit_str_lit(buf, v);
+                if args.len() > 1 {
+                    buf.write(", ");
+                }
+            }
+            Some(_) if args.len() == 2 => {
+                match &args[1] {
+                    Expr::StrLit(spec) => check_format_spec(spec)?,
+                    _ => {
+                        return Err(
+                            "the format spec must be a string literal, e.g. \"{:.2}\"".into()
+                        )
+                    }
+                }
+                return self._visit_fmt_filter(buf, args);
+            }
+            _ => return Err("invalid expression type for format filter".into()),
+        }
+        self._visit_args(buf, &args[1..])?;
+        buf.write(")");
+        Ok(())
+    }
+
+    fn _visit_fmt_filter(
+        &mut self,
+        buf: &mut Buffer,
+        args: &[Expr<'_>],
+    ) -> Result<(), CompileError> {
+        buf.write("format!(");
+        if let Some(Expr::StrLit(v)) = args.get(1) {
+            self.visit_str_lit(buf, v);
+            buf.write(", ");
+        } else {
+            return Err("invalid expression type for fmt filter".into());
+        }
+        self._visit_args(buf, &args[0..1])?;
+        if args.len() > 2 {
+            return Err("only two arguments allowed to fmt filter".into());
+        }
+        buf.write(")");
+        Ok(())
+    }
+
+    // Force type coercion on first argument to `join` filter (see #39).
+    // The optional attribute is a (dotted) field path on the items, like the
+    // key of `groupby`.
+    fn _visit_join_filter(
+        &mut self,
+        buf: &mut Buffer,
+        args: &[Expr<'_>],
+    ) -> Result<(), CompileError> {
+        let (seq, separator, attr) = match args {
+            [seq, separator] => (seq, separator, None),
+            [seq, separator, Expr::StrLit(attr)] => {
+                if !is_field_path(attr) {
+                    return Err(
+                        format!("invalid field name {:?} for the `join` filter", attr).into(),
+                    );
+                }
+                (seq, separator, Some(attr))
+            }
+            _ => {
+                return Err("the `join` filter takes a separator and an optional field \
+                            name as a string"
+                    .into())
+            }
+        };
+        match attr {
+            Some(_) => buf.write("::askama::filters::join_by("),
+            None => buf.write("::askama::filters::join("),
+        }
+        buf.write(&format!("{}, (&", self.input.escaper));
+        self.visit_expr(buf, seq)?;
+        buf.write(").into_iter(), &");
+        self.visit_expr(buf, separator)?;
+        if let Some(attr) = attr {
+            buf.write(&format!(", |_item| &_item.{}", attr));
+        }
+        buf.write(")?");
+        Ok(())
+    }
+
+    // String literal fallbacks are passed as `&str`, so they can stand in for
+    // a `String` value; other fallbacks are borrowed.
+    fn _visit_default_filter(
+        &mut self,
+        buf: &mut Buffer,
+        args: &[Expr<'_>],
+    ) -> Result<(), CompileError> {
+        let (value, fallback, if_empty) = match args {
+            [value, fallback] => (value, fallback, None),
+            [value, fallback, if_empty] => (value, fallback, Some(if_empty)),
+            _ => {
+                return Err(
+                    "the `default` filter takes a fallback value and an optional \
+                            flag to also replace empty values"
+                        .into(),
+                )
+            }
+        };
+        // A missing path into the `dynamic` field is `None` rather than a panic.
+        if self.is_dynamic_path(value) {
+            buf.write("::askama::helpers::JsonValue(");
+            self.visit_dynamic_path(buf, value)?;
+            buf.write(match if_empty {
+                Some(_) => ").or_if_empty(",
+                None => ").or(",
+            });
+            self._visit_default_fallback(buf, fallback)?;
+            if let Some(if_empty) = if_empty {
+                buf.write(", ");
+                self.visit_expr(buf, if_empty)?;
+            }
+            buf.write(")");
+            return Ok(());
+        }
+        match if_empty {
+            Some(_) => buf.write("::askama::filters::default_if_empty(&("),
+            None => buf.write("::askama::filters::default(&("),
+        }
+        // A missing key or index would make `obj[key]` panic, so it's looked
+        // up with `get()`; the value is borrowed as the fallback's type.
+        if let Expr::Index(obj, key) = value {
+            self.visit_expr(buf, obj)?;
+            buf.write(").get(");
+            self.visit_expr(buf, key)?;
+            buf.write(").map(::std::borrow::Borrow::borrow), ");
+        } else {
+            self.visit_expr(buf, value)?;
+            buf.write("), ");
+        }
+        self._visit_default_fallback(buf, fallback)?;
+        if let Some(if_empty) = if_empty {
+            buf.write(", ");
+            self.visit_expr(buf, if_empty)?;
+        }
+        buf.write(")?");
+        Ok(())
+    }
+
+    fn _visit_default_fallback(
+        &mut self,
+        buf: &mut Buffer,
+        fallback: &Expr<'_>,
+    ) -> Result<(), CompileError> {
+        if let Expr::StrLit(_) = fallback {
+            self.visit_expr(buf, fallback)?;
+        } else {
+            buf.write("&(");
+            self.visit_expr(buf, fallback)?;
+            buf.write(")");
+        }
+        Ok(())
+    }
+
+    // `indent(width)` maps to `indent()`, while the forms also taking the flags
+    // for the first and blank lines map to `indent_lines()`.
+    fn _visit_indent_filter(
+        &mut self,
+        buf: &mut Buffer,
+        args: &[Expr<'_>],
+    ) -> Result<(), CompileError> {
+        let (first, blank) = match args {
+            [_, _, first] => (first, None),
+            [_, _, first, blank] => (first, Some(blank)),
+            _ => {
+                return Err("the `indent` filter takes a width and optional flags \
+                            to also indent the first line and blank lines"
+                    .into())
+            }
+        };
+        buf.write("::askama::filters::indent_lines(&(");
+        self.visit_expr(buf, &args[0])?;
+        buf.write("), ");
+        self.visit_expr(buf, &args[1])?;
+        buf.write(", ");
+        self.visit_expr(buf, first)?;
+        buf.write(", ");
+        match blank {
+            Some(blank) => {
+                self.visit_expr(buf, blank)?;
+            }
+            None => buf.write("false"),
+        }
+        buf.write(")?");
+        Ok(())
+    }
+
+    // Whether to sort by key or value is decided at compile time, as sorting
+    // by value requires the values to be `Ord`.
+    fn _visit_dictsort_filter(
+        &mut self,
+        buf: &mut Buffer,
+        args: &[Expr<'_>],
+    ) -> Result<(), CompileError> {
+        let (map, by, reverse) = match args {
+            [map] => (map, "key", None),
+            [map, Expr::StrLit(by)] => (map, *by, None),
+            [map, Expr::StrLit(by), reverse] => (map, *by, Some(reverse)),
+            _ => {
+                return Err(
+                    "the `dictsort` filter takes an optional \"key\" or \"value\" \
+                            string literal and an optional reverse flag"
+                        .into(),
+                )
+            }
+        };
+        match by {
+            "key" => buf.write("::askama::filters::dictsort(&("),
+            "value" => buf.write("::askama::filters::dictsort_by_value(&("),
+            _ => {
+                return Err(format!(
+                    "the `dictsort` filter sorts by \"key\" or \"value\", not {:?}",
+                    by
+                )
+                .into())
+            }
+        }
+        self.visit_expr(buf, map)?;
+        buf.write("), ");
+        match reverse {
+            Some(reverse) => {
+                self.visit_expr(buf, reverse)?;
+            }
+            None => buf.write("false"),
+        }
+        buf.write(")?");
+        Ok(())
+    }
+
+    // The key given as a string is a (dotted) field path on the items, which
+    // is turned into a closure cloning that field.
+    fn _visit_groupby_filter(
+        &mut self,
+        buf: &mut Buffer,
+        args: &[Expr<'_>],
+    ) -> Result<(), CompileError> {
+        let key = match args {
+            [_, Expr::StrLit(key)] => key,
+            _ => return Err("the `groupby` filter takes the name of a field as a string".into()),
+        };
+        if !is_field_path(key) {
+            return Err(format!("invalid field name {:?} for the `groupby` filter", key).into());
+        }
+
+        buf.write("::askama::filters::groupby((&");
+        self.visit_expr(buf, &args[0])?;
+        buf.write(&format!(").into_iter(), |_item| _item.{}.clone())?", key));
+        Ok(())
+    }
+
+    // `date(pattern, tz = zone)` converts the value to the time zone first.
+    #[cfg(feature = "chrono-tz")]
+    fn _visit_date_tz_filter(
+        &mut self,
+        buf: &mut Buffer,
+        args: &[Expr<'_>],
+    ) -> Result<(), CompileError> {
+        let (value, pattern, tz) = match args {
+            [value, Expr::NamedArgument(_, tz)] => (value, None, tz),
+            [value, pattern, Expr::NamedArgument(_, tz)] => (value, Some(pattern), tz),
+            _ => {
+                return Err(
+                    "the `date` filter takes an optional pattern and a `tz` argument".into(),
+                )
+            }
+        };
+        buf.write("::askama::filters::date_tz(&(");
+        self.visit_expr(buf, value)?;
+        buf.write("), ");
+        match pattern {
+            Some(pattern) => {
+                buf.write("&(");
+                self.visit_expr(buf, pattern)?;
+                buf.write(")");
+            }
+            None => buf.write("()"),
+        }
+        buf.write(", &(");
+        self.visit_expr(buf, tz)?;
+        buf.write(&format!(
+            ")).map_err(|err| ::askama::Error::render({:?}, \"date\", err))?",
+            self.template_name(),
+        ));
+        Ok(())
+    }
+
+    #[cfg(not(feature = "chrono-tz"))]
+    fn _visit_date_tz_filter(
+        &mut self,
+        _: &mut Buffer,
+        _: &[Expr<'_>],
+    ) -> Result<(), CompileError> {
+        Err(
+            "the `tz` argument of the `date` filter requires the `chrono-tz` feature to be enabled"
+                .into(),
+        )
+    }
+
+    // A literal pattern is checked here, and compiled only once at runtime,
+    // by keeping it in a `static` next to the call.
+    fn _visit_matches_filter(
+        &mut self,
+        buf: &mut Buffer,
+        args: &[Expr<'_>],
+    ) -> Result<(), CompileError> {
+        let pattern = match args {
+            [_, Expr::StrLit(pattern)] => pattern,
+            _ => return Err("the `matches` filter takes a pattern".into()),
+        };
+        let value = syn::parse_str::<syn::LitStr>(&format!("\"{}\"", pattern))
+            .map_err(|err| format!("invalid string literal {:?}: {}", pattern, err))?
+            .value();
+        if let Err(err) = regex::Regex::new(&value) {
+            let message = format!("invalid pattern for the `matches` filter: {}", err);
+            return Err(self.locate(pattern, message.into()));
+        }
+
+        buf.write("::askama::filters::matches_static(&(");
+        self.visit_expr(buf, &args[0])?;
+        buf.write(&format!(
+            "), {{ \
+                static _REGEX: ::askama::filters::StaticRegex = \
+                    ::askama::filters::StaticRegex::new(\"{}\"); \
+                &_REGEX \
+            }})?",
+            pattern
+        ));
+        Ok(())
+    }
+
+    // Numbers implementing `Abs` are checked for overflow, other ones use their
+    // `num_traits::Signed` implementation, like in `_visit_trim_filter()`.
+    fn _visit_abs_filter(
+        &mut self,
+        buf: &mut Buffer,
+        args: &[Expr<'_>],
+    ) -> Result<(), CompileError> {
+        if args.len() != 1 {
+            return Err("the `abs` filter takes no arguments".into());
+        }
+        buf.write("::askama::filters::abs({ use ::askama::filters::AbsNumber as _; ");
+        #[cfg(feature = "num-traits")]
+        buf.write("use ::askama::filters::AbsSigned as _; ");
+        buf.write("::askama::filters::AbsSource(&(");
+        self.visit_expr(buf, &args[0])?;
+        buf.write(")).abs_source() })?");
+        Ok(())
+    }
+
+    // Strings are trimmed in place, other values formatted first; which one
+    // applies is decided by method resolution on `TrimSource`.
+    fn _visit_trim_filter(
+        &mut self,
+        buf: &mut Buffer,
+        name: &str,
+        args: &[Expr<'_>],
+    ) -> Result<(), CompileError> {
+        let (value, chars) = match args {
+            [value] => (value, None),
+            [value, chars] => (value, Some(chars)),
+            _ => {
+                return Err(
+                    format!("the `{}` filter takes an optional set of characters", name).into(),
+                )
+            }
+        };
+        buf.write(&format!(
+            "::askama::filters::{}({{ \
+             use ::askama::filters::{{TrimDisplay as _, TrimStr as _}}; \
+             ::askama::filters::TrimSource(&(",
+            name
+        ));
+        self.visit_expr(buf, value)?;
+        buf.write(")).trim_source() }, ");
+        match chars {
+            Some(chars) => {
+                buf.write("&(");
+                self.visit_expr(buf, chars)?;
+                buf.write(")");
+            }
+            None => buf.write("()"),
+        }
+        buf.write(")?");
+        Ok(())
+    }
+
+    // The end is optional, which the filter takes as an `Option`.
+    fn _visit_slice_filter(
+        &mut self,
+        buf: &mut Buffer,
+        args: &[Expr<'_>],
+    ) -> Result<(), CompileError> {
+        let (value, start, end) = match args {
+            [value, start] => (value, start, None),
+            [value, start, end] => (value, start, Some(end)),
+            _ => return Err("the `slice` filter takes a start and an optional end index".into()),
+        };
+        buf.write("::askama::filters::slice(&(");
+        self.visit_expr(buf, value)?;
+        buf.write("), (");
+        self.visit_expr(buf, start)?;
+        buf.write(") as isize, ");
+        match end {
+            Some(end) => {
+                buf.write("::std::option::Option::Some((");
+                self.visit_expr(buf, end)?;
+                buf.write(") as isize)");
+            }
+            None => buf.write("::std::option::Option::None"),
+        }
+        buf.write(")?");
+        Ok(())
+    }
+
+    // The optional field is a (dotted) field path on the items, as for
+    // `groupby`. Whether to compare case-insensitively is decided at compile
+    // time, as it requires string keys.
+    fn _visit_sort_filter(
         &mut self,
         buf: &mut Buffer,
         args: &[Expr<'_>],
     ) -> Result<(), CompileError> {
-        if args.len() > 2 {
-            return Err("only two arguments allowed to escape filter".into());
-        }
-        let opt_escaper = match args.get(1) {
-            Some(Expr::StrLit(name)) => Some(*name),
-            Some(_) => return Err("invalid escaper type for escape filter".into()),
-            None => None,
+        let (field, flags) = match args {
+            [_, Expr::StrLit(field), flags @ ..] => (Some(*field), flags),
+            [_, flags @ ..] => (None, flags),
+            [] => unreachable!(),
         };
-        let escaper = match opt_escaper {
-            Some(name) => self
-                .input
-                .config
-                .escapers
-                .iter()
-                .find_map(|(escapers, escaper)| escapers.contains(name).then(|| escaper))
-                .ok_or(CompileError::Static("invalid escaper for escape filter"))?,
-            None => self.input.escaper,
+        let (reverse, case_sensitive) = match flags {
+            [] => (None, true),
+            [reverse] => (Some(reverse), true),
+            [reverse, Expr::BoolLit(case_sensitive)] => (Some(reverse), *case_sensitive == "true"),
+            _ => {
+                return Err(
+                    "the `sort` filter takes an optional field name, an optional \
+                            reverse flag and an optional case_sensitive boolean literal"
+                        .into(),
+                )
+            }
         };
-        buf.write("::askama::filters::escape(");
-        buf.write(escaper);
-        buf.write(", ");
-        self._visit_args(buf, &args[..1])?;
+        if let Some(field) = field {
+            if !is_field_path(field) {
+                return Err(format!("invalid field name {:?} for the `sort` filter", field).into());
+            }
+        }
+
+        match (field, case_sensitive) {
+            (None, true) => buf.write("::askama::filters::sort((&"),
+            _ => buf.write("::askama::filters::sort_by((&"),
+        }
+        self.visit_expr(buf, &args[0])?;
+        buf.write(").into_iter(), ");
+        match reverse {
+            Some(reverse) => {
+                self.visit_expr(buf, reverse)?;
+            }
+            None => buf.write("false"),
+        }
+        let (a, b) = match field {
+            Some(field) => (format!("&_a.{}", field), format!("&_b.{}", field)),
+            None => ("_a".to_string(), "_b".to_string()),
+        };
+        match (field, case_sensitive) {
+            (None, true) => {}
+            (_, true) => buf.write(&format!(", |_a, _b| ::std::cmp::Ord::cmp({}, {})", a, b)),
+            (_, false) => buf.write(&format!(
+                ", |_a, _b| ::askama::filters::cmp_ignore_case({}, {})",
+                a, b
+            )),
+        }
         buf.write(")?");
         Ok(())
     }
 
-    fn _visit_format_filter(
+    // The field is borrowed from each item, so no `Clone` bound is needed.
+    fn _visit_map_filter(
         &mut self,
         buf: &mut Buffer,
         args: &[Expr<'_>],
     ) -> Result<(), CompileError> {
-        buf.write("format!(");
-        if let Some(Expr::StrLit(v)) = args.first() {
-            self.visit_str_lit(buf, v);
-            if args.len() > 1 {
-                buf.write(", ");
-            }
-        } else {
-            return Err("invalid expression type for format filter".into());
+        let field = match args {
+            [_, Expr::StrLit(field)] => field,
+            _ => return Err("the `map` filter takes the name of a field as a string".into()),
+        };
+        if !is_field_path(field) {
+            return Err(format!("invalid field name {:?} for the `map` filter", field).into());
         }
-        self._visit_args(buf, &args[1..])?;
-        buf.write(")");
+
+        buf.write("::askama::filters::map((&");
+        self.visit_expr(buf, &args[0])?;
+        buf.write(&format!(").into_iter(), |_item| &_item.{})?", field));
         Ok(())
     }
 
-    fn _visit_fmt_filter(
+    // The field is a (dotted) field path on the items, which is either a
+    // `bool` itself or compared to a value with one of Rust's comparison
+    // operators.
+    fn _visit_select_filter(
         &mut self,
         buf: &mut Buffer,
+        name: &str,
         args: &[Expr<'_>],
     ) -> Result<(), CompileError> {
-        buf.write("format!(");
-        if let Some(Expr::StrLit(v)) = args.get(1) {
-            self.visit_str_lit(buf, v);
-            buf.write(", ");
-        } else {
-            return Err("invalid expression type for fmt filter".into());
+        let (field, comparison) = match args {
+            [_, Expr::StrLit(field)] => (field, None),
+            [_, Expr::StrLit(field), Expr::StrLit(op), value] => (field, Some((op, value))),
+            _ => {
+                return Err(format!(
+                    "the `{}` filter takes the name of a field as a string, optionally \
+                     followed by a comparison operator as a string and a value",
+                    name
+                )
+                .into())
+            }
+        };
+        if !is_field_path(field) {
+            return Err(format!("invalid field name {:?} for the `{}` filter", field, name).into());
         }
-        self._visit_args(buf, &args[0..1])?;
-        if args.len() > 2 {
-            return Err("only two arguments allowed to fmt filter".into());
+
+        buf.write(&format!("::askama::filters::{}((&", name));
+        self.visit_expr(buf, &args[0])?;
+        buf.write(&format!(").into_iter(), |_item| _item.{}", field));
+        if let Some((op, value)) = comparison {
+            if !matches!(*op, "==" | "!=" | "<" | "<=" | ">" | ">=") {
+                return Err(format!(
+                    "invalid comparison operator {:?} for the `{}` filter, \
+                     expected one of ==, !=, <, <=, > or >=",
+                    op, name
+                )
+                .into());
+            }
+            buf.write(&format!(" {} (", op));
+            self.visit_expr(buf, value)?;
+            buf.write(")");
         }
-        buf.write(")");
+        buf.write(")?");
         Ok(())
     }
 
-    // Force type coercion on first argument to `join` filter (see #39).
-    fn _visit_join_filter(
+    fn _visit_unique_filter(
         &mut self,
         buf: &mut Buffer,
         args: &[Expr<'_>],
     ) -> Result<(), CompileError> {
-        buf.write("::askama::filters::join((&");
-        for (i, arg) in args.iter().enumerate() {
-            if i > 0 {
-                buf.write(", &");
+        let field = match args {
+            [_] => None,
+            [_, Expr::StrLit(field)] => Some(*field),
+            _ => {
+                return Err(
+                    "the `unique` filter takes an optional name of a field as a string".into(),
+                )
             }
-            self.visit_expr(buf, arg)?;
-            if i == 0 {
-                buf.write(").into_iter()");
+        };
+
+        match field {
+            Some(field) if !is_field_path(field) => {
+                return Err(
+                    format!("invalid field name {:?} for the `unique` filter", field).into(),
+                )
             }
+            Some(_) => buf.write("::askama::filters::unique_by((&"),
+            None => buf.write("::askama::filters::unique((&"),
         }
-        buf.write(")?");
+        self.visit_expr(buf, &args[0])?;
+        match field {
+            Some(field) => buf.write(&format!(").into_iter(), |_item| _item.{}.clone())?", field)),
+            None => buf.write(").into_iter())?"),
+        }
+        Ok(())
+    }
+
+    // The fill value is borrowed like the items, so it has to have their type.
+    fn _visit_batch_filter(
+        &mut self,
+        buf: &mut Buffer,
+        args: &[Expr<'_>],
+    ) -> Result<(), CompileError> {
+        let fill = match args {
+            [_, _] => None,
+            [_, _, fill] => Some(fill),
+            _ => return Err("the `batch` filter takes a size and an optional fill value".into()),
+        };
+
+        match fill {
+            Some(_) => buf.write("::askama::filters::batch_fill((&"),
+            None => buf.write("::askama::filters::batch((&"),
+        }
+        self.visit_expr(buf, &args[0])?;
+        buf.write(").into_iter(), ");
+        self.visit_expr(buf, &args[1])?;
+        if let Some(fill) = fill {
+            buf.write(", &(");
+            self.visit_expr(buf, fill)?;
+            buf.write(")");
+        }
+        buf.write(&format!(
+            ").map_err(|err| ::askama::Error::render({:?}, \"batch\", err))?",
+            self.template_name()
+        ));
         Ok(())
     }
 
@@ -1261,13 +3070,13 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
                 buf.write("&(");
             }
 
-            let scoped = matches!(
-                arg,
-                Expr::Filter(_, _)
-                    | Expr::MethodCall(_, _, _)
-                    | Expr::VarCall(_, _)
-                    | Expr::PathCall(_, _)
-            );
+            // The result of a filter that borrows from its input can't be
+            // returned from a block, which would drop the input first.
+            let scoped = match arg {
+                Expr::Filter(name, _) => !BORROWING_FILTERS.contains(name),
+                Expr::MethodCall(_, _, _) | Expr::VarCall(_, _) | Expr::PathCall(_, _) => true,
+                _ => false,
+            };
 
             if scoped {
                 buf.writeln("{")?;
@@ -1304,12 +3113,37 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
                 } else if attr == "last" {
                     buf.write("_loop_item.last");
                     return Ok(DisplayWrap::Unwrapped);
+                } else if attr == "length" {
+                    buf.write("_loop_length");
+                    return Ok(DisplayWrap::Unwrapped);
                 } else {
                     return Err("unknown loop variable".into());
                 }
             }
         }
-        self.visit_expr(buf, obj)?;
+        if attr == "await" && !self.input.is_async {
+            return Err(
+                "`.await` can only be used in templates declared with `async = true`".into(),
+            );
+        }
+        let mark = self.fields.len();
+        let mut obj_buf = Buffer::new(0);
+        self.visit_expr(&mut obj_buf, obj)?;
+        // Record `user.name` in place of the `user` just recorded for `obj`
+        if matches!(obj, Expr::Var(_) | Expr::Attr(..)) {
+            let path = match obj_buf.buf.as_str() {
+                "self" => Some(attr.to_string()),
+                code => code
+                    .strip_prefix("self.")
+                    .filter(|path| is_field_path(path))
+                    .map(|path| format!("{}.{}", path, attr)),
+            };
+            if let Some(path) = path {
+                self.fields.truncate(mark);
+                self.fields.push(path);
+            }
+        }
+        buf.write(&obj_buf.buf);
         buf.write(&format!(".{}", attr));
         Ok(DisplayWrap::Unwrapped)
     }
@@ -1328,6 +3162,77 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
         Ok(DisplayWrap::Unwrapped)
     }
 
+    // Whether `expr` is the `dynamic` field, or attributes and indexes of it.
+    fn is_dynamic_path(&self, expr: &Expr<'_>) -> bool {
+        match *expr {
+            _ if self.is_dynamic_root(expr) => true,
+            Expr::Attr(ref obj, _) | Expr::Index(ref obj, _) => self.is_dynamic_path(obj),
+            _ => false,
+        }
+    }
+
+    fn is_dynamic_root(&self, expr: &Expr<'_>) -> bool {
+        let dynamic = match self.input.dynamic.as_deref() {
+            Some(dynamic) => dynamic,
+            None => return false,
+        };
+        match *expr {
+            Expr::Var(name) => {
+                name == dynamic && !self.locals.contains(&name) && !self.is_closure_param(name)
+            }
+            Expr::Attr(ref obj, name) => name == dynamic && matches!(**obj, Expr::Var("self")),
+            _ => false,
+        }
+    }
+
+    // Looks a path up in the `dynamic` field as an `Option<&Value>`, which is
+    // `None` once a key or index is missing.
+    fn visit_dynamic_path(
+        &mut self,
+        buf: &mut Buffer,
+        expr: &Expr<'_>,
+    ) -> Result<(), CompileError> {
+        if self.is_dynamic_root(expr) {
+            buf.write("::askama::helpers::json_root(&");
+            match *expr {
+                Expr::Var(name) => {
+                    self.visit_var(buf, name)?;
+                }
+                _ => buf.write(&format!(
+                    "self.{}",
+                    normalize_identifier(self.input.dynamic.as_deref().unwrap())
+                )),
+            }
+            buf.write(")");
+            return Ok(());
+        }
+        match *expr {
+            Expr::Attr(ref obj, attr) => {
+                self.visit_dynamic_path(buf, obj)?;
+                buf.write(&format!(".and_then(|_v| _v.get({:?}))", attr));
+            }
+            Expr::Index(ref obj, ref key) => {
+                self.visit_dynamic_path(buf, obj)?;
+                buf.write(".and_then(|_v| _v.get(");
+                match **key {
+                    // An integer literal would default to `i32`.
+                    Expr::NumLit(n) if n.bytes().all(|b| b.is_ascii_digit()) => {
+                        buf.write(&format!("{}usize", n))
+                    }
+                    Expr::StrLit(s) => buf.write(&format!("\"{}\"", s)),
+                    _ => {
+                        buf.write("&(");
+                        self.visit_expr(buf, key)?;
+                        buf.write(")");
+                    }
+                }
+                buf.write("))");
+            }
+            _ => unreachable!("not a dynamic path"),
+        }
+        Ok(())
+    }
+
     fn visit_method_call(
         &mut self,
         buf: &mut Buffer,
@@ -1411,6 +3316,39 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
         Ok(DisplayWrap::Unwrapped)
     }
 
+    fn visit_if(
+        &mut self,
+        buf: &mut Buffer,
+        cond: &Expr<'_>,
+        then: &Expr<'_>,
+        otherwise: &Expr<'_>,
+    ) -> Result<DisplayWrap, CompileError> {
+        // See `write_cond()` for the `*(&(...) as &bool)` coercion. The
+        // branches are borrowed so that fields don't get moved out of `self`.
+        buf.write("(if *(&(");
+        self.visit_expr(buf, cond)?;
+        buf.write(") as &bool) { &(");
+        let then_wrap = self.visit_expr(buf, then)?;
+        buf.write(") } else { &(");
+        let otherwise_wrap = self.visit_expr(buf, otherwise)?;
+        buf.write(") })");
+        Ok(match (then_wrap, otherwise_wrap) {
+            (DisplayWrap::Wrapped, DisplayWrap::Wrapped) => DisplayWrap::Wrapped,
+            _ => DisplayWrap::Unwrapped,
+        })
+    }
+
+    fn visit_try(
+        &mut self,
+        buf: &mut Buffer,
+        expr: &Expr<'_>,
+    ) -> Result<DisplayWrap, CompileError> {
+        buf.write("::core::result::Result::map_err(");
+        self.visit_expr(buf, expr)?;
+        buf.write(", ::std::convert::Into::<::askama::Error>::into)?");
+        Ok(DisplayWrap::Unwrapped)
+    }
+
     fn visit_group(
         &mut self,
         buf: &mut Buffer,
@@ -1466,14 +3404,33 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
         Ok(DisplayWrap::Unwrapped)
     }
 
-    fn visit_var(&mut self, buf: &mut Buffer, s: &str) -> DisplayWrap {
+    fn visit_var(&mut self, buf: &mut Buffer, s: &str) -> Result<DisplayWrap, CompileError> {
         if s == "self" {
             buf.write(s);
-            return DisplayWrap::Unwrapped;
+            return Ok(DisplayWrap::Unwrapped);
+        }
+        if self.is_closure_param(s) {
+            buf.write(normalize_identifier(s));
+            return Ok(DisplayWrap::Unwrapped);
         }
 
-        buf.write(normalize_identifier(&self.locals.resolve_or_self(s)));
-        DisplayWrap::Unwrapped
+        let code = self.locals.resolve_or_self(s);
+        if let Some(field) = code
+            .strip_prefix("self.")
+            .filter(|path| is_field_path(path))
+        {
+            if self.locals.get(&s).is_none() {
+                self.check_field(s);
+            }
+            self.fields.push(field.to_string());
+        }
+        buf.write(normalize_identifier(&code));
+        Ok(DisplayWrap::Unwrapped)
+    }
+
+    fn is_closure_param(&self, name: &str) -> bool {
+        let name = normalize_identifier(name);
+        self.closure_params.iter().any(|param| param == name)
     }
 
     fn visit_var_call(
@@ -1482,9 +3439,21 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
         s: &str,
         args: &[Expr<'_>],
     ) -> Result<DisplayWrap, CompileError> {
+        if s == "super" {
+            return Err(
+                "`super()` renders the parent block's contents, so it can only be used \
+                        on its own, as `{{ super() }}`"
+                    .into(),
+            );
+        }
+        if let Some(extra) = self.input.extra.as_deref() {
+            if s == "get" && !self.locals.contains(&s) && !self.is_closure_param(s) {
+                return self.visit_extra_get(buf, extra, args);
+            }
+        }
         buf.write("(");
         let s = normalize_identifier(s);
-        if !self.locals.contains(&s) && s != "self" {
+        if !self.locals.contains(&s) && s != "self" && !self.is_closure_param(s) {
             buf.write("self.");
         }
         buf.write(s);
@@ -1494,6 +3463,27 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
         Ok(DisplayWrap::Unwrapped)
     }
 
+    // `get(key)` looks `key` up in the `extra` field, e.g. a
+    // `HashMap<String, String>`, as an `Option<&str>`.
+    fn visit_extra_get(
+        &mut self,
+        buf: &mut Buffer,
+        extra: &str,
+        args: &[Expr<'_>],
+    ) -> Result<DisplayWrap, CompileError> {
+        let key = match args {
+            [key] => key,
+            _ => return Err("`get()` takes the key of a value in the extra field".into()),
+        };
+        buf.write(&format!(
+            "self.{}.get(::std::convert::AsRef::<str>::as_ref(&(",
+            normalize_identifier(extra)
+        ));
+        self.visit_expr(buf, key)?;
+        buf.write("))).map(::std::convert::AsRef::<str>::as_ref)");
+        Ok(DisplayWrap::Unwrapped)
+    }
+
     fn visit_bool_lit(&mut self, buf: &mut Buffer, s: &str) -> DisplayWrap {
         buf.write(s);
         DisplayWrap::Unwrapped
@@ -1546,6 +3536,10 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
                 buf.write(&path.join("::"));
                 buf.write(" { ");
                 for (name, target) in targets {
+                    if let Target::Rest = target {
+                        buf.write("..");
+                        continue;
+                    }
                     buf.write(normalize_identifier(name));
                     buf.write(": ");
                     self.visit_target(buf, initialized, false, target);
@@ -1553,6 +3547,14 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
                 }
                 buf.write(" }");
             }
+            Target::Array(targets) => {
+                buf.write("[");
+                for target in targets {
+                    self.visit_target(buf, initialized, false, target);
+                    buf.write(",");
+                }
+                buf.write("]");
+            }
             Target::Path(path) => {
                 self.visit_path(buf, path);
             }
@@ -1580,6 +3582,36 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
                 }
                 buf.write(s);
             }
+            Target::Binding(name, target) => {
+                self.visit_target(buf, initialized, first_level, &Target::Name(name));
+                buf.write(" @ ");
+                self.visit_target(buf, initialized, first_level, target);
+            }
+            Target::Or(targets) => {
+                buf.write("(");
+                for (i, target) in targets.iter().enumerate() {
+                    if i > 0 {
+                        buf.write(" | ");
+                    }
+                    self.visit_target(buf, initialized, first_level, target);
+                }
+                buf.write(")");
+            }
+            Target::Range(op, start, end) => {
+                if first_level {
+                    buf.write("&");
+                }
+                buf.write("(");
+                if let Some(start) = start {
+                    self.visit_target(buf, initialized, false, start);
+                }
+                buf.write(op);
+                if let Some(end) = end {
+                    self.visit_target(buf, initialized, false, end);
+                }
+                buf.write(")");
+            }
+            Target::Rest => buf.write(".."),
         }
     }
 
@@ -1596,23 +3628,139 @@ impl<'a, S: std::hash::BuildHasher> Generator<'a, S> {
     // prefix whitespace suppressor from the given argument, flush that whitespace.
     // In either case, `next_ws` is reset to `None` (no trailing whitespace).
     fn flush_ws(&mut self, ws: Ws) {
-        if self.next_ws.is_some() && !ws.0 {
-            let val = self.next_ws.unwrap();
+        self.flush_ws_at(ws, true);
+    }
+
+    // Like `flush_ws()`, where `block` tells whether the whitespace is followed
+    // by a block tag (or comment), rather than an expression or the end.
+    fn flush_ws_at(&mut self, ws: Ws, block: bool) {
+        let suppress = ws.0 && self.whitespace != WhitespaceHandling::Preserve;
+        if let (Some(mut val), false) = (self.next_ws, suppress) {
+            if let Some(indent) = self
+                .next_ws_indent
+                .filter(|_| block && self.lstrip_blocks())
+            {
+                val = &val[..val.len() - indent];
+            }
+            let val = self.minimize_ws(val);
             if !val.is_empty() {
                 self.buf_writable.push(Writable::Lit(val));
             }
         }
         self.next_ws = None;
+        self.next_ws_indent = None;
     }
 
     // Sets `skip_ws` to match the suffix whitespace suppressor from the given
     // argument, to determine whether to suppress leading whitespace from the
     // next literal.
     fn prepare_ws(&mut self, ws: Ws) {
-        self.skip_ws = ws.1;
+        self.prepare_ws_at(ws, true);
+    }
+
+    // Like `prepare_ws()`, where `block` tells whether the whitespace follows a
+    // block tag (or comment), rather than an expression.
+    fn prepare_ws_at(&mut self, ws: Ws, block: bool) {
+        self.skip_ws = ws.1 && self.whitespace != WhitespaceHandling::Preserve;
+        self.trim_newline = block
+            && !self.skip_ws
+            && self.input.trim_blocks
+            && self.whitespace != WhitespaceHandling::Preserve;
+    }
+
+    fn lstrip_blocks(&self) -> bool {
+        self.input.lstrip_blocks && self.whitespace != WhitespaceHandling::Preserve
+    }
+
+    // In `minimize` mode, collapses whitespace next to a tag to a single
+    // newline or space, unless inside a `<pre>` element.
+    fn minimize_ws(&self, ws: &'a str) -> &'a str {
+        if self.whitespace != WhitespaceHandling::Minimize || self.in_pre || ws.is_empty() {
+            ws
+        } else if ws.contains('\n') {
+            "\n"
+        } else {
+            " "
+        }
+    }
+}
+
+// The name most similar to `name`, if some name is only a typo away from it
+fn closest_name(name: &str, names: impl Iterator<Item = String>) -> Option<String> {
+    let max = match name.chars().count() {
+        0..=2 => return None,
+        3..=5 => 1,
+        _ => 2,
+    };
+    names
+        .map(|candidate| (edit_distance(name, &candidate), candidate))
+        .filter(|(distance, _)| *distance <= max)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+// The number of inserted, removed, replaced and swapped characters turning
+// `a` into `b`, without editing a character twice
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut d: Vec<Vec<usize>> = (0..=a.len())
+        .map(|i| (0..=b.len()).map(|j| if i == 0 { j } else { i }).collect())
+        .collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let replaced = d[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = replaced.min(d[i - 1][j] + 1).min(d[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+// The name in an expression that errors about it are located at
+fn expr_name<'e>(expr: &Expr<'e>) -> Option<&'e str> {
+    match *expr {
+        Expr::Var(name)
+        | Expr::VarCall(name, _)
+        | Expr::Attr(_, name)
+        | Expr::Filter(name, _)
+        | Expr::MethodCall(_, name, _)
+        | Expr::NamedArgument(name, _) => Some(name),
+        _ => None,
+    }
+}
+
+// The length of the spaces and tabs after the last newline of `ws`, if any
+fn line_indent(ws: &str) -> Option<usize> {
+    let line = &ws[ws.rfind('\n')? + 1..];
+    line.bytes()
+        .all(|b| b == b' ' || b == b'\t')
+        .then_some(line.len())
+}
+
+// Removes the newline `ws` starts with if `trim` is set, for `trim_blocks`
+fn trim_first_newline(ws: &str, trim: bool) -> &str {
+    match trim {
+        true => ws
+            .strip_prefix('\n')
+            .or_else(|| ws.strip_prefix("\r\n"))
+            .unwrap_or(ws),
+        false => ws,
     }
 }
 
+// Finds the last opening `<pre>` tag (with or without attributes) in `s`.
+fn find_pre_open(s: &str) -> Option<usize> {
+    let s = s.to_ascii_lowercase();
+    s.match_indices("<pre")
+        .filter(|(i, tag)| {
+            matches!(s[i + tag.len()..].chars().next(), Some(c) if c == '>' || c.is_ascii_whitespace())
+        })
+        .map(|(i, _)| i)
+        .last()
+}
+
 struct Buffer {
     // The buffer to generate the code into
     buf: String,
@@ -1785,6 +3933,173 @@ impl MapChain<'_, &str, LocalMeta> {
     }
 }
 
+// The name of the method rendering the included template `template` with the
+// generated `body`: the template name is kept readable, with `_` escaping
+// other characters, and a hash of the body sets apart different code generated
+// for the same template, e.g. inside and outside of an HTML `<pre>` element.
+fn include_fn_name(template: &str, body: &str) -> String {
+    let mut name = String::from("_include_");
+    for c in template.chars() {
+        if c.is_ascii_alphanumeric() {
+            name.push(c);
+        } else {
+            name.push_str(&format!("_{:x}_", c as u32));
+        }
+    }
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    name.push_str(&format!("_{:016x}", hasher.finish()));
+    name
+}
+
+// Lints allowed on every generated impl. The code is derived from the template
+// source, so users have no way to address what these lints would report. Clippy
+// skips most of its lints in macro expansions; the ones below check the
+// arithmetic and indexing of template expressions regardless.
+const ALLOWED_LINTS: &str = "#[allow(\
+    unused, missing_docs, non_snake_case, \
+    clippy::arithmetic_side_effects, clippy::float_arithmetic, \
+    clippy::integer_division, clippy::integer_division_remainder_used, \
+    clippy::missing_asserts_for_indexing, clippy::modulo_arithmetic\
+)]";
+
+// The escaper of the `attr` filter
+const ATTR_ESCAPER: &str = "::askama::Attr";
+
+// Built-in filters taking any `Display` value as their input, which may be a
+// path into the `dynamic` field
+const DISPLAY_FILTERS: [&str; 37] = [
+    "attr",
+    "camelcase",
+    "capitalize",
+    "center",
+    "e",
+    "escape",
+    "fmt",
+    "format",
+    "highlight",
+    "indent",
+    "kebabcase",
+    "linebreaks",
+    "linebreaksbr",
+    "ljust",
+    "lower",
+    "lowercase",
+    "matches",
+    "paragraphbreaks",
+    "pascalcase",
+    "replace",
+    "rjust",
+    "safe",
+    "shoutycase",
+    "snakecase",
+    "striptags",
+    "title",
+    "trim",
+    "trim_end",
+    "trim_start",
+    "truncate",
+    "truncatewords",
+    "upper",
+    "uppercase",
+    "urlencode",
+    "urlencode_strict",
+    "wordcount",
+    "wordwrap",
+];
+
+// Built-in filters returning a reference into their input
+const BORROWING_FILTERS: [&str; 11] = [
+    "batch",
+    "first",
+    "last",
+    "random",
+    "random_seeded",
+    "reverse",
+    "slice",
+    "trim",
+    "trim_end",
+    "trim_start",
+    "unique",
+];
+
+// Whether `expr` is a filter borrowing from a temporary, such as the result of
+// another filter, which doesn't outlive the statement it is created in
+fn borrows_temporary(expr: &Expr<'_>) -> bool {
+    fn is_place(expr: &Expr<'_>) -> bool {
+        match expr {
+            Expr::Var(_) | Expr::Attr(_, _) | Expr::Index(_, _) | Expr::StrLit(_) => true,
+            Expr::Filter(name, args) if BORROWING_FILTERS.contains(name) => match args.first() {
+                Some(input) => is_place(input),
+                None => true,
+            },
+            _ => false,
+        }
+    }
+    matches!(expr, Expr::Filter(name, _) if BORROWING_FILTERS.contains(name)) && !is_place(expr)
+}
+
+// Whether `expr` (generated as `code`) only accesses (nested) fields of the
+// template struct, so it can also be evaluated in `size_hint()`.
+fn is_self_field(expr: &Expr<'_>, code: &str) -> bool {
+    fn is_field_path(expr: &Expr<'_>) -> bool {
+        match expr {
+            Expr::Var(_) => true,
+            Expr::Attr(obj, _) => is_field_path(obj),
+            _ => false,
+        }
+    }
+    code.starts_with("self.") && is_field_path(expr)
+}
+
+/// Whether `path` is a dotted path of field names or tuple indices
+fn is_field_path(path: &str) -> bool {
+    let is_ident = |s: &str| {
+        matches!(s.chars().next(), Some(c) if c.is_alphabetic() || c == '_')
+            && s.chars().all(|c| c.is_alphanumeric() || c == '_')
+    };
+    path.split('.')
+        .all(|part| is_ident(part) || part.parse::<usize>().is_ok())
+}
+
+/// Checks that a format spec given to the `format` filter formats the value,
+/// and only the value: it must have a single `{}` placeholder without an
+/// argument name or position, and without a width or precision taken from an
+/// argument.
+fn check_format_spec(spec: &str) -> Result<(), CompileError> {
+    let mut placeholders = 0;
+    let mut chars = spec.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+            }
+            '{' => {
+                let inner: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let (arg, format) = inner.split_once(':').unwrap_or((&inner, ""));
+                if !arg.is_empty() || format.contains('$') || format.contains('*') {
+                    return Err(format!(
+                        "format spec {:?} may only refer to the filtered value, \
+                         as in \"{{:.2}}\"",
+                        spec
+                    )
+                    .into());
+                }
+                placeholders += 1;
+            }
+            _ => {}
+        }
+    }
+    match placeholders {
+        1 => Ok(()),
+        _ => Err(format!(
+            "format spec {:?} must have exactly one placeholder, as in \"{{:.2}}\"",
+            spec
+        )
+        .into()),
+    }
+}
+
 fn median(sizes: &mut [usize]) -> usize {
     sizes.sort_unstable();
     if sizes.len() % 2 == 1 {
@@ -1794,6 +4109,135 @@ fn median(sizes: &mut [usize]) -> usize {
     }
 }
 
+/// Which of the `loop` variables are referenced by the body of a `for` loop.
+///
+/// Nested loops get their own `loop`, so only their iterable, condition and
+/// `else` block are taken into account. Included templates, macro calls and
+/// blocks can't be inspected here, so they are assumed to use everything.
+#[derive(Default)]
+struct LoopUsage {
+    item: bool,
+    length: bool,
+}
+
+impl LoopUsage {
+    fn of(nodes: &[Node<'_>]) -> Self {
+        let mut usage = LoopUsage::default();
+        usage.visit_nodes(nodes);
+        usage
+    }
+
+    fn visit_nodes(&mut self, nodes: &[Node<'_>]) {
+        for node in nodes {
+            match node {
+                Node::Lit(..)
+                | Node::Comment(_)
+                | Node::LetDecl(..)
+                | Node::Extends(_)
+                | Node::Import(..)
+                | Node::Macro(..)
+                | Node::Raw(..)
+                | Node::IncludeStr(..)
+                | Node::OutputComment(..)
+                | Node::Break(_)
+                | Node::Continue(_) => {}
+                Node::Expr(_, expr) | Node::Let(_, _, expr) | Node::Debug(_, expr) => {
+                    self.visit_expr(expr)
+                }
+                Node::Call(_, _, _, args) => {
+                    self.item = true;
+                    self.length = true;
+                    args.iter().for_each(|arg| self.visit_expr(arg));
+                }
+                Node::Cond(conds, _) => {
+                    for (_, test, nodes) in conds {
+                        if let Some(CondTest { expr, .. }) = test {
+                            self.visit_expr(expr);
+                        }
+                        self.visit_nodes(nodes);
+                    }
+                }
+                Node::Match(_, expr, arms, _) => {
+                    self.visit_expr(expr);
+                    for (_, _, guard, nodes) in arms {
+                        if let Some(guard) = guard {
+                            self.visit_expr(guard);
+                        }
+                        self.visit_nodes(nodes);
+                    }
+                }
+                Node::FilterBlock(_, filter, nodes, _) => {
+                    self.visit_expr(filter);
+                    self.visit_nodes(nodes);
+                }
+                Node::Capture(_, _, nodes, _) | Node::Spaceless(_, nodes, _) => {
+                    self.visit_nodes(nodes)
+                }
+                Node::Loop(inner) => {
+                    self.visit_expr(&inner.iter);
+                    if let Some(cond) = &inner.cond {
+                        self.visit_expr(cond);
+                    }
+                    self.visit_nodes(&inner.else_block);
+                }
+                // The included template is rendered with its own context.
+                Node::Include(_, _, Some(ctx), _) => self.visit_expr(ctx),
+                Node::BlockDef(..) | Node::Include(..) => {
+                    self.item = true;
+                    self.length = true;
+                }
+            }
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr<'_>) {
+        match expr {
+            Expr::BoolLit(_)
+            | Expr::NumLit(_)
+            | Expr::StrLit(_)
+            | Expr::CharLit(_)
+            | Expr::Path(_)
+            | Expr::RustMacro(..)
+            | Expr::FilterSource => {}
+            Expr::Var(name) => self.item |= *name == "loop",
+            Expr::Attr(obj, attr) => {
+                if matches!(**obj, Expr::Var("loop")) && *attr == "length" {
+                    self.length = true;
+                }
+                self.visit_expr(obj);
+            }
+            Expr::VarCall(_, args) | Expr::PathCall(_, args) | Expr::Filter(_, args) => {
+                args.iter().for_each(|arg| self.visit_expr(arg));
+            }
+            Expr::Array(elements) => elements.iter().for_each(|elem| self.visit_expr(elem)),
+            Expr::If(cond, then, otherwise) => {
+                self.visit_expr(cond);
+                self.visit_expr(then);
+                self.visit_expr(otherwise);
+            }
+            Expr::Index(obj, key) | Expr::BinOp(_, obj, key) => {
+                self.visit_expr(obj);
+                self.visit_expr(key);
+            }
+            Expr::Unary(_, expr)
+            | Expr::Group(expr)
+            | Expr::NamedArgument(_, expr)
+            | Expr::Closure(_, expr)
+            | Expr::Try(expr) => self.visit_expr(expr),
+            Expr::Range(_, start, end) => {
+                start
+                    .iter()
+                    .chain(end)
+                    .for_each(|expr| self.visit_expr(expr));
+            }
+            Expr::MethodCall(obj, _, args) => {
+                self.visit_expr(obj);
+                args.iter().for_each(|arg| self.visit_expr(arg));
+            }
+        }
+    }
+}
+
 #[derive(Clone, PartialEq)]
 enum AstLevel {
     Top,
@@ -1814,7 +4258,8 @@ impl Copy for DisplayWrap {}
 #[derive(Debug)]
 enum Writable<'a> {
     Lit(&'a str),
-    Expr(&'a Expr<'a>),
+    // The expression and the template it was written in
+    Expr(&'a Expr<'a>, Option<&'a Path>),
 }
 
 // Identifiers to be replaced with raw identifiers, so as to avoid
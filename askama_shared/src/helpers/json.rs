@@ -0,0 +1,93 @@
+use std::borrow::Borrow;
+use std::fmt;
+
+use serde_json::Value;
+
+/// Borrows the field a template names as `dynamic`, which is a
+/// `serde_json::Value` or a reference to one
+pub fn json_root<V: Borrow<Value> + ?Sized>(value: &V) -> Option<&Value> {
+    Some(value.borrow())
+}
+
+/// A value looked up in the `dynamic` field of a template, as rendered
+///
+/// Strings are written without quotes, a missing value or `null` writes
+/// nothing, and other values are written as JSON.
+#[derive(Clone, Copy, Debug)]
+pub struct JsonValue<'a>(pub Option<&'a Value>);
+
+impl<'a> JsonValue<'a> {
+    /// Replaces a missing or `null` value with `fallback`, like the `default`
+    /// filter
+    pub fn or<F: ?Sized>(self, fallback: &'a F) -> JsonOr<'a, F> {
+        self.or_if_empty(fallback, false)
+    }
+
+    /// Like [`JsonValue::or()`], but also replaces an empty string, array or
+    /// object if `if_empty` is set
+    pub fn or_if_empty<F: ?Sized>(self, fallback: &'a F, if_empty: bool) -> JsonOr<'a, F> {
+        match self.0 {
+            None | Some(Value::Null) => JsonOr::Fallback(fallback),
+            Some(value) if if_empty && is_empty(value) => JsonOr::Fallback(fallback),
+            Some(_) => JsonOr::Value(self),
+        }
+    }
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::String(s) => s.is_empty(),
+        Value::Array(items) => items.is_empty(),
+        Value::Object(entries) => entries.is_empty(),
+        _ => false,
+    }
+}
+
+impl fmt::Display for JsonValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            None | Some(Value::Null) => f.pad(""),
+            Some(Value::String(s)) => f.pad(s),
+            Some(value) => f.pad(&value.to_string()),
+        }
+    }
+}
+
+/// A [`JsonValue`], or the fallback it was replaced with
+#[derive(Debug)]
+pub enum JsonOr<'a, F: ?Sized> {
+    Value(JsonValue<'a>),
+    Fallback(&'a F),
+}
+
+impl<F: fmt::Display + ?Sized> fmt::Display for JsonOr<'_, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonOr::Value(value) => value.fmt(f),
+            JsonOr::Fallback(fallback) => fallback.fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_value() {
+        let data = json!({"title": "<T>", "count": 2, "tags": ["a"], "none": null, "empty": ""});
+        let get = |key: &str| JsonValue(json_root(&data).and_then(|v| v.get(key)));
+        assert_eq!(get("title").to_string(), "<T>");
+        assert_eq!(get("count").to_string(), "2");
+        assert_eq!(get("tags").to_string(), r#"["a"]"#);
+        assert_eq!(get("none").to_string(), "");
+        assert_eq!(get("missing").to_string(), "");
+
+        assert_eq!(get("title").or("-").to_string(), "<T>");
+        assert_eq!(get("none").or("-").to_string(), "-");
+        assert_eq!(get("missing").or(&0).to_string(), "0");
+        assert_eq!(get("empty").or("-").to_string(), "");
+        assert_eq!(get("empty").or_if_empty("-", true).to_string(), "-");
+    }
+}
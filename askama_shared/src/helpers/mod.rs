@@ -1,6 +1,14 @@
+use std::fmt;
 use std::iter::Enumerate;
 use std::iter::Peekable;
 
+use askama_escape::{Escaper, MarkupDisplay};
+
+#[cfg(feature = "serde_json")]
+mod json;
+#[cfg(feature = "serde_json")]
+pub use self::json::{json_root, JsonOr, JsonValue};
+
 pub struct TemplateLoop<I>
 where
     I: Iterator,
@@ -47,3 +55,261 @@ pub struct LoopItem {
     pub first: bool,
     pub last: bool,
 }
+
+/// Write a value straight into the output, bypassing the `fmt` machinery
+///
+/// The generated code prefers this trait over `Display` for types which
+/// implement it, falling back to `Display` for everything else.
+pub trait FastWritable {
+    fn write_into<W, E>(&self, dest: &mut W, escaper: &E) -> fmt::Result
+    where
+        W: fmt::Write + ?Sized,
+        E: Escaper;
+}
+
+impl<T: FastWritable + ?Sized> FastWritable for &T {
+    #[inline]
+    fn write_into<W, E>(&self, dest: &mut W, escaper: &E) -> fmt::Result
+    where
+        W: fmt::Write + ?Sized,
+        E: Escaper,
+    {
+        T::write_into(self, dest, escaper)
+    }
+}
+
+impl FastWritable for str {
+    #[inline]
+    fn write_into<W, E>(&self, dest: &mut W, escaper: &E) -> fmt::Result
+    where
+        W: fmt::Write + ?Sized,
+        E: Escaper,
+    {
+        escaper.write_escaped(dest, self)
+    }
+}
+
+impl FastWritable for String {
+    #[inline]
+    fn write_into<W, E>(&self, dest: &mut W, escaper: &E) -> fmt::Result
+    where
+        W: fmt::Write + ?Sized,
+        E: Escaper,
+    {
+        escaper.write_escaped(dest, self)
+    }
+}
+
+/// Escaped filter output, e.g. bound by `{% let %}`, is written as it is
+/// instead of being escaped again.
+impl<E2: Escaper, T: fmt::Display> FastWritable for MarkupDisplay<E2, T> {
+    #[inline]
+    fn write_into<W, E>(&self, dest: &mut W, _: &E) -> fmt::Result
+    where
+        W: fmt::Write + ?Sized,
+        E: Escaper,
+    {
+        write!(dest, "{}", self)
+    }
+}
+
+macro_rules! impl_fast_writable_for_int {
+    ($($ty:ty => |$n:ident| $abs:expr, $neg:expr;)*) => { $(
+        impl FastWritable for $ty {
+            fn write_into<W, E>(&self, dest: &mut W, escaper: &E) -> fmt::Result
+            where
+                W: fmt::Write + ?Sized,
+                E: Escaper,
+            {
+                let $n = *self;
+                // large enough for `i128::MIN`
+                let mut digits = [0u8; 40];
+                let mut pos = digits.len();
+                let mut rest = $abs;
+                loop {
+                    pos -= 1;
+                    digits[pos] = b'0' + (rest % 10) as u8;
+                    rest /= 10;
+                    if rest == 0 {
+                        break;
+                    }
+                }
+                if $neg {
+                    pos -= 1;
+                    digits[pos] = b'-';
+                }
+                // the buffer only ever contains ASCII digits and '-'
+                let s = std::str::from_utf8(&digits[pos..]).map_err(|_| fmt::Error)?;
+                escaper.write_escaped(dest, s)
+            }
+        }
+    )* };
+}
+
+impl_fast_writable_for_int!(
+    i8 => |n| n.unsigned_abs(), n < 0;
+    i16 => |n| n.unsigned_abs(), n < 0;
+    i32 => |n| n.unsigned_abs(), n < 0;
+    i64 => |n| n.unsigned_abs(), n < 0;
+    i128 => |n| n.unsigned_abs(), n < 0;
+    isize => |n| n.unsigned_abs(), n < 0;
+    u8 => |n| n, false;
+    u16 => |n| n, false;
+    u32 => |n| n, false;
+    u64 => |n| n, false;
+    u128 => |n| n, false;
+    usize => |n| n, false;
+);
+
+/// Wrapper used by generated code to pick [`FastWritable`] when possible
+///
+/// Method resolution prefers [`WriteFast`], implemented on `Writable<T>`
+/// itself, over [`WriteDisplay`], implemented on `&Writable<T>`, so that
+/// `(&Writable(&expr)).askama_write(..)` only falls back to `Display` if
+/// the expression's type does not implement [`FastWritable`].
+pub struct Writable<'a, T: ?Sized>(pub &'a T);
+
+pub trait WriteFast {
+    fn askama_write<W, E>(&self, dest: &mut W, escaper: E) -> fmt::Result
+    where
+        W: fmt::Write + ?Sized,
+        E: Escaper;
+}
+
+impl<T: FastWritable + ?Sized> WriteFast for Writable<'_, T> {
+    #[inline]
+    fn askama_write<W, E>(&self, dest: &mut W, escaper: E) -> fmt::Result
+    where
+        W: fmt::Write + ?Sized,
+        E: Escaper,
+    {
+        self.0.write_into(dest, &escaper)
+    }
+}
+
+pub trait WriteDisplay {
+    fn askama_write<W, E>(&self, dest: &mut W, escaper: E) -> fmt::Result
+    where
+        W: fmt::Write + ?Sized,
+        E: Escaper;
+}
+
+impl<T: fmt::Display + ?Sized> WriteDisplay for &Writable<'_, T> {
+    #[inline]
+    fn askama_write<W, E>(&self, dest: &mut W, escaper: E) -> fmt::Result
+    where
+        W: fmt::Write + ?Sized,
+        E: Escaper,
+    {
+        write!(dest, "{}", MarkupDisplay::new_unsafe(self.0, escaper))
+    }
+}
+
+/// Removes the whitespace between a `>` closing a tag and the `<` opening the
+/// next one, used by `{% spaceless %}` blocks
+///
+/// Whitespace next to text, and inside tags (such as in quoted attribute
+/// values), is kept.
+pub fn spaceless(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = String::with_capacity(s.len());
+    let (mut in_tag, mut quote) = (false, None);
+    let (mut start, mut i) = (0, 0);
+    while i < bytes.len() {
+        match (in_tag, quote) {
+            (false, _) => in_tag = bytes[i] == b'<',
+            (true, Some(q)) if bytes[i] == q => quote = None,
+            (true, Some(_)) => {}
+            (true, None) => match bytes[i] {
+                q @ (b'"' | b'\'') => quote = Some(q),
+                b'>' => {
+                    in_tag = false;
+                    let end = i
+                        + 1
+                        + bytes[i + 1..]
+                            .iter()
+                            .take_while(|b| b.is_ascii_whitespace())
+                            .count();
+                    if end > i + 1 && bytes.get(end) == Some(&b'<') {
+                        out.push_str(&s[start..=i]);
+                        start = end;
+                        i = end;
+                        continue;
+                    }
+                }
+                _ => {}
+            },
+        }
+        i += 1;
+    }
+    out.push_str(&s[start..]);
+    out
+}
+
+/// Minifies the HTML rendered by a template with `minify = true`
+///
+/// The output stays spec compliant, e.g. the doctype is kept as it is.
+#[cfg(feature = "minify")]
+pub fn minify(s: &str) -> crate::Result<String> {
+    let cfg = ::minify_html::Cfg::spec_compliant();
+    String::from_utf8(::minify_html::minify(s.as_bytes(), &cfg))
+        .map_err(|err| err.utf8_error().into())
+}
+
+#[cfg(test)]
+// The explicit borrows mirror the generated code, which relies on them to
+// pick between `WriteFast` and `WriteDisplay`.
+#[allow(clippy::needless_borrow)]
+mod tests {
+    use super::{spaceless, Writable, WriteDisplay as _, WriteFast as _};
+    use askama_escape::{Html, Text};
+
+    #[test]
+    fn test_fast_writable_ints() {
+        let mut buf = String::new();
+        (&Writable(&0u8)).askama_write(&mut buf, Text).unwrap();
+        buf.push(' ');
+        (&Writable(&-42i32)).askama_write(&mut buf, Html).unwrap();
+        buf.push(' ');
+        (&Writable(&i128::MIN))
+            .askama_write(&mut buf, Html)
+            .unwrap();
+        buf.push(' ');
+        (&Writable(&u64::MAX)).askama_write(&mut buf, Html).unwrap();
+        assert_eq!(
+            buf,
+            "0 -42 -170141183460469231731687303715884105728 18446744073709551615"
+        );
+    }
+
+    #[test]
+    fn test_fast_writable_strings() {
+        let mut buf = String::new();
+        (&Writable(&"<a>")).askama_write(&mut buf, Html).unwrap();
+        (&Writable(&String::from("&")))
+            .askama_write(&mut buf, Html)
+            .unwrap();
+        (&Writable(&"<b>")).askama_write(&mut buf, Text).unwrap();
+        assert_eq!(buf, "&lt;a&gt;&amp;<b>");
+    }
+
+    #[test]
+    fn test_display_fallback() {
+        let mut buf = String::new();
+        (&Writable(&1.5f32)).askama_write(&mut buf, Html).unwrap();
+        (&Writable(&'<')).askama_write(&mut buf, Html).unwrap();
+        assert_eq!(buf, "1.5&lt;");
+    }
+
+    #[test]
+    fn test_spaceless() {
+        assert_eq!(spaceless(""), "");
+        assert_eq!(spaceless("<p>\n  <a> x </a>\n</p>"), "<p><a> x </a></p>");
+        assert_eq!(spaceless("a <b> c\n</b> d"), "a <b> c\n</b> d");
+        assert_eq!(
+            spaceless("<a title=\"x >  <y\" alt='> <'>  <b>"),
+            "<a title=\"x >  <y\" alt='> <'><b>"
+        );
+        assert_eq!(spaceless("<br>\t \n"), "<br>\t \n");
+    }
+}
@@ -1,4 +1,6 @@
 use std::collections::HashMap;
+use std::env;
+use std::hash::BuildHasher;
 use std::path::{Path, PathBuf};
 
 use crate::parser::{Expr, Loop, Macro, Node};
@@ -10,9 +12,9 @@ pub struct Heritage<'a> {
 }
 
 impl<'a> Heritage<'a> {
-    pub fn new<'n>(
+    pub fn new<'n, S: BuildHasher>(
         mut ctx: &'n Context<'n>,
-        contexts: &'n HashMap<&'n PathBuf, Context<'n>>,
+        contexts: &'n HashMap<&'n PathBuf, Context<'n>, S>,
     ) -> Heritage<'n> {
         let mut blocks: BlockAncestry<'n> = ctx
             .blocks
@@ -34,8 +36,13 @@ impl<'a> Heritage<'a> {
 type BlockAncestry<'a> = HashMap<&'a str, Vec<(&'a Context<'a>, &'a Node<'a>)>>;
 
 pub struct Context<'a> {
+    pub path: PathBuf,
+    /// The source the nodes were parsed from, to locate errors in
+    pub source: &'a str,
     pub nodes: &'a [Node<'a>],
     pub extends: Option<PathBuf>,
+    /// Parents extended instead of `extends` if their condition holds
+    pub extends_if: Vec<(&'a Expr<'a>, PathBuf)>,
     pub blocks: HashMap<&'a str, &'a Node<'a>>,
     pub macros: HashMap<&'a str, &'a Macro<'a>>,
     pub imports: HashMap<&'a str, PathBuf>,
@@ -45,9 +52,11 @@ impl<'a> Context<'a> {
     pub fn new<'n>(
         config: &Config<'_>,
         path: &Path,
+        source: &'n str,
         nodes: &'n [Node<'n>],
     ) -> Result<Context<'n>, CompileError> {
         let mut extends = None;
+        let mut extends_if = Vec::new();
         let mut blocks = Vec::new();
         let mut macros = HashMap::new();
         let mut imports = HashMap::new();
@@ -57,10 +66,16 @@ impl<'a> Context<'a> {
         while let Some(nodes) = nested.pop() {
             for n in nodes {
                 match n {
-                    Node::Extends(Expr::StrLit(extends_path)) if top => match extends {
+                    Node::Extends(parents) if top => match extends {
                         Some(_) => return Err("multiple extend blocks found".into()),
                         None => {
-                            extends = Some(config.find_template(extends_path, Some(path))?);
+                            for (parent, cond) in parents {
+                                let parent = config.find_template(parent, Some(path))?;
+                                match cond {
+                                    Some(cond) => extends_if.push((cond, parent)),
+                                    None => extends = Some(parent),
+                                }
+                            }
                         }
                     },
                     Node::Macro(name, m) if top => {
@@ -75,11 +90,22 @@ impl<'a> Context<'a> {
                             "extends, macro or import blocks not allowed below top level".into(),
                         );
                     }
-                    def @ Node::BlockDef(_, _, _, _) => {
-                        blocks.push(def);
-                        if let Node::BlockDef(_, _, nodes, _) = def {
-                            nested.push(nodes);
+                    def @ Node::BlockDef(_, name, required, nodes, _) => {
+                        // A required block only marks where overrides go.
+                        let placeholder = nodes.iter().all(|n| match n {
+                            Node::Lit(_, val, _) => val.is_empty(),
+                            Node::Comment(_) => true,
+                            _ => false,
+                        });
+                        if *required && !placeholder {
+                            return Err(format!(
+                                "required block '{}' can only contain whitespace and comments",
+                                name
+                            )
+                            .into());
                         }
+                        blocks.push(def);
+                        nested.push(nodes);
                     }
                     Node::Cond(branches, _) => {
                         for (_, _, nodes) in branches {
@@ -93,33 +119,64 @@ impl<'a> Context<'a> {
                         nested.push(else_block);
                     }
                     Node::Match(_, _, arms, _) => {
-                        for (_, _, arm) in arms {
+                        for (_, _, _, arm) in arms {
                             nested.push(arm);
                         }
                     }
+                    Node::FilterBlock(_, _, nodes, _)
+                    | Node::Capture(_, _, nodes, _)
+                    | Node::Spaceless(_, nodes, _) => {
+                        nested.push(nodes);
+                    }
                     _ => {}
                 }
             }
             top = false;
         }
 
-        let blocks: HashMap<_, _> = blocks
-            .iter()
-            .map(|def| {
-                if let Node::BlockDef(_, name, _, _) = def {
-                    (*name, *def)
-                } else {
-                    unreachable!()
+        // A second definition of a block in the same template would silently
+        // replace the first one, so it's an error rather than an override.
+        let mut block_map = HashMap::with_capacity(blocks.len());
+        for def in blocks {
+            if let Node::BlockDef(_, name, _, _, _) = def {
+                if block_map.insert(*name, def).is_some() {
+                    let path = env::var_os("CARGO_MANIFEST_DIR")
+                        .and_then(|root| path.strip_prefix(root).ok())
+                        .unwrap_or(path);
+                    return Err(format!(
+                        "block '{}' is defined more than once in {}",
+                        name,
+                        path.display()
+                    )
+                    .into());
                 }
-            })
-            .collect();
+            }
+        }
+        let blocks = block_map;
 
         Ok(Context {
+            path: path.to_path_buf(),
+            source,
             nodes,
             extends,
+            extends_if,
             blocks,
             macros,
             imports,
         })
     }
+
+    /// The same template, unconditionally extending `parent`
+    pub fn with_parent(&self, parent: &Path) -> Context<'a> {
+        Context {
+            path: self.path.clone(),
+            source: self.source,
+            nodes: self.nodes,
+            extends: Some(parent.to_path_buf()),
+            extends_if: Vec::new(),
+            blocks: self.blocks.clone(),
+            macros: self.macros.clone(),
+            imports: self.imports.clone(),
+        }
+    }
 }
@@ -1,129 +1,96 @@
-use crate::{CompileError, Config, Syntax};
+use crate::{CompileError, Config, Syntax, WhitespaceHandling};
 
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use quote::ToTokens;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::Token;
 
 pub struct TemplateInput<'a> {
     pub ast: &'a syn::DeriveInput,
     pub config: &'a Config<'a>,
-    pub syntax: &'a Syntax<'a>,
+    pub syntax: Syntax<'a>,
     pub source: Source,
     pub print: Print,
     pub escaper: &'a str,
     pub ext: Option<String>,
     pub parent: Option<&'a syn::Type>,
     pub path: PathBuf,
+    pub is_async: bool,
+    pub whitespace: WhitespaceHandling,
+    pub trim_blocks: bool,
+    pub lstrip_blocks: bool,
+    /// Whether the rendered output is run through an HTML minifier
+    pub minify: bool,
+    pub mime_type: String,
+    /// The only block to render, if any
+    pub block: Option<String>,
+    /// The path of the module custom filters are called from
+    pub filters: String,
+    pub include_mode: IncludeMode,
+    /// The field holding the runtime values read by `get()`, if any
+    pub extra: Option<String>,
+    /// The `serde_json::Value` field paths into which are looked up at
+    /// runtime, if any
+    pub dynamic: Option<String>,
 }
 
 impl<'a> TemplateInput<'a> {
     /// Extract the template metadata from the `DeriveInput` structure. This
-    /// mostly recovers the data for the `TemplateInput` fields from the
-    /// `template()` attribute list fields; it also finds the of the `_parent`
-    /// field, if any.
+    /// mostly validates the data from the `template()` attribute list fields
+    /// (as collected in `TemplateArgs`) against the configuration; it also
+    /// finds the of the `_parent` field, if any.
     pub fn new<'n>(
         ast: &'n syn::DeriveInput,
         config: &'n Config<'_>,
+        args: &'n TemplateArgs,
     ) -> Result<TemplateInput<'n>, CompileError> {
-        // Check that an attribute called `template()` exists and that it is
-        // the proper type (list).
-        let meta = ast
-            .attrs
-            .iter()
-            .find_map(|attr| {
-                attr.path.is_ident("template").then(|| {
-                    attr.parse_meta()
-                        .map_err(|e| format!("unable to parse attribute: {}", e).into())
-                })
-            })
-            .unwrap_or(Err(CompileError::Static("no attribute 'template' found")))?;
-
-        let meta_list = match meta {
-            syn::Meta::List(inner) => inner,
-            _ => return Err("attribute 'template' has incorrect type".into()),
-        };
-
-        // Loop over the meta attributes and find everything that we
-        // understand. Raise panics if something is not right.
-        // `source` contains an enum that can represent `path` or `source`.
-        let mut source = None;
-        let mut print = Print::None;
-        let mut escaping = None;
-        let mut ext = None;
-        let mut syntax = None;
-        for item in meta_list.nested {
-            let pair = match item {
-                syn::NestedMeta::Meta(syn::Meta::NameValue(ref pair)) => pair,
-                _ => {
-                    return Err(format!(
-                        "unsupported attribute argument {:?}",
-                        item.to_token_stream()
-                    )
-                    .into())
-                }
-            };
-
-            if pair.path.is_ident("path") {
-                if let syn::Lit::Str(ref s) = pair.lit {
-                    if source.is_some() {
-                        return Err("must specify 'source' or 'path', not both".into());
-                    }
-                    source = Some(Source::Path(s.value()));
-                } else {
-                    return Err("template path must be string literal".into());
-                }
-            } else if pair.path.is_ident("source") {
-                if let syn::Lit::Str(ref s) = pair.lit {
-                    if source.is_some() {
-                        return Err("must specify 'source' or 'path', not both".into());
-                    }
-                    source = Some(Source::Source(s.value()));
-                } else {
-                    return Err("template source must be string literal".into());
-                }
-            } else if pair.path.is_ident("print") {
-                if let syn::Lit::Str(ref s) = pair.lit {
-                    print = s.value().parse()?;
-                } else {
-                    return Err("print value must be string literal".into());
-                }
-            } else if pair.path.is_ident("escape") {
-                if let syn::Lit::Str(ref s) = pair.lit {
-                    escaping = Some(s.value());
-                } else {
-                    return Err("escape value must be string literal".into());
-                }
-            } else if pair.path.is_ident("ext") {
-                if let syn::Lit::Str(ref s) = pair.lit {
-                    ext = Some(s.value());
-                } else {
-                    return Err("ext value must be string literal".into());
-                }
-            } else if pair.path.is_ident("syntax") {
-                if let syn::Lit::Str(ref s) = pair.lit {
-                    syntax = Some(s.value())
-                } else {
-                    return Err("syntax value must be string literal".into());
-                }
-            } else {
-                return Err(format!(
-                    "unsupported attribute key '{}' found",
-                    pair.path.to_token_stream()
-                )
-                .into());
-            }
-        }
+        let TemplateArgs {
+            source,
+            print,
+            escaping,
+            ext,
+            syntax,
+            delimiters,
+            is_async,
+            whitespace,
+            trim_blocks,
+            lstrip_blocks,
+            minify,
+            mime_type,
+            name,
+            partials: _,
+            block,
+            filters,
+            include_mode,
+            extra,
+            dynamic,
+            config: _,
+            source_file,
+        } = args;
 
         // Validate the `source` and `ext` value together, since they are
         // related. In case `source` was used instead of `path`, the value
         // of `ext` is merged into a synthetic `path` value here.
-        let source = source.expect("template path or source not found in attributes");
-        let path = match (&source, &ext) {
-            (&Source::Path(ref path), _) => config.find_template(path, None)?,
-            (&Source::Source(_), Some(ext)) => PathBuf::from(format!("{}.{}", ast.ident, ext)),
-            (&Source::Source(_), None) => {
-                return Err("must include 'ext' attribute when using 'source' attribute".into())
+        let source = source
+            .clone()
+            .expect("template path or source not found in attributes");
+        let path = match (&source, ext, name) {
+            (Source::Path(_), _, Some(_)) => {
+                return Err("the 'name' attribute can only be used with 'source'".into())
+            }
+            (Source::Path(path), _, None) if path.starts_with("./") || path.starts_with("../") => {
+                config.find_relative_template(path, source_file.as_deref())?
+            }
+            (Source::Path(path), _, None) => config.find_template(path, None)?,
+            (Source::Source(_), _, Some(name)) => PathBuf::from(name),
+            (Source::Source(_), Some(ext), None) => PathBuf::from(format!("{}.{}", ast.ident, ext)),
+            (Source::Source(_), None, None) => {
+                return Err(
+                    "must include 'ext' or 'name' attribute when using 'source' attribute".into(),
+                )
             }
         };
 
@@ -141,6 +108,40 @@ impl<'a> TemplateInput<'a> {
             _ => None,
         };
 
+        let has_field = |name: &str| match ast.data {
+            syn::Data::Struct(syn::DataStruct {
+                fields: syn::Fields::Named(ref fields),
+                ..
+            }) => fields
+                .named
+                .iter()
+                .any(|f| f.ident.as_ref().filter(|ident| *ident == name).is_some()),
+            _ => false,
+        };
+        if let Some(extra) = extra {
+            if !has_field(extra) {
+                return Err(
+                    format!("extra field '{}' not found in struct {}", extra, ast.ident).into(),
+                );
+            }
+        }
+        if let Some(dynamic) = dynamic {
+            #[cfg(not(feature = "serde_json"))]
+            return Err(format!(
+                "the dynamic field '{}' requires the `serde-json` feature to be enabled",
+                dynamic
+            )
+            .into());
+            #[cfg(feature = "serde_json")]
+            if !has_field(dynamic) {
+                return Err(format!(
+                    "dynamic field '{}' not found in struct {}",
+                    dynamic, ast.ident
+                )
+                .into());
+            }
+        }
+
         if parent.is_some() {
             eprint!(
                 "   --> in struct {}\n   = use of deprecated field '_parent'\n",
@@ -149,18 +150,32 @@ impl<'a> TemplateInput<'a> {
         }
 
         // Validate syntax
-        let syntax = syntax.map_or_else(
-            || Ok(config.syntaxes.get(config.default_syntax).unwrap()),
-            |s| {
-                config.syntaxes.get(&s).ok_or_else(|| {
-                    CompileError::String(format!("attribute syntax {} not exist", s))
-                })
-            },
-        )?;
+        let syntax = match (syntax, delimiters) {
+            (Some(_), Some(_)) => {
+                return Err("must specify 'syntax' or 'delimiters', not both".into());
+            }
+            (None, Some([block, expr, comment])) => {
+                let syntax = Syntax {
+                    block_start: &block.0,
+                    block_end: &block.1,
+                    expr_start: &expr.0,
+                    expr_end: &expr.1,
+                    comment_start: &comment.0,
+                    comment_end: &comment.1,
+                };
+                syntax.validate()?;
+                syntax
+            }
+            (Some(s), None) => *config
+                .syntaxes
+                .get(s)
+                .ok_or_else(|| CompileError::String(format!("attribute syntax {} not exist", s)))?,
+            (None, None) => *config.syntaxes.get(config.default_syntax).unwrap(),
+        };
 
         // Match extension against defined output formats
 
-        let extension = escaping.unwrap_or_else(|| {
+        let extension = escaping.clone().unwrap_or_else(|| {
             path.extension()
                 .map(|s| s.to_str().unwrap())
                 .unwrap_or("")
@@ -175,29 +190,88 @@ impl<'a> TemplateInput<'a> {
             }
         }
 
+        // Only an escaper inferred from the extension falls back.
+        if escaping.is_none() {
+            escaper = escaper.or(config.fallback_escaper.as_ref());
+        }
         let escaper = escaper.ok_or_else(|| {
-            CompileError::String(format!("no escaper defined for extension '{}'", extension,))
+            CompileError::String(format!(
+                "no escaper defined for extension '{}', configure one for it \
+                 or a `fallback_escaper`",
+                extension,
+            ))
         })?;
 
+        let mime_type = match mime_type {
+            Some(mime_type) => mime_type.clone(),
+            None => {
+                let ext = ext.as_deref().or_else(|| self::extension(&path));
+                extension_to_mime_type(ext.unwrap_or("")).to_string()
+            }
+        };
+
+        let filters = match filters {
+            Some(filters) => match syn::parse_str::<syn::Path>(filters) {
+                Ok(path) => path.to_token_stream().to_string().replace(' ', ""),
+                Err(_) => {
+                    return Err(format!(
+                        "filters value must be a module path, such as \"crate::filters\", \
+                         not {:?}",
+                        filters
+                    )
+                    .into())
+                }
+            },
+            None => "filters".to_string(),
+        };
+
         Ok(TemplateInput {
             ast,
             config,
             syntax,
             source,
-            print,
+            print: *print,
             escaper,
-            ext,
+            ext: ext.clone(),
             parent,
             path,
+            is_async: *is_async,
+            whitespace: match whitespace {
+                Some(whitespace) => whitespace.parse()?,
+                None => config.whitespace,
+            },
+            trim_blocks: trim_blocks.unwrap_or(config.trim_blocks),
+            lstrip_blocks: lstrip_blocks.unwrap_or(config.lstrip_blocks),
+            // The configuration only applies to HTML, unlike the attribute.
+            minify: minify.unwrap_or(config.minify && mime_type.starts_with("text/html")),
+            mime_type,
+            block: block.clone(),
+            filters,
+            include_mode: match include_mode {
+                Some(include_mode) => include_mode.parse()?,
+                None => IncludeMode::Inline,
+            },
+            extra: extra.clone(),
+            dynamic: dynamic.clone(),
         })
     }
 
     pub fn extension(&self) -> Option<&str> {
         self.ext.as_deref().or_else(|| extension(&self.path))
     }
+
+    /// The path to name a template by in error messages, unless its source
+    /// isn't read from a file
+    pub fn file_path<'p>(&self, path: &'p Path) -> Option<&'p Path> {
+        match self.source {
+            Source::Source(_) if path == self.path => None,
+            _ if self.config.sources.contains_key(path) => None,
+            _ => Some(path),
+        }
+    }
 }
 
-fn extension(path: &Path) -> Option<&str> {
+pub(crate) fn extension(path: &Path) -> Option<&str> {
     let ext = path.extension().map(|s| s.to_str().unwrap())?;
 
     const JINJA_EXTENSIONS: [&str; 3] = ["j2", "jinja", "jinja2"];
@@ -211,12 +285,330 @@ fn extension(path: &Path) -> Option<&str> {
     }
 }
 
+/// The MIME types of the rendered templates, by template extension
+const MIME_TYPES: [(&str, &str); 12] = [
+    ("css", "text/css; charset=utf-8"),
+    ("csv", "text/csv; charset=utf-8"),
+    ("htm", "text/html; charset=utf-8"),
+    ("html", "text/html; charset=utf-8"),
+    ("js", "application/javascript; charset=utf-8"),
+    ("json", "application/json"),
+    ("md", "text/markdown; charset=utf-8"),
+    ("svg", "image/svg+xml"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("xhtml", "application/xhtml+xml"),
+    ("xml", "application/xml"),
+    ("yaml", "application/yaml"),
+];
+
+/// The MIME type used for extensions not found in `MIME_TYPES`
+const DEFAULT_MIME_TYPE: &str = "text/plain; charset=utf-8";
+
+pub fn extension_to_mime_type(ext: &str) -> &'static str {
+    MIME_TYPES
+        .iter()
+        .find(|(e, _)| e.eq_ignore_ascii_case(ext))
+        .map_or(DEFAULT_MIME_TYPE, |(_, mime_type)| mime_type)
+}
+
+/// The arguments of the `template()` attribute, as given by the user. These
+/// are collected before the configuration is read, which they are then
+/// checked against in `TemplateInput::new()`.
+pub struct TemplateArgs {
+    pub source: Option<Source>,
+    pub print: Print,
+    pub escaping: Option<String>,
+    pub ext: Option<String>,
+    pub syntax: Option<String>,
+    /// The start and end delimiters for blocks, expressions and comments.
+    pub delimiters: Option<[(String, String); 3]>,
+    /// Generate an `async fn render_async()` instead of implementing `Template`.
+    pub is_async: bool,
+    pub whitespace: Option<String>,
+    /// Overrides the `trim_blocks` configuration.
+    pub trim_blocks: Option<bool>,
+    /// Overrides the `lstrip_blocks` configuration.
+    pub lstrip_blocks: Option<bool>,
+    /// Overrides the `minify` configuration.
+    pub minify: Option<bool>,
+    /// Overrides the MIME type derived from the template's extension.
+    pub mime_type: Option<String>,
+    /// The virtual path of a `source` template.
+    pub name: Option<String>,
+    /// Further templates given in Rust source, by their virtual path.
+    pub partials: Vec<(String, String)>,
+    /// Renders only the block of this name.
+    pub block: Option<String>,
+    /// The module custom filters are called from, instead of `filters`.
+    pub filters: Option<String>,
+    /// Whether included templates are inlined or generated as methods.
+    pub include_mode: Option<String>,
+    /// The field holding the runtime values read by `get()`.
+    pub extra: Option<String>,
+    /// The `serde_json::Value` field whose paths are looked up at runtime.
+    pub dynamic: Option<String>,
+    /// The path of the configuration file, relative to the crate root.
+    pub config: Option<String>,
+    /// The Rust source file deriving `Template`, if the compiler tells.
+    pub source_file: Option<PathBuf>,
+}
+
+impl TemplateArgs {
+    pub fn new(ast: &syn::DeriveInput) -> Result<Self, CompileError> {
+        // Check that an attribute called `template()` exists and that it is
+        // the proper type (list).
+        let attr = ast
+            .attrs
+            .iter()
+            .find(|attr| attr.path.is_ident("template"))
+            .ok_or(CompileError::Static("no attribute 'template' found"))?;
+        if attr.tokens.is_empty() {
+            return Err("attribute 'template' has incorrect type".into());
+        }
+        let items = attr
+            .parse_args_with(Punctuated::<AttrArg, Token![,]>::parse_terminated)
+            .map_err(|e| format!("unable to parse attribute: {}", e))?;
+
+        // Loop over the meta attributes and find everything that we
+        // understand. Raise panics if something is not right.
+        // `source` contains an enum that can represent `path` or `source`.
+        let mut args = TemplateArgs {
+            source: None,
+            print: Print::None,
+            escaping: None,
+            ext: None,
+            syntax: None,
+            delimiters: None,
+            is_async: false,
+            whitespace: None,
+            trim_blocks: None,
+            lstrip_blocks: None,
+            minify: None,
+            mime_type: None,
+            name: None,
+            partials: Vec::new(),
+            block: None,
+            filters: None,
+            include_mode: None,
+            extra: None,
+            dynamic: None,
+            config: None,
+            source_file: None,
+        };
+        for pair in items {
+            if pair.path.is_ident("path") {
+                if let AttrValue::Lit(syn::Lit::Str(ref s)) = pair.value {
+                    if args.source.is_some() {
+                        return Err("must specify 'source' or 'path', not both".into());
+                    }
+                    args.source = Some(Source::Path(s.value()));
+                } else {
+                    return Err("template path must be string literal".into());
+                }
+            } else if pair.path.is_ident("source") {
+                if let AttrValue::Lit(syn::Lit::Str(ref s)) = pair.value {
+                    if args.source.is_some() {
+                        return Err("must specify 'source' or 'path', not both".into());
+                    }
+                    args.source = Some(Source::Source(s.value()));
+                } else {
+                    return Err("template source must be string literal".into());
+                }
+            } else if pair.path.is_ident("print") {
+                if let AttrValue::Lit(syn::Lit::Str(ref s)) = pair.value {
+                    args.print = s.value().parse()?;
+                } else {
+                    return Err("print value must be string literal".into());
+                }
+            } else if pair.path.is_ident("escape") {
+                if let AttrValue::Lit(syn::Lit::Str(ref s)) = pair.value {
+                    args.escaping = Some(s.value());
+                } else {
+                    return Err("escape value must be string literal".into());
+                }
+            } else if pair.path.is_ident("ext") {
+                if let AttrValue::Lit(syn::Lit::Str(ref s)) = pair.value {
+                    args.ext = Some(s.value());
+                } else {
+                    return Err("ext value must be string literal".into());
+                }
+            } else if pair.path.is_ident("syntax") {
+                if let AttrValue::Lit(syn::Lit::Str(ref s)) = pair.value {
+                    args.syntax = Some(s.value())
+                } else {
+                    return Err("syntax value must be string literal".into());
+                }
+            } else if pair.path.is_ident("delimiters") {
+                args.delimiters = Some(parse_delimiters(&pair.value)?);
+            } else if pair.path.is_ident("whitespace") {
+                if let AttrValue::Lit(syn::Lit::Str(ref s)) = pair.value {
+                    args.whitespace = Some(s.value());
+                } else {
+                    return Err("whitespace value must be string literal".into());
+                }
+            } else if pair.path.is_ident("trim_blocks") {
+                if let AttrValue::Lit(syn::Lit::Bool(ref b)) = pair.value {
+                    args.trim_blocks = Some(b.value);
+                } else {
+                    return Err("trim_blocks value must be a boolean literal".into());
+                }
+            } else if pair.path.is_ident("lstrip_blocks") {
+                if let AttrValue::Lit(syn::Lit::Bool(ref b)) = pair.value {
+                    args.lstrip_blocks = Some(b.value);
+                } else {
+                    return Err("lstrip_blocks value must be a boolean literal".into());
+                }
+            } else if pair.path.is_ident("minify") {
+                if let AttrValue::Lit(syn::Lit::Bool(ref b)) = pair.value {
+                    args.minify = Some(b.value);
+                } else {
+                    return Err("minify value must be a boolean literal".into());
+                }
+            } else if pair.path.is_ident("mime_type") {
+                if let AttrValue::Lit(syn::Lit::Str(ref s)) = pair.value {
+                    args.mime_type = Some(s.value());
+                } else {
+                    return Err("mime_type value must be string literal".into());
+                }
+            } else if pair.path.is_ident("name") {
+                if let AttrValue::Lit(syn::Lit::Str(ref s)) = pair.value {
+                    args.name = Some(s.value());
+                } else {
+                    return Err("name value must be string literal".into());
+                }
+            } else if pair.path.is_ident("partial") {
+                match pair.value {
+                    AttrValue::List(ref list) if list.len() == 2 => {
+                        args.partials.push((list[0].value(), list[1].value()));
+                    }
+                    _ => {
+                        return Err("partial value must be a virtual path and a template \
+                                    source, e.g. (\"base.html\", \"...\")"
+                            .into())
+                    }
+                }
+            } else if pair.path.is_ident("block") {
+                if let AttrValue::Lit(syn::Lit::Str(ref s)) = pair.value {
+                    args.block = Some(s.value());
+                } else {
+                    return Err("block value must be string literal".into());
+                }
+            } else if pair.path.is_ident("filters") {
+                if let AttrValue::Lit(syn::Lit::Str(ref s)) = pair.value {
+                    args.filters = Some(s.value());
+                } else {
+                    return Err("filters value must be string literal".into());
+                }
+            } else if pair.path.is_ident("include_mode") {
+                if let AttrValue::Lit(syn::Lit::Str(ref s)) = pair.value {
+                    args.include_mode = Some(s.value());
+                } else {
+                    return Err("include_mode value must be string literal".into());
+                }
+            } else if pair.path.is_ident("extra") {
+                if let AttrValue::Lit(syn::Lit::Str(ref s)) = pair.value {
+                    args.extra = Some(s.value());
+                } else {
+                    return Err("extra value must be string literal".into());
+                }
+            } else if pair.path.is_ident("dynamic") {
+                if let AttrValue::Lit(syn::Lit::Str(ref s)) = pair.value {
+                    args.dynamic = Some(s.value());
+                } else {
+                    return Err("dynamic value must be string literal".into());
+                }
+            } else if pair.path.is_ident("config") {
+                if let AttrValue::Lit(syn::Lit::Str(ref s)) = pair.value {
+                    args.config = Some(s.value());
+                } else {
+                    return Err("config value must be string literal".into());
+                }
+            } else if pair.path.is_ident("async") {
+                if let AttrValue::Lit(syn::Lit::Bool(ref b)) = pair.value {
+                    args.is_async = b.value;
+                } else {
+                    return Err("async value must be a boolean literal".into());
+                }
+            } else {
+                return Err(format!(
+                    "unsupported attribute key '{}' found",
+                    pair.path.to_token_stream()
+                )
+                .into());
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// Parse a `delimiters = ("<% %>", "<%= %>", "<%# %>")` value: the start and
+/// end delimiters for blocks, expressions and comments, separated by spaces.
+fn parse_delimiters(value: &AttrValue) -> Result<[(String, String); 3], CompileError> {
+    const EXPECTED: &str =
+        "delimiters must be a list of three strings for blocks, expressions and comments, \
+         e.g. (\"{% %}\", \"{{ }}\", \"{# #}\")";
+
+    let list = match value {
+        AttrValue::List(list) if list.len() == 3 => list,
+        _ => return Err(EXPECTED.into()),
+    };
+
+    let pair = |s: &syn::LitStr| {
+        let s = s.value();
+        let mut parts = s.split_whitespace();
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(start), Some(end), None) => Ok((start.to_string(), end.to_string())),
+            _ => Err(CompileError::String(format!(
+                "invalid delimiters {:?}: expected a start and an end delimiter \
+                 separated by a space",
+                s
+            ))),
+        }
+    };
+    Ok([pair(&list[0])?, pair(&list[1])?, pair(&list[2])?])
+}
+
+/// A single `key = value` argument of the `template()` attribute. Unlike
+/// `syn::MetaNameValue`, the value may be a parenthesized list of strings.
+struct AttrArg {
+    path: syn::Path,
+    value: AttrValue,
+}
+
+enum AttrValue {
+    Lit(syn::Lit),
+    List(Vec<syn::LitStr>),
+}
+
+impl Parse for AttrArg {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        // `async` is a keyword, so it can't be parsed as a path.
+        let path = if input.peek(Token![async]) {
+            let token = input.parse::<Token![async]>()?;
+            syn::Ident::new("async", token.span).into()
+        } else {
+            input.parse()?
+        };
+        input.parse::<Token![=]>()?;
+        let value = if input.peek(syn::token::Paren) {
+            let content;
+            syn::parenthesized!(content in input);
+            let list = Punctuated::<syn::LitStr, Token![,]>::parse_terminated(&content)?;
+            AttrValue::List(list.into_iter().collect())
+        } else {
+            AttrValue::Lit(input.parse()?)
+        };
+        Ok(AttrArg { path, value })
+    }
+}
+
+#[derive(Clone)]
 pub enum Source {
     Path(String),
     Source(String),
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum Print {
     All,
     Ast,
@@ -239,6 +631,28 @@ impl FromStr for Print {
     }
 }
 
+/// How the code generator handles `{% include %}`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IncludeMode {
+    /// The included template's code is generated at each include site
+    Inline,
+    /// The included template is generated as a method of its own, which is
+    /// called from each include site that doesn't need its local variables
+    Function,
+}
+
+impl FromStr for IncludeMode {
+    type Err = CompileError;
+
+    fn from_str(s: &str) -> Result<IncludeMode, Self::Err> {
+        Ok(match s {
+            "inline" => IncludeMode::Inline,
+            "function" => IncludeMode::Function,
+            v => return Err(format!("invalid value for include_mode option: {}", v).into()),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -279,6 +693,16 @@ mod tests {
         assert_eq!(extension(Path::new("foo/bar/baz.txt.jinja2")), Some("txt"));
     }
 
+    #[test]
+    fn test_mime_type() {
+        assert_eq!(extension_to_mime_type("html"), "text/html; charset=utf-8");
+        assert_eq!(extension_to_mime_type("HTML"), "text/html; charset=utf-8");
+        assert_eq!(extension_to_mime_type("json"), "application/json");
+        assert_eq!(extension_to_mime_type("txt"), "text/plain; charset=utf-8");
+        assert_eq!(extension_to_mime_type("unknown"), DEFAULT_MIME_TYPE);
+        assert_eq!(extension_to_mime_type(""), DEFAULT_MIME_TYPE);
+    }
+
     #[test]
     fn test_only_jinja_ext() {
         assert_eq!(extension(Path::new("foo-bar.j2")), Some("j2"));
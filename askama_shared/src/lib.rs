@@ -1,9 +1,10 @@
 #![cfg_attr(feature = "cargo-clippy", allow(unused_parens))]
 #![deny(elided_lifetimes_in_paths)]
 
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::TryFrom;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::{env, fmt, fs};
 
 #[cfg(feature = "serde")]
@@ -30,6 +31,26 @@ pub struct Config<'a> {
     pub syntaxes: BTreeMap<String, Syntax<'a>>,
     pub default_syntax: &'a str,
     pub escapers: Vec<(HashSet<String>, String)>,
+    /// The escaper of templates with an extension no escaper is configured
+    /// for, which are an error otherwise
+    pub fallback_escaper: Option<String>,
+    /// How `{#! … !#}` comments are written for the templates with these
+    /// extensions: the start and end of the comment, where an empty end
+    /// starts each line with the start instead
+    pub comments: Vec<(HashSet<String>, String, String)>,
+    pub loop_size_factor: usize,
+    pub whitespace: WhitespaceHandling,
+    /// Whether the first newline after a block tag is removed
+    pub trim_blocks: bool,
+    /// Whether the spaces and tabs before a block tag starting a line are removed
+    pub lstrip_blocks: bool,
+    /// Whether warnings about templates are errors instead
+    pub strict: bool,
+    /// Whether the output of HTML templates is minified
+    pub minify: bool,
+    /// Templates given in Rust source, by their virtual path, which are found
+    /// before any template file.
+    pub sources: HashMap<PathBuf, String>,
 }
 
 impl<'a> Config<'a> {
@@ -46,17 +67,51 @@ impl<'a> Config<'a> {
             RawConfig::from_toml_str(s)?
         };
 
-        let (dirs, default_syntax) = match raw.general {
+        let (
+            dirs,
+            default_syntax,
+            loop_size_factor,
+            whitespace,
+            strict,
+            trim_blocks,
+            lstrip_blocks,
+            minify,
+            fallback_escaper,
+        ) = match raw.general {
             Some(General {
                 dirs,
                 default_syntax,
+                loop_size_factor,
+                whitespace,
+                strict,
+                trim_blocks,
+                lstrip_blocks,
+                minify,
+                fallback_escaper,
             }) => (
                 dirs.map_or(default_dirs, |v| {
                     v.into_iter().map(|dir| root.join(dir)).collect()
                 }),
                 default_syntax.unwrap_or(DEFAULT_SYNTAX_NAME),
+                loop_size_factor.unwrap_or(DEFAULT_LOOP_SIZE_FACTOR),
+                whitespace.map_or(Ok(WhitespaceHandling::Suppress), str::parse)?,
+                strict.unwrap_or(false),
+                trim_blocks.unwrap_or(false),
+                lstrip_blocks.unwrap_or(false),
+                minify.unwrap_or(false),
+                fallback_escaper,
+            ),
+            None => (
+                default_dirs,
+                DEFAULT_SYNTAX_NAME,
+                DEFAULT_LOOP_SIZE_FACTOR,
+                WhitespaceHandling::Suppress,
+                false,
+                false,
+                false,
+                false,
+                None,
             ),
-            None => (default_dirs, DEFAULT_SYNTAX_NAME),
         };
 
         if let Some(raw_syntaxes) = raw.syntax {
@@ -92,12 +147,106 @@ impl<'a> Config<'a> {
         for (extensions, path) in DEFAULT_ESCAPERS {
             escapers.push((str_set(extensions), (*path).to_string()));
         }
+        let fallback_escaper = match fallback_escaper {
+            Some(name) => match escapers.iter().find(|(exts, _)| exts.contains(name)) {
+                Some((_, path)) => Some(path.clone()),
+                None => {
+                    return Err(format!("no escaper named {:?} for fallback_escaper", name).into())
+                }
+            },
+            None => None,
+        };
+
+        let mut comments = Vec::new();
+        for comment in raw.comment.unwrap_or_default() {
+            comments.push((
+                str_set(&comment.extensions),
+                comment.start.to_string(),
+                comment.end.unwrap_or("").to_string(),
+            ));
+        }
+        for (extensions, start, end) in DEFAULT_COMMENTS {
+            comments.push((str_set(extensions), start.to_string(), end.to_string()));
+        }
 
         Ok(Config {
             dirs,
             syntaxes,
             default_syntax,
             escapers,
+            fallback_escaper,
+            comments,
+            loop_size_factor,
+            whitespace,
+            trim_blocks,
+            lstrip_blocks,
+            strict,
+            minify,
+            sources: HashMap::new(),
+        })
+    }
+
+    /// Finds the first of several candidate templates that exists, e.g. for
+    /// `{% include "theme/a.html" or "a.html" %}`
+    pub fn find_first_template(
+        &self,
+        paths: &[&str],
+        start_at: Option<&Path>,
+    ) -> std::result::Result<PathBuf, CompileError> {
+        if let [path] = paths {
+            return self.find_template(path, start_at);
+        }
+        paths
+            .iter()
+            .find_map(|path| self.find_template(path, start_at).ok())
+            .ok_or_else(|| {
+                format!(
+                    "none of the templates {:?} found in directories {:?}",
+                    paths, self.dirs
+                )
+                .into()
+            })
+    }
+
+    /// Registers a template given in Rust source under a virtual path, so it
+    /// can be extended, included or imported like a template file.
+    pub fn add_source(&mut self, name: &str, source: String) {
+        self.sources.insert(PathBuf::from(name), source);
+    }
+
+    /// Gets the source of a template, either given in Rust source or read
+    /// from its file.
+    pub fn template_source(&self, path: &Path) -> std::result::Result<String, CompileError> {
+        match self.sources.get(path) {
+            Some(source) => Ok(source.clone()),
+            None => get_template_source(path),
+        }
+    }
+
+    /// Resolves a `./` or `../` template path against the directory of the
+    /// Rust source file deriving `Template`, instead of the template dirs.
+    pub fn find_relative_template(
+        &self,
+        path: &str,
+        source_file: Option<&Path>,
+    ) -> std::result::Result<PathBuf, CompileError> {
+        let source_file = source_file.ok_or_else(|| {
+            format!(
+                "template {:?} is relative to the deriving source file, \
+                 whose location is unknown",
+                path
+            )
+        })?;
+        let dir = source_file.parent().unwrap_or_else(|| Path::new(""));
+        // Rooted, since `include_bytes!()` resolves relative paths against
+        // the file it is expanded in rather than the working directory.
+        std::fs::canonicalize(dir.join(path)).map_err(|_| {
+            format!(
+                "template {:?} not found next to {:?}",
+                path,
+                source_file.display()
+            )
+            .into()
         })
     }
 
@@ -106,6 +255,10 @@ impl<'a> Config<'a> {
         path: &str,
         start_at: Option<&Path>,
     ) -> std::result::Result<PathBuf, CompileError> {
+        if self.sources.contains_key(Path::new(path)) {
+            return Ok(PathBuf::from(path));
+        }
+
         if let Some(root) = start_at {
             let relative = root.with_file_name(path);
             if relative.exists() {
@@ -128,7 +281,7 @@ impl<'a> Config<'a> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct Syntax<'a> {
     pub block_start: &'a str,
     pub block_end: &'a str,
@@ -151,6 +304,43 @@ impl<'a> Default for Syntax<'a> {
     }
 }
 
+impl Syntax<'_> {
+    /// Check that the delimiters can be told apart by the parser.
+    pub(crate) fn validate(&self) -> std::result::Result<(), CompileError> {
+        let delimiters = [
+            ("block_start", self.block_start),
+            ("block_end", self.block_end),
+            ("expr_start", self.expr_start),
+            ("expr_end", self.expr_end),
+            ("comment_start", self.comment_start),
+            ("comment_end", self.comment_end),
+        ];
+        if let Some((name, _)) = delimiters.iter().find(|(_, d)| d.is_empty()) {
+            return Err(format!("delimiter {} must not be empty", name).into());
+        }
+
+        // The parser tries comments first, then expressions, then blocks, so
+        // none of these start delimiters may be a prefix of a later one.
+        let starts = [
+            ("comment_start", self.comment_start),
+            ("expr_start", self.expr_start),
+            ("block_start", self.block_start),
+        ];
+        for (i, (a_name, a)) in starts.iter().enumerate() {
+            for (b_name, b) in &starts[i + 1..] {
+                if b.starts_with(a) {
+                    return Err(format!(
+                        "delimiters overlap: {} {:?} is a prefix of {} {:?}",
+                        a_name, a, b_name, b
+                    )
+                    .into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 impl<'a> TryFrom<RawSyntax<'a>> for Syntax<'a> {
     type Error = CompileError;
 
@@ -165,26 +355,7 @@ impl<'a> TryFrom<RawSyntax<'a>> for Syntax<'a> {
             comment_end: raw.comment_end.unwrap_or(default.comment_end),
         };
 
-        if syntax.block_start.len() != 2
-            || syntax.block_end.len() != 2
-            || syntax.expr_start.len() != 2
-            || syntax.expr_end.len() != 2
-            || syntax.comment_start.len() != 2
-            || syntax.comment_end.len() != 2
-        {
-            return Err("length of delimiters must be two".into());
-        }
-
-        let bs = syntax.block_start.as_bytes()[0];
-        let be = syntax.block_start.as_bytes()[1];
-        let cs = syntax.comment_start.as_bytes()[0];
-        let ce = syntax.comment_start.as_bytes()[1];
-        let es = syntax.block_start.as_bytes()[0];
-        let ee = syntax.block_start.as_bytes()[1];
-        if !((bs == cs && bs == es) || (be == ce && be == ee)) {
-            return Err(format!("bad delimiters block_start: {}, comment_start: {}, expr_start: {}, needs one of the two characters in common", syntax.block_start, syntax.comment_start, syntax.expr_start).into());
-        }
-
+        syntax.validate()?;
         Ok(syntax)
     }
 }
@@ -196,6 +367,7 @@ struct RawConfig<'d> {
     general: Option<General<'d>>,
     syntax: Option<Vec<RawSyntax<'d>>>,
     escaper: Option<Vec<RawEscaper<'d>>>,
+    comment: Option<Vec<RawComment<'d>>>,
 }
 
 impl<'d> RawConfig<'d> {
@@ -217,6 +389,45 @@ struct General<'a> {
     #[cfg_attr(feature = "serde", serde(borrow))]
     dirs: Option<Vec<&'a str>>,
     default_syntax: Option<&'a str>,
+    loop_size_factor: Option<usize>,
+    whitespace: Option<&'a str>,
+    trim_blocks: Option<bool>,
+    lstrip_blocks: Option<bool>,
+    strict: Option<bool>,
+    minify: Option<bool>,
+    fallback_escaper: Option<&'a str>,
+}
+
+/// How the whitespace around block tags, expressions and comments is handled
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WhitespaceHandling {
+    /// Whitespace is kept, except where suppressed with `-` markers
+    Suppress,
+    /// All whitespace is kept, `-` markers are ignored
+    Preserve,
+    /// Like `Suppress`, but any other run of whitespace next to a tag is
+    /// collapsed to a single newline (if it contains one) or space
+    Minimize,
+}
+
+impl FromStr for WhitespaceHandling {
+    type Err = CompileError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "suppress" => WhitespaceHandling::Suppress,
+            "preserve" => WhitespaceHandling::Preserve,
+            "minimize" => WhitespaceHandling::Minimize,
+            v => {
+                return Err(format!(
+                    "invalid value for whitespace: {:?}, expected \"suppress\", \
+                     \"preserve\" or \"minimize\"",
+                    v
+                )
+                .into())
+            }
+        })
+    }
 }
 
 #[cfg_attr(feature = "serde", derive(Deserialize))]
@@ -236,18 +447,37 @@ struct RawEscaper<'a> {
     extensions: Vec<&'a str>,
 }
 
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+struct RawComment<'a> {
+    start: &'a str,
+    end: Option<&'a str>,
+    extensions: Vec<&'a str>,
+}
+
+/// Reads the configuration file at the path in the `ASKAMA_CONFIG`
+/// environment variable, or else `askama.toml` in the crate root if it exists.
 pub fn read_config_file() -> std::result::Result<String, CompileError> {
-    let root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
-    let filename = root.join(CONFIG_FILE_NAME);
-    if filename.exists() {
-        fs::read_to_string(&filename).map_err(|_| {
-            CompileError::String(format!("unable to read {}", filename.to_str().unwrap()))
-        })
-    } else {
-        Ok("".to_string())
+    match env::var_os(CONFIG_ENV_VAR) {
+        Some(config_path) => read_config_file_at(config_path.as_ref()),
+        None => {
+            let root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+            if root.join(CONFIG_FILE_NAME).exists() {
+                read_config_file_at(CONFIG_FILE_NAME.as_ref())
+            } else {
+                Ok("".to_string())
+            }
+        }
     }
 }
 
+/// Reads the configuration file at `config_path`, relative to the crate root
+pub fn read_config_file_at(config_path: &Path) -> std::result::Result<String, CompileError> {
+    let root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let filename = root.join(config_path);
+    fs::read_to_string(&filename)
+        .map_err(|_| CompileError::String(format!("unable to read {}", filename.to_str().unwrap())))
+}
+
 fn str_set<T>(vals: &[T]) -> HashSet<String>
 where
     T: ToString,
@@ -284,24 +514,51 @@ pub struct Integrations {
 }
 
 static CONFIG_FILE_NAME: &str = "askama.toml";
+// Environment variable overriding the path of the configuration file
+static CONFIG_ENV_VAR: &str = "ASKAMA_CONFIG";
 static DEFAULT_SYNTAX_NAME: &str = "default";
+// Number of iterations assumed for a loop when estimating the rendered size
+static DEFAULT_LOOP_SIZE_FACTOR: usize = 2;
+static DEFAULT_COMMENTS: &[(&[&str], &str, &str)] = &[
+    (
+        &[
+            "htm", "html", "j2", "jinja", "jinja2", "md", "svg", "xhtml", "xml",
+        ],
+        "<!-- ",
+        " -->",
+    ),
+    (&["css"], "/* ", " */"),
+    (
+        &[
+            "c", "cpp", "go", "h", "java", "js", "rs", "scss", "swift", "ts",
+        ],
+        "// ",
+        "",
+    ),
+    (&["py", "sh", "toml", "yaml", "yml"], "# ", ""),
+    (&["lua", "sql"], "-- ", ""),
+];
+
 static DEFAULT_ESCAPERS: &[(&[&str], &str)] = &[
-    (&["html", "htm", "xml"], "::askama::Html"),
-    (&["md", "none", "txt", "yml", ""], "::askama::Text"),
+    (&["html", "htm", "svg", "xml"], "::askama::Html"),
+    (&["csv", "md", "none", "txt", "yml", ""], "::askama::Text"),
     (&["j2", "jinja", "jinja2"], "::askama::Html"),
+    (&["json"], "::askama::Json"),
 ];
 
 #[derive(Debug)]
 pub enum CompileError {
     Static(&'static str),
     String(String),
+    /// An error already pointing at the part of a template it is about
+    Located(String),
 }
 
 impl fmt::Display for CompileError {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             CompileError::Static(s) => write!(fmt, "{}", s),
-            CompileError::String(s) => write!(fmt, "{}", s),
+            CompileError::String(s) | CompileError::Located(s) => write!(fmt, "{}", s),
         }
     }
 }
@@ -333,6 +590,16 @@ mod tests {
         assert_eq!(get_template_source(&path).unwrap(), "bar");
     }
 
+    #[test]
+    fn test_read_config_file() {
+        let config = read_config_file_at(Path::new("templates/b.html")).unwrap();
+        assert_eq!(config, "bar\n");
+
+        let err = read_config_file_at(Path::new("missing.toml")).unwrap_err();
+        assert!(err.to_string().starts_with("unable to read "));
+        assert!(err.to_string().ends_with("missing.toml"));
+    }
+
     #[test]
     fn test_default_config() {
         let mut root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
@@ -350,6 +617,80 @@ mod tests {
         assert_eq!(config.dirs, vec![root]);
     }
 
+    #[cfg(feature = "config")]
+    #[test]
+    fn test_config_loop_size_factor() {
+        assert_eq!(Config::new("").unwrap().loop_size_factor, 2);
+        let config = Config::new("[general]\nloop_size_factor = 10").unwrap();
+        assert_eq!(config.loop_size_factor, 10);
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn test_config_whitespace() {
+        let config = Config::new("").unwrap();
+        assert_eq!(config.whitespace, WhitespaceHandling::Suppress);
+        let config = Config::new("[general]\nwhitespace = \"minimize\"").unwrap();
+        assert_eq!(config.whitespace, WhitespaceHandling::Minimize);
+        let err = Config::new("[general]\nwhitespace = \"trim\"").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid value for whitespace: \"trim\", expected \"suppress\", \"preserve\" or \"minimize\""
+        );
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn test_config_minify() {
+        assert!(!Config::new("").unwrap().minify);
+        assert!(Config::new("[general]\nminify = true").unwrap().minify);
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn test_config_trim_blocks() {
+        let config = Config::new("").unwrap();
+        assert!(!config.trim_blocks && !config.lstrip_blocks);
+        let config = Config::new("[general]\ntrim_blocks = true").unwrap();
+        assert!(config.trim_blocks && !config.lstrip_blocks);
+        let config = Config::new("[general]\nlstrip_blocks = true").unwrap();
+        assert!(!config.trim_blocks && config.lstrip_blocks);
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn test_config_strict() {
+        assert!(!Config::new("").unwrap().strict);
+        assert!(Config::new("[general]\nstrict = true").unwrap().strict);
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn test_config_fallback_escaper() {
+        assert_eq!(Config::new("").unwrap().fallback_escaper, None);
+        let config = Config::new("[general]\nfallback_escaper = \"html\"").unwrap();
+        assert_eq!(config.fallback_escaper.as_deref(), Some("::askama::Html"));
+
+        let config = Config::new(
+            r#"
+            [general]
+            fallback_escaper = "tex"
+
+            [[escaper]]
+            path = "::my_crate::Tex"
+            extensions = ["tex"]
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.fallback_escaper.as_deref(), Some("::my_crate::Tex"));
+
+        let err = Config::new("[general]\nfallback_escaper = \"latex\"").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no escaper named \"latex\" for fallback_escaper"
+        );
+    }
+
     fn assert_eq_rooted(actual: &Path, expected: &str) {
         let mut root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
         root.push("templates");
@@ -390,6 +731,33 @@ mod tests {
         assert_eq_rooted(&path, "sub/sub1/d.html");
     }
 
+    #[test]
+    fn find_first() {
+        let config = Config::new("").unwrap();
+        let path = config
+            .find_first_template(&["missing.html", "sub/b.html", "a.html"], None)
+            .unwrap();
+        assert_eq_rooted(&path, "sub/b.html");
+        let err = config
+            .find_first_template(&["missing.html", "other.html"], None)
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("none of the templates [\"missing.html\", \"other.html\"] found"));
+    }
+
+    #[test]
+    fn find_source() {
+        let mut config = Config::new("").unwrap();
+        config.add_source("b.html", "inline".to_string());
+        let path = config.find_template("b.html", None).unwrap();
+        assert_eq!(path, Path::new("b.html"));
+        assert_eq!(config.template_source(&path).unwrap(), "inline");
+
+        let path = config.find_template("a.html", None).unwrap();
+        assert_eq!(config.template_source(&path).unwrap(), "foo");
+    }
+
     #[cfg(feature = "config")]
     #[test]
     fn add_syntax() {
@@ -482,6 +850,32 @@ mod tests {
         let _config = Config::new(raw_config).unwrap();
     }
 
+    #[cfg(feature = "config")]
+    #[test]
+    fn overlapping_syntax() {
+        let raw_config = r#"
+        [[syntax]]
+        name = "erb"
+        block_start = "<%"
+        block_end = "%>"
+        expr_start = "<%="
+        expr_end = "%>"
+        comment_start = "<%#"
+        comment_end = "%>"
+        "#;
+        let config = Config::new(raw_config).unwrap();
+        assert_eq!(config.syntaxes["erb"].expr_start, "<%=");
+
+        let raw_config = r#"
+        syntax = [{ name = "foo", block_start = "{{=" }]
+        "#;
+        let err = Config::new(raw_config).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "delimiters overlap: expr_start \"{{\" is a prefix of block_start \"{{=\""
+        );
+    }
+
     #[cfg(feature = "toml")]
     #[should_panic]
     #[test]
@@ -509,13 +903,43 @@ mod tests {
             config.escapers,
             vec![
                 (str_set(&["js"]), "::askama::Js".into()),
-                (str_set(&["html", "htm", "xml"]), "::askama::Html".into()),
                 (
-                    str_set(&["md", "none", "txt", "yml", ""]),
+                    str_set(&["html", "htm", "svg", "xml"]),
+                    "::askama::Html".into()
+                ),
+                (
+                    str_set(&["csv", "md", "none", "txt", "yml", ""]),
                     "::askama::Text".into()
                 ),
                 (str_set(&["j2", "jinja", "jinja2"]), "::askama::Html".into()),
+                (str_set(&["json"]), "::askama::Json".into()),
+            ]
+        );
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn test_config_comments() {
+        let config = Config::new(
+            r#"
+            [[comment]]
+            start = "% "
+            extensions = ["tex"]
+
+            [[comment]]
+            start = "(* "
+            end = " *)"
+            extensions = ["ml"]
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.comments[..2],
+            [
+                (str_set(&["tex"]), "% ".into(), "".into()),
+                (str_set(&["ml"]), "(* ".into(), " *)".into()),
             ]
         );
+        assert_eq!(config.comments.len(), 2 + DEFAULT_COMMENTS.len());
     }
 }
@@ -1,10 +1,14 @@
 use std::cell::Cell;
+use std::env;
+use std::path::Path;
 use std::str;
 
 use nom::branch::alt;
 use nom::bytes::complete::{escaped, is_not, tag, take_till, take_until};
 use nom::character::complete::{anychar, char, digit1};
-use nom::combinator::{complete, consumed, cut, eof, map, not, opt, peek, recognize, value};
+use nom::combinator::{
+    complete, consumed, cut, eof, map, not, opt, peek, recognize, value, verify,
+};
 use nom::error::{Error, ErrorKind};
 use nom::multi::{fold_many0, many0, many1, separated_list0, separated_list1};
 use nom::sequence::{delimited, pair, preceded, terminated, tuple};
@@ -16,6 +20,8 @@ use crate::{CompileError, Syntax};
 pub enum Node<'a> {
     Lit(&'a str, &'a str, &'a str),
     Comment(Ws),
+    /// A comment written to the output, as a comment of the target language
+    OutputComment(Ws, &'a str),
     Expr(Ws, Expr<'a>),
     Call(Ws, Option<&'a str>, &'a str, Vec<Expr<'a>>),
     LetDecl(Ws, Target<'a>),
@@ -23,14 +29,28 @@ pub enum Node<'a> {
     Cond(Vec<Cond<'a>>, Ws),
     Match(Ws, Expr<'a>, Vec<When<'a>>, Ws),
     Loop(Loop<'a>),
-    Extends(Expr<'a>),
-    BlockDef(Ws, &'a str, Vec<Node<'a>>, Ws),
-    Include(Ws, &'a str),
+    FilterBlock(Ws, Expr<'a>, Vec<Node<'a>>, Ws),
+    /// Binds the rendered contents to a variable, as `{% set x %}…{% endset %}`
+    Capture(Ws, Target<'a>, Vec<Node<'a>>, Ws),
+    /// Strips the whitespace between HTML tags from the rendered contents
+    Spaceless(Ws, Vec<Node<'a>>, Ws),
+    /// The candidate parents, each with the condition under which it is
+    /// extended, except for the last one, which is extended otherwise
+    Extends(Vec<(&'a str, Option<Expr<'a>>)>),
+    /// A block, which is `required` to be overridden if the flag is set
+    BlockDef(Ws, &'a str, bool, Vec<Node<'a>>, Ws),
+    /// The candidate paths, of which the first that exists is included, and
+    /// whether the include is marked `escape_as_parent`
+    Include(Ws, Vec<&'a str>, Option<Expr<'a>>, bool),
+    /// Writes the contents of a file as they are, without parsing them
+    IncludeStr(Ws, &'a str),
     Import(Ws, &'a str, &'a str),
     Macro(&'a str, Macro<'a>),
     Raw(Ws, &'a str, &'a str, &'a str, Ws),
     Break(Ws),
     Continue(Ws),
+    /// Writes the `Debug` representation of the expression in debug builds
+    Debug(Ws, Expr<'a>),
 }
 
 #[derive(Debug, PartialEq)]
@@ -65,6 +85,15 @@ pub enum Expr<'a> {
     Group(Box<Expr<'a>>),
     MethodCall(Box<Expr<'a>>, &'a str, Vec<Expr<'a>>),
     RustMacro(&'a str, &'a str),
+    If(Box<Expr<'a>>, Box<Expr<'a>>, Box<Expr<'a>>),
+    /// `|params| body`, e.g. as the argument of a method call
+    Closure(Vec<Target<'a>>, Box<Expr<'a>>),
+    /// `expr?`, returning the error of a `Result` from the render method
+    Try(Box<Expr<'a>>),
+    // A `name = value` argument of a macro call
+    NamedArgument(&'a str, Box<Expr<'a>>),
+    // The rendered contents of a `{% filter %}` block
+    FilterSource,
 }
 
 impl Expr<'_> {
@@ -86,7 +115,9 @@ impl Expr<'_> {
             // The result of a call likely doesn't need to be borrowed,
             // as in that case the call is more likely to return a
             // reference in the first place then.
-            VarCall(..) | Path(..) | PathCall(..) | MethodCall(..) => true,
+            VarCall(..) | Path(..) | PathCall(..) | MethodCall(..) | Try(..) => true,
+            // A borrowed closure wouldn't have its parameter types inferred.
+            Closure(..) => true,
             // If the `expr` is within a `Unary` or `BinOp` then
             // an assumption can be made that the operand is copy.
             // If not, then the value is moved and adding `.clone()`
@@ -107,12 +138,14 @@ impl Expr<'_> {
     }
 }
 
-pub type When<'a> = (Ws, Target<'a>, Vec<Node<'a>>);
+/// A `when` arm: its pattern, an optional `if` guard and its body
+pub type When<'a> = (Ws, Target<'a>, Option<Expr<'a>>, Vec<Node<'a>>);
 
 #[derive(Debug, PartialEq)]
 pub struct Macro<'a> {
     pub ws1: Ws,
-    pub args: Vec<&'a str>,
+    /// The parameters, with their default values if any
+    pub args: Vec<(&'a str, Option<Expr<'a>>)>,
     pub nodes: Vec<Node<'a>>,
     pub ws2: Ws,
 }
@@ -122,11 +155,20 @@ pub enum Target<'a> {
     Name(&'a str),
     Tuple(Vec<&'a str>, Vec<Target<'a>>),
     Struct(Vec<&'a str>, Vec<(&'a str, Target<'a>)>),
+    Array(Vec<Target<'a>>),
     NumLit(&'a str),
     StrLit(&'a str),
     CharLit(&'a str),
     BoolLit(&'a str),
     Path(Vec<&'a str>),
+    /// `name @ pattern`
+    Binding(&'a str, Box<Target<'a>>),
+    /// `pattern | pattern | ...`
+    Or(Vec<Target<'a>>),
+    /// `a..=b`, `a..b`, `a..` or `..=b`, with number or character literals
+    Range(&'a str, Option<Box<Target<'a>>>, Option<Box<Target<'a>>>),
+    /// `..`, the rest of a tuple, array or struct pattern
+    Rest,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -341,6 +383,9 @@ fn expr_path_call(i: &str) -> IResult<&str, Expr<'_>> {
 }
 
 fn named_target(i: &str) -> IResult<&str, (&str, Target<'_>)> {
+    if let Ok((i, _)) = tag::<_, _, ()>("..")(i) {
+        return Ok((i, ("..", Target::Rest)));
+    }
     let (i, (src, target)) = pair(identifier, opt(preceded(ws(char(':')), target)))(i)?;
     Ok((i, (src, target.unwrap_or(Target::Name(src)))))
 }
@@ -354,14 +399,64 @@ fn variant_lit(i: &str) -> IResult<&str, Target<'_>> {
     ))(i)
 }
 
+fn range_bound(i: &str) -> IResult<&str, Box<Target<'_>>> {
+    map(alt((variant_char_lit, variant_num_lit)), Box::new)(i)
+}
+
 fn target(i: &str) -> IResult<&str, Target<'_>> {
+    let (i, first) = target_binding(i)?;
+    let (i, mut rest) = many0(preceded(
+        ws(terminated(char('|'), not(char('|')))),
+        target_binding,
+    ))(i)?;
+    if rest.is_empty() {
+        return Ok((i, first));
+    }
+    rest.insert(0, first);
+    Ok((i, Target::Or(rest)))
+}
+
+fn target_binding(i: &str) -> IResult<&str, Target<'_>> {
+    let (i, name) = opt(terminated(identifier, ws(char('@'))))(i)?;
+    let (i, target) = target_single(i)?;
+    Ok((
+        i,
+        match name {
+            Some(name) => Target::Binding(name, Box::new(target)),
+            None => target,
+        },
+    ))
+}
+
+fn target_single(i: &str) -> IResult<&str, Target<'_>> {
     let mut opt_opening_paren = map(opt(ws(char('('))), |o| o.is_some());
     let mut opt_closing_paren = map(opt(ws(char(')'))), |o| o.is_some());
     let mut opt_opening_brace = map(opt(ws(char('{'))), |o| o.is_some());
 
     let (i, lit) = opt(variant_lit)(i)?;
     if let Some(lit) = lit {
-        return Ok((i, lit));
+        let (i, range) = opt(ws(alt((tag("..="), tag("..")))))(i)?;
+        return match range {
+            Some("..=") => {
+                let (i, end) = cut(range_bound)(i)?;
+                Ok((i, Target::Range("..=", Some(Box::new(lit)), Some(end))))
+            }
+            Some(op) => {
+                let (i, end) = opt(range_bound)(i)?;
+                Ok((i, Target::Range(op, Some(Box::new(lit)), end)))
+            }
+            None => Ok((i, lit)),
+        };
+    }
+
+    let (i, end) = opt(preceded(ws(tag("..=")), cut(range_bound)))(i)?;
+    if let Some(end) = end {
+        return Ok((i, Target::Range("..=", None, Some(end))));
+    }
+
+    let (i, rest) = opt(tag(".."))(i)?;
+    if rest.is_some() {
+        return Ok((i, Target::Rest));
     }
 
     // match tuples and unused parentheses
@@ -393,6 +488,19 @@ fn target(i: &str) -> IResult<&str, Target<'_>> {
         return Ok((i, Target::Tuple(Vec::new(), targets)));
     }
 
+    // match arrays and slices
+    let (i, target_is_array) = map(opt(ws(char('['))), |o| o.is_some())(i)?;
+    if target_is_array {
+        let (i, targets) = alt((
+            map(ws(char(']')), |_| Vec::new()),
+            terminated(
+                cut(separated_list1(ws(char(',')), target)),
+                pair(opt(ws(char(','))), ws(cut(char(']')))),
+            ),
+        ))(i)?;
+        return Ok((i, Target::Array(targets)));
+    }
+
     // match structs
     let (i, path) = opt(path)(i)?;
     if let Some(path) = path {
@@ -490,10 +598,44 @@ fn nested_parenthesis(i: &str) -> IResult<&str, ()> {
     }
 }
 
-fn parameters(i: &str) -> IResult<&str, Vec<&str>> {
+fn parameter(i: &str) -> IResult<&str, (&str, Option<Expr<'_>>)> {
+    pair(ws(identifier), opt(preceded(ws(char('=')), ws(expr_any))))(i)
+}
+
+fn parameters(i: &str) -> IResult<&str, Vec<(&str, Option<Expr<'_>>)>> {
+    let (j, params) = delimited(
+        ws(char('(')),
+        separated_list0(char(','), parameter),
+        ws(char(')')),
+    )(i)?;
+
+    // Parameters without defaults can't be filled in positionally after
+    // ones with defaults have been left out.
+    let mut has_default = false;
+    for (_, default) in &params {
+        if default.is_none() && has_default {
+            return Err(nom::Err::Failure(error_position!(i, ErrorKind::Tag)));
+        }
+        has_default |= default.is_some();
+    }
+    Ok((j, params))
+}
+
+fn call_argument(i: &str) -> IResult<&str, Expr<'_>> {
+    let named = map(
+        pair(
+            terminated(ws(identifier), pair(char('='), not(char('=')))),
+            ws(expr_any),
+        ),
+        |(name, value)| Expr::NamedArgument(name, Box::new(value)),
+    );
+    alt((named, ws(expr_any)))(i)
+}
+
+fn call_arguments(i: &str) -> IResult<&str, Vec<Expr<'_>>> {
     delimited(
         ws(char('(')),
-        separated_list0(char(','), ws(identifier)),
+        separated_list0(char(','), call_argument),
         ws(char(')')),
     )(i)
 }
@@ -504,8 +646,53 @@ fn expr_group(i: &str) -> IResult<&str, Expr<'_>> {
     })(i)
 }
 
+fn keyword<'a>(k: &'a str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    move |i: &'a str| -> IResult<&'a str, &'a str> {
+        let (j, v) = identifier(i)?;
+        if k == v {
+            Ok((j, v))
+        } else {
+            Err(nom::Err::Error(error_position!(i, ErrorKind::Tag)))
+        }
+    }
+}
+
+fn expr_if(i: &str) -> IResult<&str, Expr<'_>> {
+    let braced = |i| delimited(ws(char('{')), expr_any, ws(char('}')))(i);
+    let (i, (_, (cond, then, _, otherwise))) = tuple((
+        ws(keyword("if")),
+        cut(tuple((
+            expr_any,
+            braced,
+            ws(keyword("else")),
+            alt((expr_if, braced)),
+        ))),
+    ))(i)?;
+    Ok((
+        i,
+        Expr::If(Box::new(cond), Box::new(then), Box::new(otherwise)),
+    ))
+}
+
+// A `|` starting an operand opens a closure, while one following an operand
+// applies a filter (or is a bitwise or, if followed by whitespace).
+fn expr_closure(i: &str) -> IResult<&str, Expr<'_>> {
+    let params = alt((
+        map(ws(tag("||")), |_| Vec::new()),
+        delimited(
+            ws(char('|')),
+            separated_list0(ws(char(',')), ws(target_binding)),
+            ws(char('|')),
+        ),
+    ));
+    let (i, (params, body)) = pair(params, expr_any)(i)?;
+    Ok((i, Expr::Closure(params, Box::new(body))))
+}
+
 fn expr_single(i: &str) -> IResult<&str, Expr<'_>> {
     alt((
+        expr_if,
+        expr_closure,
         expr_bool_lit,
         expr_num_lit,
         expr_str_lit,
@@ -520,46 +707,61 @@ fn expr_single(i: &str) -> IResult<&str, Expr<'_>> {
     ))(i)
 }
 
+// The generic arguments of a turbofish, e.g. `Vec<_>` in `collect::<Vec<_>>()`
+fn generic_args(i: &str) -> IResult<&str, ()> {
+    let nested = recognize(delimited(char('<'), generic_args, char('>')));
+    let (i, _) = many0(alt((is_not("<>"), nested)))(i)?;
+    Ok((i, ()))
+}
+
+fn turbofish(i: &str) -> IResult<&str, &str> {
+    recognize(tuple((ws(tag("::")), char('<'), generic_args, char('>'))))(i)
+}
+
 fn attr(i: &str) -> IResult<&str, (&str, Option<Vec<Expr<'_>>>)> {
     let (i, (_, attr, args)) = tuple((
         ws(char('.')),
-        alt((num_lit, identifier)),
+        alt((num_lit, recognize(pair(identifier, opt(turbofish))))),
         ws(opt(arguments)),
     ))(i)?;
     Ok((i, (attr, args)))
 }
 
-fn expr_attr(i: &str) -> IResult<&str, Expr<'_>> {
-    let (i, (obj, attrs)) = tuple((expr_single, many0(attr)))(i)?;
+fn index(i: &str) -> IResult<&str, Expr<'_>> {
+    let (i, (_, key, _)) = tuple((ws(char('[')), expr_any, ws(char(']'))))(i)?;
+    Ok((i, key))
+}
+
+enum Suffix<'a> {
+    Attr(&'a str, Option<Vec<Expr<'a>>>),
+    Index(Expr<'a>),
+    Try,
+}
+
+// Attribute accesses, method calls, indexing and `?`, chained in any order
+fn expr_index(i: &str) -> IResult<&str, Expr<'_>> {
+    let suffix = alt((
+        map(attr, |(attr, args)| Suffix::Attr(attr, args)),
+        map(index, Suffix::Index),
+        map(ws(char('?')), |_| Suffix::Try),
+    ));
+    let (i, (obj, suffixes)) = tuple((expr_single, many0(suffix)))(i)?;
 
     let mut res = obj;
-    for (aname, args) in attrs {
-        res = if let Some(args) = args {
-            Expr::MethodCall(Box::new(res), aname, args)
-        } else {
-            Expr::Attr(Box::new(res), aname)
+    for suffix in suffixes {
+        res = match suffix {
+            Suffix::Attr(aname, Some(args)) => Expr::MethodCall(Box::new(res), aname, args),
+            Suffix::Attr(aname, None) => Expr::Attr(Box::new(res), aname),
+            Suffix::Index(key) => Expr::Index(Box::new(res), Box::new(key)),
+            Suffix::Try => Expr::Try(Box::new(res)),
         };
     }
 
     Ok((i, res))
 }
 
-fn expr_index(i: &str) -> IResult<&str, Expr<'_>> {
-    let key = opt(tuple((ws(char('[')), expr_any, ws(char(']')))));
-    let (i, (obj, key)) = tuple((expr_attr, key))(i)?;
-    let key = key.map(|(_, key, _)| key);
-
-    Ok((
-        i,
-        match key {
-            Some(key) => Expr::Index(Box::new(obj), Box::new(key)),
-            None => obj,
-        },
-    ))
-}
-
 fn filter(i: &str) -> IResult<&str, (&str, Option<Vec<Expr<'_>>>)> {
-    let (i, (_, fname, args)) = tuple((char('|'), ws(identifier), opt(arguments)))(i)?;
+    let (i, (_, fname, args)) = tuple((char('|'), ws(identifier), opt(call_arguments)))(i)?;
     Ok((i, (fname, args)))
 }
 
@@ -582,7 +784,10 @@ fn expr_filtered(i: &str) -> IResult<&str, Expr<'_>> {
 }
 
 fn expr_unary(i: &str) -> IResult<&str, Expr<'_>> {
-    let (i, (op, expr)) = tuple((opt(alt((ws(tag("!")), ws(tag("-"))))), expr_index))(i)?;
+    let (i, (op, expr)) = tuple((
+        opt(alt((ws(tag("!")), ws(tag("-")), ws(tag("*"))))),
+        expr_index,
+    ))(i)?;
     Ok((
         i,
         match op {
@@ -668,7 +873,15 @@ fn expr_node<'a>(i: &'a str, s: &State<'_>) -> IResult<&'a str, Node<'a>> {
         }))),
     ));
     let (i, (_, (pws, expr, nws, _))) = p(i)?;
-    Ok((i, Node::Expr(Ws(pws.is_some(), nws.is_some()), expr)))
+    let ws = Ws(pws.is_some(), nws.is_some());
+    Ok((
+        i,
+        match expr {
+            // `{{ super() }}` is the same as `{% call super() %}`
+            Expr::VarCall("super", args) if args.is_empty() => Node::Call(ws, None, "super", args),
+            expr => Node::Expr(ws, expr),
+        },
+    ))
 }
 
 fn block_call(i: &str) -> IResult<&str, Node<'_>> {
@@ -678,7 +891,7 @@ fn block_call(i: &str) -> IResult<&str, Node<'_>> {
         cut(tuple((
             opt(tuple((ws(identifier), ws(tag("::"))))),
             ws(identifier),
-            ws(arguments),
+            ws(call_arguments),
             opt(char('-')),
         ))),
     ));
@@ -762,7 +975,12 @@ fn match_else_block<'a>(i: &'a str, s: &State<'_>) -> IResult<&'a str, When<'a>>
     let (i, (_, pws, _, (nws, _, block))) = p(i)?;
     Ok((
         i,
-        (Ws(pws.is_some(), nws.is_some()), Target::Name("_"), block),
+        (
+            Ws(pws.is_some(), nws.is_some()),
+            Target::Name("_"),
+            None,
+            block,
+        ),
     ))
 }
 
@@ -773,13 +991,14 @@ fn when_block<'a>(i: &'a str, s: &State<'_>) -> IResult<&'a str, When<'a>> {
         ws(tag("when")),
         cut(tuple((
             ws(target),
+            opt(preceded(ws(keyword("if")), cut(ws(expr_any)))),
             opt(char('-')),
             |i| tag_block_end(i, s),
             cut(|i| parse_template(i, s)),
         ))),
     ));
-    let (i, (_, pws, _, (target, nws, _, block))) = p(i)?;
-    Ok((i, (Ws(pws.is_some(), nws.is_some()), target, block)))
+    let (i, (_, pws, _, (target, guard, nws, _, block))) = p(i)?;
+    Ok((i, (Ws(pws.is_some(), nws.is_some()), target, guard, block)))
 }
 
 fn block_match<'a>(i: &'a str, s: &State<'_>) -> IResult<&'a str, Node<'a>> {
@@ -862,6 +1081,16 @@ fn block_let(i: &str) -> IResult<&str, Node<'_>> {
     ))
 }
 
+fn block_debug(i: &str) -> IResult<&str, Node<'_>> {
+    let mut p = tuple((
+        opt(char('-')),
+        ws(tag("debug")),
+        cut(tuple((ws(expr_any), opt(char('-'))))),
+    ));
+    let (i, (pws, _, (expr, nws))) = p(i)?;
+    Ok((i, Node::Debug(Ws(pws.is_some(), nws.is_some()), expr)))
+}
+
 fn parse_loop_content<'a>(i: &'a str, s: &State<'_>) -> IResult<&'a str, Vec<Node<'a>>> {
     s.loop_depth.set(s.loop_depth.get() + 1);
     let result = parse_template(i, s);
@@ -929,20 +1158,149 @@ fn block_for<'a>(i: &'a str, s: &State<'_>) -> IResult<&'a str, Node<'a>> {
     ))
 }
 
+fn block_filter<'a>(i: &'a str, s: &State<'_>) -> IResult<&'a str, Node<'a>> {
+    let mut start = tuple((
+        opt(char('-')),
+        ws(tag("filter")),
+        cut(tuple((
+            ws(identifier),
+            opt(arguments),
+            many0(filter),
+            ws(opt(char('-'))),
+            |i| tag_block_end(i, s),
+        ))),
+    ));
+    let (i, (pws1, _, (fname, args, filters, nws1, _))) = start(i)?;
+
+    let mut args = args.unwrap_or_default();
+    args.insert(0, Expr::FilterSource);
+    let mut res = Expr::Filter(fname, args);
+    for (fname, args) in filters {
+        let mut args = args.unwrap_or_default();
+        args.insert(0, res);
+        res = Expr::Filter(fname, args);
+    }
+
+    let mut end = cut(tuple((
+        |i| parse_template(i, s),
+        cut(tuple((
+            |i| tag_block_start(i, s),
+            opt(char('-')),
+            ws(tag("endfilter")),
+            opt(char('-')),
+        ))),
+    )));
+    let (i, (contents, (_, pws2, _, nws2))) = end(i)?;
+
+    Ok((
+        i,
+        Node::FilterBlock(
+            Ws(pws1.is_some(), nws1.is_some()),
+            res,
+            contents,
+            Ws(pws2.is_some(), nws2.is_some()),
+        ),
+    ))
+}
+
+// Only a `set` without a value captures its contents, so it is tried before
+// `block_let` and doesn't commit to the block form until the tag has ended.
+fn block_set_capture<'a>(i: &'a str, s: &State<'_>) -> IResult<&'a str, Node<'a>> {
+    let mut start = tuple((
+        opt(char('-')),
+        ws(keyword("set")),
+        ws(identifier),
+        opt(char('-')),
+        |i| tag_block_end(i, s),
+    ));
+    let (i, (pws1, _, var, nws1, _)) = start(i)?;
+
+    let mut end = cut(tuple((
+        |i| parse_template(i, s),
+        cut(tuple((
+            |i| tag_block_start(i, s),
+            opt(char('-')),
+            ws(tag("endset")),
+            opt(char('-')),
+        ))),
+    )));
+    let (i, (contents, (_, pws2, _, nws2))) = end(i)?;
+
+    Ok((
+        i,
+        Node::Capture(
+            Ws(pws1.is_some(), nws1.is_some()),
+            Target::Name(var),
+            contents,
+            Ws(pws2.is_some(), nws2.is_some()),
+        ),
+    ))
+}
+
+fn block_spaceless<'a>(i: &'a str, s: &State<'_>) -> IResult<&'a str, Node<'a>> {
+    let mut start = tuple((
+        opt(char('-')),
+        ws(tag("spaceless")),
+        cut(tuple((opt(char('-')), |i| tag_block_end(i, s)))),
+    ));
+    let (i, (pws1, _, (nws1, _))) = start(i)?;
+
+    let mut end = cut(tuple((
+        |i| parse_template(i, s),
+        cut(tuple((
+            |i| tag_block_start(i, s),
+            opt(char('-')),
+            ws(tag("endspaceless")),
+            opt(char('-')),
+        ))),
+    )));
+    let (i, (contents, (_, pws2, _, nws2))) = end(i)?;
+
+    Ok((
+        i,
+        Node::Spaceless(
+            Ws(pws1.is_some(), nws1.is_some()),
+            contents,
+            Ws(pws2.is_some(), nws2.is_some()),
+        ),
+    ))
+}
+
 fn block_extends(i: &str) -> IResult<&str, Node<'_>> {
-    let (i, (_, name)) = tuple((ws(tag("extends")), ws(expr_str_lit)))(i)?;
-    Ok((i, Node::Extends(name)))
+    let mut p = tuple((
+        ws(tag("extends")),
+        ws(expr_str_lit),
+        many0(tuple((
+            ws(keyword("if")),
+            cut(tuple((ws(expr_any), ws(keyword("else")), ws(expr_str_lit)))),
+        ))),
+    ));
+    let (i, (_, first, alternatives)) = p(i)?;
+    let path = |name| match name {
+        Expr::StrLit(s) => s,
+        _ => panic!("extends path must be a string literal"),
+    };
+
+    let mut parents = vec![(path(first), None)];
+    for (_, (cond, _, name)) in alternatives {
+        parents.last_mut().unwrap().1 = Some(cond);
+        parents.push((path(name), None));
+    }
+    Ok((i, Node::Extends(parents)))
 }
 
 fn block_block<'a>(i: &'a str, s: &State<'_>) -> IResult<&'a str, Node<'a>> {
     let mut start = tuple((
         opt(char('-')),
         ws(tag("block")),
-        cut(tuple((ws(identifier), opt(char('-')), |i| {
-            tag_block_end(i, s)
-        }))),
+        cut(tuple((
+            ws(identifier),
+            opt(ws(keyword("required"))),
+            opt(char('-')),
+            |i| tag_block_end(i, s),
+        ))),
     ));
-    let (i, (pws1, _, (name, nws1, _))) = start(i)?;
+    let (i, (pws1, _, (name, required, nws1, _))) = start(i)?;
 
     let mut end = cut(tuple((
         |i| parse_template(i, s),
@@ -960,6 +1318,7 @@ fn block_block<'a>(i: &'a str, s: &State<'_>) -> IResult<&'a str, Node<'a>> {
         Node::BlockDef(
             Ws(pws1.is_some(), nws1.is_some()),
             name,
+            required.is_some(),
             contents,
             Ws(pws2.is_some(), nws2.is_some()),
         ),
@@ -970,21 +1329,46 @@ fn block_include(i: &str) -> IResult<&str, Node<'_>> {
     let mut p = tuple((
         opt(char('-')),
         ws(tag("include")),
-        cut(pair(ws(expr_str_lit), opt(char('-')))),
+        cut(tuple((
+            separated_list1(ws(keyword("or")), ws(expr_str_lit)),
+            opt(ws(keyword("escape_as_parent"))),
+            opt(preceded(ws(keyword("with")), ws(expr_any))),
+            opt(char('-')),
+        ))),
     ));
-    let (i, (pws, _, (name, nws))) = p(i)?;
+    let (i, (pws, _, (names, escape_as_parent, ctx, nws))) = p(i)?;
+    let paths = names
+        .into_iter()
+        .map(|name| match name {
+            Expr::StrLit(s) => s,
+            _ => panic!("include path must be a string literal"),
+        })
+        .collect();
     Ok((
         i,
         Node::Include(
             Ws(pws.is_some(), nws.is_some()),
-            match name {
-                Expr::StrLit(s) => s,
-                _ => panic!("include path must be a string literal"),
-            },
+            paths,
+            ctx,
+            escape_as_parent.is_some(),
         ),
     ))
 }
 
+fn block_include_str(i: &str) -> IResult<&str, Node<'_>> {
+    let mut p = tuple((
+        opt(char('-')),
+        ws(tag("include_str")),
+        cut(tuple((ws(expr_str_lit), opt(char('-'))))),
+    ));
+    let (i, (pws, _, (name, nws))) = p(i)?;
+    let path = match name {
+        Expr::StrLit(s) => s,
+        _ => panic!("include_str path must be a string literal"),
+    };
+    Ok((i, Node::IncludeStr(Ws(pws.is_some(), nws.is_some()), path)))
+}
+
 fn block_import(i: &str) -> IResult<&str, Node<'_>> {
     let mut p = tuple((
         opt(char('-')),
@@ -1099,11 +1483,15 @@ fn block_node<'a>(i: &'a str, s: &State<'_>) -> IResult<&'a str, Node<'a>> {
         |i| tag_block_start(i, s),
         alt((
             block_call,
+            |i| block_set_capture(i, s),
             block_let,
             |i| block_if(i, s),
             |i| block_for(i, s),
             |i| block_match(i, s),
+            |i| block_filter(i, s),
+            |i| block_spaceless(i, s),
             block_extends,
+            block_include_str,
             block_include,
             block_import,
             |i| block_block(i, s),
@@ -1111,6 +1499,7 @@ fn block_node<'a>(i: &'a str, s: &State<'_>) -> IResult<&'a str, Node<'a>> {
             |i| block_raw(i, s),
             |i| break_statement(i, s),
             |i| continue_statement(i, s),
+            block_debug,
         )),
         cut(|i| tag_block_end(i, s)),
     ));
@@ -1125,11 +1514,11 @@ fn block_comment_body<'a>(mut i: &'a str, s: &State<'_>) -> IResult<&'a str, &'a
         match take_until::<_, _, Error<_>>(s.syntax.comment_start)(i) {
             Ok((start, _)) if start.as_ptr() < end.as_ptr() => {
                 level += 1;
-                i = &start[2..];
+                i = &start[s.syntax.comment_start.len()..];
             }
             _ if level > 0 => {
                 level -= 1;
-                i = &end[2..];
+                i = &end[s.syntax.comment_end.len()..];
             }
             _ => return Ok((end, tail)),
         }
@@ -1149,9 +1538,31 @@ fn block_comment<'a>(i: &'a str, s: &State<'_>) -> IResult<&'a str, Node<'a>> {
     Ok((i, Node::Comment(Ws(pws.is_some(), tail.ends_with('-')))))
 }
 
+// `{#! text !#}`, with the `-` markers inside the `!`s. A comment that starts
+// with `!` but doesn't end with one, like `{#! text #}`, is a regular comment.
+fn output_comment<'a>(i: &'a str, s: &State<'_>) -> IResult<&'a str, Node<'a>> {
+    let mut p = tuple((
+        |i| tag_comment_start(i, s),
+        char('!'),
+        opt(char('-')),
+        verify(take_until(s.syntax.comment_end), |text: &str| {
+            text.ends_with('!')
+        }),
+        |i| tag_comment_end(i, s),
+    ));
+    let (i, (_, _, pws, text, _)) = p(i)?;
+    let text = &text[..text.len() - 1];
+    let (text, nws) = match text.strip_suffix('-') {
+        Some(text) => (text, true),
+        None => (text, false),
+    };
+    Ok((i, Node::OutputComment(Ws(pws.is_some(), nws), text.trim())))
+}
+
 fn parse_template<'a>(i: &'a str, s: &State<'_>) -> IResult<&'a str, Vec<Node<'a>>> {
     many0(alt((
         complete(|i| take_content(i, s)),
+        complete(|i| output_comment(i, s)),
         complete(|i| block_comment(i, s)),
         complete(|i| expr_node(i, s)),
         complete(|i| block_node(i, s)),
@@ -1178,6 +1589,15 @@ fn tag_expr_end<'a>(i: &'a str, s: &State<'_>) -> IResult<&'a str, &'a str> {
 }
 
 pub fn parse<'a>(src: &'a str, syntax: &'a Syntax<'a>) -> Result<Vec<Node<'a>>, CompileError> {
+    parse_with_path(src, syntax, None)
+}
+
+/// Like [`parse()`], but errors are reported as `path:line:col` if `path` is given
+pub fn parse_with_path<'a>(
+    src: &'a str,
+    syntax: &'a Syntax<'a>,
+    path: Option<&Path>,
+) -> Result<Vec<Node<'a>>, CompileError> {
     let state = State {
         syntax,
         loop_depth: Cell::new(0),
@@ -1185,7 +1605,7 @@ pub fn parse<'a>(src: &'a str, syntax: &'a Syntax<'a>) -> Result<Vec<Node<'a>>,
     match parse_template(src, &state) {
         Ok((left, res)) => {
             if !left.is_empty() {
-                Err(format!("unable to parse template:\n\n{:?}", left).into())
+                Err(parse_error_message(src, src.len() - left.len(), path).into())
             } else {
                 Ok(res)
             }
@@ -1193,33 +1613,91 @@ pub fn parse<'a>(src: &'a str, syntax: &'a Syntax<'a>) -> Result<Vec<Node<'a>>,
 
         Err(nom::Err::Error(err)) | Err(nom::Err::Failure(err)) => {
             let nom::error::Error { input, .. } = err;
-            let offset = src.len() - input.len();
-            let (source_before, source_after) = src.split_at(offset);
-
-            let source_after = match source_after.char_indices().enumerate().take(41).last() {
-                Some((40, (i, _))) => format!("{:?}...", &source_after[..i]),
-                _ => format!("{:?}", source_after),
-            };
-
-            let (row, last_line) = source_before.lines().enumerate().last().unwrap();
-            let column = last_line.chars().count();
-
-            let msg = format!(
-                "problems parsing template source at row {}, column {} near:\n{}",
-                row + 1,
-                column,
-                source_after,
-            );
-            Err(msg.into())
+            Err(parse_error_message(src, src.len() - input.len(), path).into())
         }
 
         Err(nom::Err::Incomplete(_)) => Err("parsing incomplete".into()),
     }
 }
 
+/// Describe a parse error at byte `offset` of `src`, with the offending
+/// line and a caret under the column the parser gave up at.
+fn parse_error_message(src: &str, offset: usize, path: Option<&Path>) -> String {
+    source_message("problems parsing template source", src, offset, 1, path)
+}
+
+/// Describe `message` as found at the `len` bytes at byte `offset` of `src`,
+/// with the line they are on and carets under them
+pub(crate) fn source_message(
+    message: &str,
+    src: &str,
+    offset: usize,
+    len: usize,
+    path: Option<&Path>,
+) -> String {
+    let (before, after) = src.split_at(offset);
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let line_end = after.find('\n').map_or(src.len(), |i| offset + i);
+    let line = src[line_start..line_end].trim_end_matches('\r');
+    let row = before.matches('\n').count() + 1;
+    let column = before[line_start..].chars().count() + 1;
+
+    let location = match path {
+        Some(path) => {
+            let path = env::var_os("CARGO_MANIFEST_DIR")
+                .and_then(|root| path.strip_prefix(root).ok())
+                .unwrap_or(path);
+            format!("{}:{}:{}", path.display(), row, column)
+        }
+        None => format!("row {}, column {}", row, column),
+    };
+
+    // Very long lines (e.g. inline `source` templates) are cut down to
+    // the part around the error.
+    let skip = column.saturating_sub(SNIPPET_CONTEXT + 1);
+    let prefix = if skip > 0 { "..." } else { "" };
+    let snippet: String = line.chars().skip(skip).take(2 * SNIPPET_CONTEXT).collect();
+    let suffix = if line.chars().count() > skip + 2 * SNIPPET_CONTEXT {
+        "..."
+    } else {
+        ""
+    };
+    let caret_pad: String = prefix
+        .chars()
+        .chain(line.chars().skip(skip).take(column - 1 - skip))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    // The carets end with the line, or with the snippet if it is cut short.
+    let carets = after[..len.min(line_end - offset)]
+        .chars()
+        .count()
+        .min(2 * SNIPPET_CONTEXT + skip + 1 - column)
+        .max(1);
+
+    let gutter = " ".repeat(row.to_string().len());
+    format!(
+        "{} at {}\n\
+         {} |\n\
+         {} | {}{}{}\n\
+         {} | {}{}",
+        message,
+        location,
+        gutter,
+        row,
+        prefix,
+        snippet,
+        suffix,
+        gutter,
+        caret_pad,
+        "^".repeat(carets),
+    )
+}
+
+const SNIPPET_CONTEXT: usize = 40;
+
 #[cfg(test)]
 mod tests {
-    use super::{Expr, Node, Ws};
+    use super::{Expr, Node, Target, Ws};
     use crate::Syntax;
 
     fn check_ws_split(s: &str, res: &(&str, &str, &str)) {
@@ -1287,6 +1765,20 @@ mod tests {
                 ),
             )],
         );
+        assert_eq!(
+            super::parse("{{ d|date(\"%H\", tz=zone) }}", &syntax).unwrap(),
+            vec![Node::Expr(
+                Ws(false, false),
+                Filter(
+                    "date",
+                    vec![
+                        Var("d"),
+                        StrLit("%H"),
+                        NamedArgument("tz", Var("zone").into())
+                    ]
+                ),
+            )],
+        );
     }
 
     #[test]
@@ -1509,6 +2001,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_arithmetic() {
+        use Expr::*;
+        let syntax = Syntax::default();
+        assert_eq!(
+            super::parse("{{ ((a + b) * (c - 1)) % d }}", &syntax).unwrap(),
+            vec![Node::Expr(
+                Ws(false, false),
+                BinOp(
+                    "%",
+                    Group(
+                        BinOp(
+                            "*",
+                            Group(BinOp("+", Var("a").into(), Var("b").into()).into()).into(),
+                            Group(BinOp("-", Var("c").into(), NumLit("1").into()).into()).into(),
+                        )
+                        .into()
+                    )
+                    .into(),
+                    Var("d").into(),
+                )
+            )],
+        );
+        // The `%` of a block's end delimiter is not taken for an operator.
+        assert_eq!(
+            super::parse("{% let x = a % b %}", &syntax).unwrap(),
+            vec![Node::Let(
+                Ws(false, false),
+                Target::Name("x"),
+                BinOp("%", Var("a").into(), Var("b").into()),
+            )],
+        );
+        assert_eq!(
+            super::parse("{% let x = a -%}", &syntax).unwrap(),
+            vec![Node::Let(Ws(false, true), Target::Name("x"), Var("a"))],
+        );
+    }
+
     #[test]
     fn test_associativity() {
         use Expr::*;
@@ -1624,4 +2154,708 @@ mod tests {
             vec![Node::Comment(Ws(false, false))],
         );
     }
+
+    #[test]
+    fn test_parse_filter_block() {
+        let s = &Syntax::default();
+
+        assert_eq!(
+            super::parse("{% filter upper %}a{% endfilter %}", s).unwrap(),
+            vec![Node::FilterBlock(
+                Ws(false, false),
+                Expr::Filter("upper", vec![Expr::FilterSource]),
+                vec![Node::Lit("", "a", "")],
+                Ws(false, false),
+            )],
+        );
+        assert_eq!(
+            super::parse("{%- filter indent(2)|trim -%}a{%- endfilter -%}", s).unwrap(),
+            vec![Node::FilterBlock(
+                Ws(true, true),
+                Expr::Filter(
+                    "trim",
+                    vec![Expr::Filter(
+                        "indent",
+                        vec![Expr::FilterSource, Expr::NumLit("2")]
+                    )]
+                ),
+                vec![Node::Lit("", "a", "")],
+                Ws(true, true),
+            )],
+        );
+    }
+
+    #[test]
+    fn test_parse_required_block() {
+        let s = Syntax::default();
+        assert_eq!(
+            super::parse("{% block title required -%} {%- endblock %}", &s).unwrap(),
+            vec![Node::BlockDef(
+                Ws(false, true),
+                "title",
+                true,
+                vec![Node::Lit(" ", "", "")],
+                Ws(true, false),
+            )],
+        );
+        assert_eq!(
+            super::parse("{% block required %}{% endblock %}", &s).unwrap(),
+            vec![Node::BlockDef(
+                Ws(false, false),
+                "required",
+                false,
+                vec![],
+                Ws(false, false),
+            )],
+        );
+    }
+
+    #[test]
+    fn test_parse_spaceless() {
+        let s = &Syntax::default();
+
+        assert_eq!(
+            super::parse("{% spaceless %}<a> </a>{% endspaceless %}", s).unwrap(),
+            vec![Node::Spaceless(
+                Ws(false, false),
+                vec![Node::Lit("", "<a> </a>", "")],
+                Ws(false, false),
+            )],
+        );
+        assert_eq!(
+            super::parse("{%- spaceless -%} <a> {%- endspaceless -%}", s).unwrap(),
+            vec![Node::Spaceless(
+                Ws(true, true),
+                vec![Node::Lit(" ", "<a>", " ")],
+                Ws(true, true),
+            )],
+        );
+    }
+
+    #[test]
+    fn test_parse_set_capture() {
+        let s = &Syntax::default();
+
+        assert_eq!(
+            super::parse("{% set x %}a{% endset %}", s).unwrap(),
+            vec![Node::Capture(
+                Ws(false, false),
+                Target::Name("x"),
+                vec![Node::Lit("", "a", "")],
+                Ws(false, false),
+            )],
+        );
+        assert_eq!(
+            super::parse("{%- set x -%} a {%- endset -%}", s).unwrap(),
+            vec![Node::Capture(
+                Ws(true, true),
+                Target::Name("x"),
+                vec![Node::Lit(" ", "a", " ")],
+                Ws(true, true),
+            )],
+        );
+        assert_eq!(
+            super::parse("{% set x = 1 %}", s).unwrap(),
+            vec![Node::Let(
+                Ws(false, false),
+                Target::Name("x"),
+                Expr::NumLit("1")
+            )],
+        );
+        assert!(super::parse("{% set x %}a", s).is_err());
+    }
+
+    #[test]
+    fn test_parse_array_target() {
+        let s = Syntax::default();
+        assert_eq!(
+            super::parse("{% let [a, (b, _)] = c %}", &s).unwrap(),
+            vec![Node::Let(
+                Ws(false, false),
+                Target::Array(vec![
+                    Target::Name("a"),
+                    Target::Tuple(vec![], vec![Target::Name("b"), Target::Name("_")]),
+                ]),
+                Expr::Var("c"),
+            )],
+        );
+        assert!(super::parse("{% let [a, b = c %}", &s).is_err());
+    }
+
+    #[test]
+    fn test_parse_if_expr() {
+        let s = Syntax::default();
+        assert_eq!(
+            super::parse("{{ if a { b } else if c { 1 } else { d|e } }}", &s).unwrap(),
+            vec![Node::Expr(
+                Ws(false, false),
+                Expr::If(
+                    Box::new(Expr::Var("a")),
+                    Box::new(Expr::Var("b")),
+                    Box::new(Expr::If(
+                        Box::new(Expr::Var("c")),
+                        Box::new(Expr::NumLit("1")),
+                        Box::new(Expr::Filter("e", vec![Expr::Var("d")])),
+                    )),
+                ),
+            )],
+        );
+        assert_eq!(
+            super::parse("{{ iffy }}", &s).unwrap(),
+            vec![Node::Expr(Ws(false, false), Expr::Var("iffy"))],
+        );
+        assert!(super::parse("{{ if a { b } }}", &s).is_err());
+    }
+
+    #[test]
+    fn test_parse_for_range() {
+        let s = Syntax::default();
+        let iter = |src| match super::parse(src, &s).unwrap().pop() {
+            Some(Node::Loop(super::Loop { iter, .. })) => iter,
+            node => panic!("expected a loop, got {:?}", node),
+        };
+        assert_eq!(
+            iter("{% for i in 0..n %}{% endfor %}"),
+            Expr::Range(
+                "..",
+                Some(Box::new(Expr::NumLit("0"))),
+                Some(Box::new(Expr::Var("n"))),
+            ),
+        );
+        assert_eq!(
+            iter("{% for i in 1 ..= n %}{% endfor %}"),
+            Expr::Range(
+                "..=",
+                Some(Box::new(Expr::NumLit("1"))),
+                Some(Box::new(Expr::Var("n"))),
+            ),
+        );
+        assert_eq!(
+            iter("{% for i in page.start..page.end + 1 %}{% endfor %}"),
+            Expr::Range(
+                "..",
+                Some(Box::new(Expr::Attr(Box::new(Expr::Var("page")), "start"))),
+                Some(Box::new(Expr::BinOp(
+                    "+",
+                    Box::new(Expr::Attr(Box::new(Expr::Var("page")), "end")),
+                    Box::new(Expr::NumLit("1")),
+                ))),
+            ),
+        );
+        assert_eq!(
+            iter("{% for i in a..=b.len() %}{% endfor %}"),
+            Expr::Range(
+                "..=",
+                Some(Box::new(Expr::Var("a"))),
+                Some(Box::new(Expr::MethodCall(
+                    Box::new(Expr::Var("b")),
+                    "len",
+                    vec![],
+                ))),
+            ),
+        );
+    }
+
+    #[test]
+    fn test_parse_debug() {
+        let s = Syntax::default();
+        assert_eq!(
+            super::parse("{% debug user.name %}", &s).unwrap(),
+            vec![Node::Debug(
+                Ws(false, false),
+                Expr::Attr(Box::new(Expr::Var("user")), "name"),
+            )],
+        );
+        assert_eq!(
+            super::parse("{%- debug items|length -%}", &s).unwrap(),
+            vec![Node::Debug(
+                Ws(true, true),
+                Expr::Filter("length", vec![Expr::Var("items")]),
+            )],
+        );
+        assert!(super::parse("{% debug %}", &s).is_err());
+    }
+
+    #[test]
+    fn test_parse_index() {
+        let s = Syntax::default();
+        assert_eq!(
+            super::parse("{{ matrix[i][j + 1] }}", &s).unwrap(),
+            vec![Node::Expr(
+                Ws(false, false),
+                Expr::Index(
+                    Box::new(Expr::Index(
+                        Box::new(Expr::Var("matrix")),
+                        Box::new(Expr::Var("i")),
+                    )),
+                    Box::new(Expr::BinOp(
+                        "+",
+                        Box::new(Expr::Var("j")),
+                        Box::new(Expr::NumLit("1")),
+                    )),
+                ),
+            )],
+        );
+        assert_eq!(
+            super::parse("{{ users[0].name.len() }}", &s).unwrap(),
+            vec![Node::Expr(
+                Ws(false, false),
+                Expr::MethodCall(
+                    Box::new(Expr::Attr(
+                        Box::new(Expr::Index(
+                            Box::new(Expr::Var("users")),
+                            Box::new(Expr::NumLit("0")),
+                        )),
+                        "name",
+                    )),
+                    "len",
+                    vec![],
+                ),
+            )],
+        );
+        assert_eq!(
+            super::parse("{{ config[\"title\"]|upper }}", &s).unwrap(),
+            vec![Node::Expr(
+                Ws(false, false),
+                Expr::Filter(
+                    "upper",
+                    vec![Expr::Index(
+                        Box::new(Expr::Var("config")),
+                        Box::new(Expr::StrLit("title")),
+                    )],
+                ),
+            )],
+        );
+    }
+
+    #[test]
+    fn test_parse_try() {
+        let s = Syntax::default();
+        assert_eq!(
+            super::parse("{{ self.lookup(id)?.name }}", &s).unwrap(),
+            vec![Node::Expr(
+                Ws(false, false),
+                Expr::Attr(
+                    Box::new(Expr::Try(Box::new(Expr::MethodCall(
+                        Box::new(Expr::Var("self")),
+                        "lookup",
+                        vec![Expr::Var("id")]
+                    )))),
+                    "name"
+                )
+            )],
+        );
+        assert_eq!(
+            super::parse("{{ a? ?|upper }}", &s).unwrap(),
+            vec![Node::Expr(
+                Ws(false, false),
+                Expr::Filter(
+                    "upper",
+                    vec![Expr::Try(Box::new(Expr::Try(Box::new(Expr::Var("a")))))]
+                )
+            )],
+        );
+    }
+
+    #[test]
+    fn test_parse_closure() {
+        let s = Syntax::default();
+        assert_eq!(
+            super::parse(
+                "{{ items.iter().filter(|x| x.active).count()|fmt(\"{:03}\") }}",
+                &s
+            )
+            .unwrap(),
+            vec![Node::Expr(
+                Ws(false, false),
+                Expr::Filter(
+                    "fmt",
+                    vec![
+                        Expr::MethodCall(
+                            Box::new(Expr::MethodCall(
+                                Box::new(Expr::MethodCall(
+                                    Box::new(Expr::Var("items")),
+                                    "iter",
+                                    vec![],
+                                )),
+                                "filter",
+                                vec![Expr::Closure(
+                                    vec![Target::Name("x")],
+                                    Box::new(Expr::Attr(Box::new(Expr::Var("x")), "active")),
+                                )],
+                            )),
+                            "count",
+                            vec![],
+                        ),
+                        Expr::StrLit("{:03}"),
+                    ],
+                ),
+            )],
+        );
+        assert_eq!(
+            super::parse("{{ pairs.map(|(a, b)| a | b) }}", &s).unwrap(),
+            vec![Node::Expr(
+                Ws(false, false),
+                Expr::MethodCall(
+                    Box::new(Expr::Var("pairs")),
+                    "map",
+                    vec![Expr::Closure(
+                        vec![Target::Tuple(
+                            vec![],
+                            vec![Target::Name("a"), Target::Name("b")],
+                        )],
+                        Box::new(Expr::BinOp(
+                            "|",
+                            Box::new(Expr::Var("a")),
+                            Box::new(Expr::Var("b")),
+                        )),
+                    )],
+                ),
+            )],
+        );
+        assert_eq!(
+            super::parse("{{ x.unwrap_or_else(|| 0) || y }}", &s).unwrap(),
+            vec![Node::Expr(
+                Ws(false, false),
+                Expr::BinOp(
+                    "||",
+                    Box::new(Expr::MethodCall(
+                        Box::new(Expr::Var("x")),
+                        "unwrap_or_else",
+                        vec![Expr::Closure(vec![], Box::new(Expr::NumLit("0")))],
+                    )),
+                    Box::new(Expr::Var("y")),
+                ),
+            )],
+        );
+    }
+
+    #[test]
+    fn test_parse_turbofish() {
+        let s = Syntax::default();
+        assert_eq!(
+            super::parse("{{ s.parse::<u32>().is_ok() }}", &s).unwrap(),
+            vec![Node::Expr(
+                Ws(false, false),
+                Expr::MethodCall(
+                    Box::new(Expr::MethodCall(
+                        Box::new(Expr::Var("s")),
+                        "parse::<u32>",
+                        vec![],
+                    )),
+                    "is_ok",
+                    vec![],
+                ),
+            )],
+        );
+        assert_eq!(
+            super::parse("{{ it.collect::<HashMap<_, Vec<_>>>() }}", &s).unwrap(),
+            vec![Node::Expr(
+                Ws(false, false),
+                Expr::MethodCall(
+                    Box::new(Expr::Var("it")),
+                    "collect::<HashMap<_, Vec<_>>>",
+                    vec![],
+                ),
+            )],
+        );
+    }
+
+    #[test]
+    fn test_parse_error_location() {
+        let err = super::parse("{% if x %}\n  {{ x| }}\n{% endif %}", &Syntax::default())
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "problems parsing template source at row 2, column 7\n  |\n2 |   {{ x| }}\n  |       ^"
+        );
+
+        let path = std::path::Path::new("templates/broken.html");
+        let err = super::parse_with_path("{%for i in x%}{%endfo%}", &Syntax::default(), Some(path))
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "problems parsing template source at templates/broken.html:1:17\n  \
+             |\n1 | {%for i in x%}{%endfo%}\n  |                 ^"
+        );
+    }
+
+    #[test]
+    fn test_parse_error_long_line() {
+        let src = format!("{}{{% endfo %}}{}", "a".repeat(100), "b".repeat(100));
+        let err = super::parse(&src, &Syntax::default())
+            .unwrap_err()
+            .to_string();
+        let lines: Vec<_> = err.lines().collect();
+        assert_eq!(
+            lines[0],
+            "problems parsing template source at row 1, column 101"
+        );
+        assert_eq!(
+            lines[2],
+            format!(
+                "1 | ...{}{{% endfo %}}{}...",
+                "a".repeat(40),
+                "b".repeat(29)
+            )
+        );
+        assert_eq!(lines[3], format!("  | {}^", " ".repeat(43)));
+    }
+
+    #[test]
+    fn test_source_message() {
+        let src = "{% for x in y %}\n{{ x|uper }}\n{% endfor %}";
+        assert_eq!(
+            super::source_message("unknown filter", src, 22, 4, None),
+            "unknown filter at row 2, column 6\n  |\n2 | {{ x|uper }}\n  |      ^^^^"
+        );
+        // The carets stop at the end of the line.
+        assert_eq!(
+            super::source_message("unknown block", src, 3, 100, None),
+            "unknown block at row 1, column 4\n  |\n1 | {% for x in y %}\n  |    ^^^^^^^^^^^^^"
+        );
+    }
+
+    #[test]
+    fn test_parse_output_comment() {
+        let s = Syntax::default();
+        assert_eq!(
+            super::parse("{#! Generated, do not edit !#}", &s).unwrap(),
+            vec![Node::OutputComment(
+                Ws(false, false),
+                "Generated, do not edit"
+            )],
+        );
+        assert_eq!(
+            super::parse("a {#!- b\n c -!#} d", &s).unwrap(),
+            vec![
+                Node::Lit("", "a", " "),
+                Node::OutputComment(Ws(true, true), "b\n c"),
+                Node::Lit(" ", "d", ""),
+            ],
+        );
+        assert_eq!(
+            super::parse("{# {#! b !#} #}", &s).unwrap(),
+            vec![Node::Comment(Ws(false, false))],
+        );
+        assert_eq!(
+            super::parse("{#! b #}", &s).unwrap(),
+            vec![Node::Comment(Ws(false, false))],
+        );
+        assert_eq!(
+            super::parse("{#! b #}{#!! c !#}", &s).unwrap(),
+            vec![
+                Node::Comment(Ws(false, false)),
+                Node::OutputComment(Ws(false, false), "! c")
+            ],
+        );
+    }
+
+    #[test]
+    fn test_parse_include_str() {
+        let s = Syntax::default();
+        assert_eq!(
+            super::parse("{%- include_str \"icon.svg\" %}", &s).unwrap(),
+            vec![Node::IncludeStr(Ws(true, false), "icon.svg")],
+        );
+        assert!(super::parse("{% include_str \"a\" or \"b\" %}", &s).is_err());
+    }
+
+    #[test]
+    fn test_parse_include() {
+        let s = Syntax::default();
+        assert_eq!(
+            super::parse("{% include \"a.html\" %}", &s).unwrap(),
+            vec![Node::Include(Ws(false, false), vec!["a.html"], None, false)],
+        );
+        assert_eq!(
+            super::parse("{%- include \"a.html\" with item.page -%}", &s).unwrap(),
+            vec![Node::Include(
+                Ws(true, true),
+                vec!["a.html"],
+                Some(Expr::Attr(Box::new(Expr::Var("item")), "page")),
+                false,
+            )],
+        );
+        assert_eq!(
+            super::parse("{% include \"theme/a.html\" or \"a.html\" %}", &s).unwrap(),
+            vec![Node::Include(
+                Ws(false, false),
+                vec!["theme/a.html", "a.html"],
+                None,
+                false,
+            )],
+        );
+        assert_eq!(
+            super::parse("{% include \"a.txt\" escape_as_parent %}", &s).unwrap(),
+            vec![Node::Include(Ws(false, false), vec!["a.txt"], None, true)],
+        );
+    }
+
+    #[test]
+    fn test_parse_extends() {
+        let s = Syntax::default();
+        assert_eq!(
+            super::parse("{% extends \"base.html\" %}", &s).unwrap(),
+            vec![Node::Extends(vec![("base.html", None)])],
+        );
+        assert_eq!(
+            super::parse(
+                "{% extends \"m.html\" if mobile else \"t.html\" if self.tablet() else \"d.html\" %}",
+                &s
+            )
+            .unwrap(),
+            vec![Node::Extends(vec![
+                ("m.html", Some(Expr::Var("mobile"))),
+                (
+                    "t.html",
+                    Some(Expr::MethodCall(
+                        Box::new(Expr::Var("self")),
+                        "tablet",
+                        vec![]
+                    ))
+                ),
+                ("d.html", None),
+            ])],
+        );
+        assert!(super::parse("{% extends \"m.html\" if mobile %}", &s).is_err());
+    }
+
+    #[test]
+    fn test_parse_macro_default_args() {
+        let s = Syntax::default();
+        match &super::parse("{% macro m(a, b = 1, c=a) %}{% endmacro %}", &s).unwrap()[..] {
+            [Node::Macro("m", def)] => assert_eq!(
+                def.args,
+                vec![
+                    ("a", None),
+                    ("b", Some(Expr::NumLit("1"))),
+                    ("c", Some(Expr::Var("a"))),
+                ]
+            ),
+            nodes => panic!("unexpected nodes {:?}", nodes),
+        }
+        assert!(super::parse("{% macro m(a = 1, b) %}{% endmacro %}", &s).is_err());
+    }
+
+    #[test]
+    fn test_parse_super() {
+        let s = Syntax::default();
+        assert_eq!(
+            super::parse("{{ super() }}{%- call super() %}", &s).unwrap(),
+            vec![
+                Node::Call(Ws(false, false), None, "super", vec![]),
+                Node::Call(Ws(true, false), None, "super", vec![]),
+            ],
+        );
+        assert_eq!(
+            super::parse("{{ super()|upper }}", &s).unwrap(),
+            vec![Node::Expr(
+                Ws(false, false),
+                Expr::Filter("upper", vec![Expr::VarCall("super", vec![])]),
+            )],
+        );
+    }
+
+    #[test]
+    fn test_parse_match_patterns() {
+        let s = Syntax::default();
+        match &super::parse(
+            "{% match x %}{% when n @ (1 | 2) if *n > 1 %}{% when S { a, .. } %}\
+             {% when n @ 3..=9 %}{% when 'a'..'z' | ..=0 | 10.. %}{% endmatch %}",
+            &s,
+        )
+        .unwrap()[..]
+        {
+            [Node::Match(_, Expr::Var("x"), arms, _)] => {
+                assert_eq!(
+                    arms[0].1,
+                    Target::Binding(
+                        "n",
+                        Box::new(Target::Or(vec![Target::NumLit("1"), Target::NumLit("2")]))
+                    )
+                );
+                assert_eq!(
+                    arms[0].2,
+                    Some(Expr::BinOp(
+                        ">",
+                        Box::new(Expr::Unary("*", Box::new(Expr::Var("n")))),
+                        Box::new(Expr::NumLit("1"))
+                    ))
+                );
+                assert_eq!(
+                    arms[1].1,
+                    Target::Struct(
+                        vec!["S"],
+                        vec![("a", Target::Name("a")), ("..", Target::Rest)]
+                    )
+                );
+                assert_eq!(arms[1].2, None);
+                assert_eq!(
+                    arms[2].1,
+                    Target::Binding(
+                        "n",
+                        Box::new(Target::Range(
+                            "..=",
+                            Some(Box::new(Target::NumLit("3"))),
+                            Some(Box::new(Target::NumLit("9")))
+                        ))
+                    )
+                );
+                assert_eq!(
+                    arms[3].1,
+                    Target::Or(vec![
+                        Target::Range(
+                            "..",
+                            Some(Box::new(Target::CharLit("a"))),
+                            Some(Box::new(Target::CharLit("z")))
+                        ),
+                        Target::Range("..=", None, Some(Box::new(Target::NumLit("0")))),
+                        Target::Range("..", Some(Box::new(Target::NumLit("10"))), None),
+                    ])
+                );
+            }
+            nodes => panic!("unexpected nodes {:?}", nodes),
+        }
+    }
+
+    #[test]
+    fn test_parse_call_named_args() {
+        let s = Syntax::default();
+        assert_eq!(
+            super::parse("{% call m(x, b = 2, c=x == 1) %}", &s).unwrap(),
+            vec![Node::Call(
+                Ws(false, false),
+                None,
+                "m",
+                vec![
+                    Expr::Var("x"),
+                    Expr::NamedArgument("b", Box::new(Expr::NumLit("2"))),
+                    Expr::NamedArgument(
+                        "c",
+                        Box::new(Expr::BinOp(
+                            "==",
+                            Box::new(Expr::Var("x")),
+                            Box::new(Expr::NumLit("1"))
+                        ))
+                    ),
+                ],
+            )],
+        );
+        assert_eq!(
+            super::parse("{% call m(x == 1) %}", &s).unwrap(),
+            vec![Node::Call(
+                Ws(false, false),
+                None,
+                "m",
+                vec![Expr::BinOp(
+                    "==",
+                    Box::new(Expr::Var("x")),
+                    Box::new(Expr::NumLit("1"))
+                )],
+            )],
+        );
+    }
 }
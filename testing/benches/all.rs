@@ -10,9 +10,35 @@ criterion_group!(benches, functions);
 fn functions(c: &mut Criterion) {
     c.bench_function("Big table", |b| big_table(b, 100));
     c.bench_function("Teams", teams);
+    c.bench_function("Integer rows", |b| int_table(b, 10_000));
+    c.bench_function("Big table, String to bytes", |b| big_table_bytes(b, 100));
+    c.bench_function("Big table, write_into", |b| big_table_write_into(b, 100));
 }
 
 fn big_table(b: &mut criterion::Bencher, size: usize) {
+    let ctx = big_table_ctx(size);
+    b.iter(|| ctx.render().unwrap());
+}
+
+fn big_table_bytes(b: &mut criterion::Bencher, size: usize) {
+    let ctx = big_table_ctx(size);
+    let mut out = Vec::with_capacity(ctx.size_hint());
+    b.iter(|| {
+        out.clear();
+        out.extend_from_slice(ctx.render().unwrap().as_bytes());
+    });
+}
+
+fn big_table_write_into(b: &mut criterion::Bencher, size: usize) {
+    let ctx = big_table_ctx(size);
+    let mut out = Vec::with_capacity(ctx.size_hint());
+    b.iter(|| {
+        out.clear();
+        ctx.write_into(&mut out).unwrap();
+    });
+}
+
+fn big_table_ctx(size: usize) -> BigTable {
     let mut table = Vec::with_capacity(size);
     for _ in 0..size {
         let mut inner = Vec::with_capacity(size);
@@ -21,8 +47,7 @@ fn big_table(b: &mut criterion::Bencher, size: usize) {
         }
         table.push(inner);
     }
-    let ctx = BigTable { table };
-    b.iter(|| ctx.render().unwrap());
+    BigTable { table }
 }
 
 #[derive(Template)]
@@ -31,6 +56,20 @@ struct BigTable {
     table: Vec<Vec<usize>>,
 }
 
+fn int_table(b: &mut criterion::Bencher, size: usize) {
+    let rows = (0..size)
+        .map(|i| (i, i as i64 - 5_000, i as u32 * 31))
+        .collect();
+    let ctx = IntTable { rows };
+    b.iter(|| ctx.render().unwrap());
+}
+
+#[derive(Template)]
+#[template(path = "int-table.html")]
+struct IntTable {
+    rows: Vec<(usize, i64, u32)>,
+}
+
 fn teams(b: &mut criterion::Bencher) {
     let teams = Teams {
         year: 2015,
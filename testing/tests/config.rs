@@ -0,0 +1,34 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(
+    source = "[[ name ]] {{ name }}",
+    ext = "txt",
+    config = "configs/brackets.toml"
+)]
+struct ConfigAttributeTemplate<'a> {
+    name: &'a str,
+}
+
+#[test]
+fn test_config_attribute() {
+    let t = ConfigAttributeTemplate { name: "foo" };
+    assert_eq!(t.render().unwrap(), "foo {{ name }}");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% if a %}\n  {% if b %}\n    yes\n  {% endif %}\n{% endif %}\n",
+    ext = "txt",
+    config = "configs/trim-blocks.toml"
+)]
+struct TrimBlocksConfigTemplate {
+    a: bool,
+    b: bool,
+}
+
+#[test]
+fn test_config_trim_blocks() {
+    let t = TrimBlocksConfigTemplate { a: true, b: true };
+    assert_eq!(t.render().unwrap(), "    yes\n");
+}
@@ -0,0 +1,37 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(
+    source = "<%# greeting %><% if cond %>Hello, <%= name %>!<% endif %> {{ raw }}",
+    ext = "txt",
+    delimiters = ("<% %>", "<%= %>", "<%# %>")
+)]
+struct ErbTemplate<'a> {
+    cond: bool,
+    name: &'a str,
+}
+
+#[test]
+fn test_erb_delimiters() {
+    let t = ErbTemplate {
+        cond: true,
+        name: "world",
+    };
+    assert_eq!(t.render().unwrap(), "Hello, world! {{ raw }}");
+}
+
+#[derive(Template)]
+#[template(
+    source = r"\VAR{ title|upper } \BLOCK{ for i in 1..=3 }\VAR{ i }\BLOCK{- endfor }\#{ note }",
+    ext = "txt",
+    delimiters = (r"\BLOCK{ }", r"\VAR{ }", r"\#{ }")
+)]
+struct LatexTemplate<'a> {
+    title: &'a str,
+}
+
+#[test]
+fn test_latex_delimiters() {
+    let t = LatexTemplate { title: "intro" };
+    assert_eq!(t.render().unwrap(), "INTRO 123");
+}
@@ -0,0 +1,40 @@
+use std::collections::HashMap;
+
+use askama::{DynTemplate, Template};
+
+#[derive(Template)]
+#[template(source = "<h1>{{ title }}</h1>", ext = "html")]
+struct PageTemplate<'a> {
+    title: &'a str,
+}
+
+#[derive(Template)]
+#[template(source = "{{ a }} + {{ b }}", ext = "txt")]
+struct SumTemplate {
+    a: u32,
+    b: u32,
+}
+
+#[test]
+fn test_dyn_template() {
+    let mut routes: HashMap<&str, Box<dyn DynTemplate>> = HashMap::new();
+    routes.insert("page", Box::new(PageTemplate { title: "<Home>" }));
+    routes.insert("sum", Box::new(SumTemplate { a: 1, b: 2 }));
+
+    let page = &routes["page"];
+    assert_eq!(page.dyn_render().unwrap(), "<h1>&lt;Home&gt;</h1>");
+    assert_eq!(page.dyn_mime_type(), "text/html; charset=utf-8");
+
+    let sum = &routes["sum"];
+    let mut buf = String::from("= ");
+    sum.dyn_render_into(&mut buf).unwrap();
+    assert_eq!(buf, "= 1 + 2");
+    let mut bytes = Vec::new();
+    sum.dyn_write_into(&mut bytes).unwrap();
+    assert_eq!(bytes, b"1 + 2");
+    assert_eq!(sum.dyn_mime_type(), "text/plain; charset=utf-8");
+
+    // Not ambiguous with both traits in scope.
+    let page = PageTemplate { title: "" };
+    assert_eq!(page.mime_type(), "text/html; charset=utf-8");
+}
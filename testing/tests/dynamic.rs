@@ -0,0 +1,59 @@
+#![cfg(feature = "serde_json")]
+
+use askama::Template;
+use serde_json::{json, Value};
+
+#[derive(Template)]
+#[template(
+    source = "<h1>{{ data.title }}</h1> {{ data[\"items\"][0].name }}, {{ data.items[1][\"name\"] }} \
+              ({{ data.items|default(\"none\") }})\
+              {% for i in indexes %} {{ data.items[i].name|default(\"-\") }}{% endfor %} \
+              [{{ data.missing.deeply }}] {{ data.subtitle|default(\"No subtitle\", true) }} \
+              {{ data.count }} {{ count }} {{ data.title|upper }} {{ data.count|fmt(\"{:>3}\") }}",
+    ext = "html",
+    dynamic = "data"
+)]
+struct DynamicTemplate<'a> {
+    data: &'a Value,
+    indexes: &'a [usize],
+    count: u32,
+}
+
+#[test]
+fn test_dynamic() {
+    let data = json!({
+        "title": "<News>",
+        "subtitle": "",
+        "items": [{"name": "a"}, {"name": "b"}],
+        "count": 2,
+    });
+    let t = DynamicTemplate {
+        data: &data,
+        indexes: &[1, 2],
+        count: 3,
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "<h1>&lt;News&gt;</h1> a, b \
+         ([{&quot;name&quot;:&quot;a&quot;},{&quot;name&quot;:&quot;b&quot;}]) b - \
+         [] No subtitle 2 3 &lt;NEWS&gt;   2"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% let data = \"shadowed\" %}{{ data }} {{ self.data.title }}",
+    ext = "txt",
+    dynamic = "data"
+)]
+struct DynamicShadowTemplate {
+    data: Value,
+}
+
+#[test]
+fn test_dynamic_shadowed() {
+    let t = DynamicShadowTemplate {
+        data: json!({"title": "T"}),
+    };
+    assert_eq!(t.render().unwrap(), "shadowed T");
+}
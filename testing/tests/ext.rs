@@ -65,3 +65,75 @@ fn test_path_ext_html_jinja_and_ext_txt() {
     assert_eq!(t.render().unwrap(), "foo.html.jinja");
     assert_eq!(t.extension(), Some("txt"));
 }
+
+#[derive(Template)]
+#[template(source = "{}", ext = "json")]
+struct SourceJson;
+
+#[derive(Template)]
+#[template(source = "<rss/>", ext = "xml", mime_type = "application/rss+xml")]
+struct SourceRss;
+
+#[test]
+fn test_mime_type() {
+    use askama::SizedTemplate;
+
+    assert_eq!(PathHtml.mime_type(), "text/html; charset=utf-8");
+    assert_eq!(PathHtmlJinja.mime_type(), "text/html; charset=utf-8");
+    assert_eq!(
+        PathHtmlJinjaAndExtTxt.mime_type(),
+        "text/plain; charset=utf-8"
+    );
+    assert_eq!(PathJinja.mime_type(), "text/plain; charset=utf-8");
+    assert_eq!(SourceJson.mime_type(), "application/json");
+    assert_eq!(SourceRss.mime_type(), "application/rss+xml");
+    assert_eq!(
+        <SourceRss as SizedTemplate>::mime_type(),
+        "application/rss+xml"
+    );
+}
+
+macro_rules! escaped_ext {
+    ($name:ident, $ext:literal) => {
+        #[derive(Template)]
+        #[template(source = "{{ s }}", ext = $ext)]
+        struct $name<'a> {
+            s: &'a str,
+        }
+    };
+}
+
+escaped_ext!(ExtHtm, "htm");
+escaped_ext!(ExtXml, "xml");
+escaped_ext!(ExtSvg, "svg");
+escaped_ext!(ExtTxt, "txt");
+escaped_ext!(ExtMd, "md");
+escaped_ext!(ExtCsv, "csv");
+
+#[test]
+fn test_ext_escapers() {
+    let s = "<a&b>";
+    assert_eq!(ExtHtm { s }.render().unwrap(), "&lt;a&amp;b&gt;");
+    assert_eq!(ExtXml { s }.render().unwrap(), "&lt;a&amp;b&gt;");
+    assert_eq!(ExtSvg { s }.render().unwrap(), "&lt;a&amp;b&gt;");
+    assert_eq!(ExtTxt { s }.render().unwrap(), s);
+    assert_eq!(ExtMd { s }.render().unwrap(), s);
+    assert_eq!(ExtCsv { s }.render().unwrap(), s);
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ s }}",
+    ext = "tex",
+    config = "configs/fallback-escaper.toml"
+)]
+struct ExtFallback<'a> {
+    s: &'a str,
+}
+
+#[test]
+fn test_ext_fallback_escaper() {
+    let t = ExtFallback { s: "<a&b>" };
+    assert_eq!(t.render().unwrap(), "&lt;a&amp;b&gt;");
+    assert_eq!(t.extension(), Some("tex"));
+}
@@ -0,0 +1,53 @@
+use std::collections::{BTreeMap, HashMap};
+
+use askama::Template;
+
+#[derive(Template)]
+#[template(
+    source = "{{ get(\"title\")|default(\"Untitled\") }}: \
+              {% if let Some(author) = get(\"author\") %}by {{ author }}{% else %}anonymous{% endif %}\
+              {% for key in keys %} {{ key }}={{ get(key)|default(\"-\") }}{% endfor %}",
+    ext = "html",
+    extra = "ctx"
+)]
+struct ExtraTemplate<'a> {
+    ctx: HashMap<String, String>,
+    keys: &'a [&'a str],
+}
+
+#[test]
+fn test_extra() {
+    let mut ctx = HashMap::new();
+    ctx.insert("title".to_string(), "Tom & Jerry".to_string());
+    ctx.insert("year".to_string(), "1940".to_string());
+    let t = ExtraTemplate {
+        ctx,
+        keys: &["year", "studio"],
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "Tom &amp; Jerry: anonymous year=1940 studio=-"
+    );
+
+    let mut ctx = HashMap::new();
+    ctx.insert("author".to_string(), "Hanna".to_string());
+    let t = ExtraTemplate { ctx, keys: &[] };
+    assert_eq!(t.render().unwrap(), "Untitled: by Hanna");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ get(\"k\")|default(\"\") }}",
+    ext = "txt",
+    extra = "values"
+)]
+struct ExtraBTreeMapTemplate {
+    values: BTreeMap<&'static str, &'static str>,
+}
+
+#[test]
+fn test_extra_btree_map() {
+    let mut values = BTreeMap::new();
+    values.insert("k", "v");
+    assert_eq!(ExtraBTreeMapTemplate { values }.render().unwrap(), "v");
+}
@@ -0,0 +1,84 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(
+    source = "{% filter upper %}<p>Hello, {{ name }}!</p>{% endfilter %}",
+    ext = "html"
+)]
+struct FilterBlockTemplate<'a> {
+    name: &'a str,
+}
+
+#[test]
+fn test_filter_block() {
+    let t = FilterBlockTemplate { name: "<world>" };
+    assert_eq!(t.render().unwrap(), "<P>HELLO, &LT;WORLD&GT;!</P>");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% filter trim %} {{ s }} {% endfilter %} \
+              {% filter lower %}{% filter upper %}{{ s }}{% endfilter %}{% endfilter %} \
+              <b>{% filter lower %}{{ s|safe }}{% endfilter %}</b>",
+    ext = "html"
+)]
+struct FilterBlockEscapeTemplate<'a> {
+    s: &'a str,
+}
+
+#[test]
+fn test_filter_block_escape() {
+    let t = FilterBlockEscapeTemplate { s: "<a>" };
+    assert_eq!(t.render().unwrap(), "&lt;a&gt; &lt;a&gt; <b><a></b>");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{%- filter trim|truncate(8, \"...\") -%}
+    {% for s in strings %}{{ s }} {% endfor %}
+{%- endfilter -%}",
+    ext = "txt"
+)]
+struct FilterBlockChainTemplate {
+    strings: Vec<&'static str>,
+}
+
+#[test]
+fn test_filter_block_chain() {
+    let t = FilterBlockChainTemplate {
+        strings: vec!["foo", "bar", "baz"],
+    };
+    assert_eq!(t.render().unwrap(), "foo bar ...");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% filter lower %}A{% filter upper %}b{{ c }}{% endfilter %}D{% endfilter %}",
+    ext = "txt"
+)]
+struct FilterBlockNestedTemplate {
+    c: char,
+}
+
+#[test]
+fn test_filter_block_nested() {
+    let t = FilterBlockNestedTemplate { c: 'C' };
+    assert_eq!(t.render().unwrap(), "abcd");
+}
+
+#[derive(Template)]
+#[template(
+    source = "items:\n  {% filter indent(2) %}- {{ a }}\n- {{ b }}\n{% endfilter %}\
+              {{ \"x\\r\\ny\"|indent(1, true) }}",
+    ext = "txt"
+)]
+struct FilterBlockIndentTemplate<'a> {
+    a: &'a str,
+    b: &'a str,
+}
+
+#[test]
+fn test_filter_block_indent() {
+    let t = FilterBlockIndentTemplate { a: "one", b: "two" };
+    assert_eq!(t.render().unwrap(), "items:\n  - one\n  - two\n x\r\n y");
+}
@@ -1,11 +1,11 @@
 #![allow(clippy::blacklisted_name)]
 
-#[cfg(feature = "serde-json")]
+#[cfg(feature = "serde_json")]
 #[macro_use]
 extern crate serde_json;
 
 use askama::Template;
-#[cfg(feature = "serde-json")]
+#[cfg(feature = "serde_json")]
 use serde_json::Value;
 
 #[derive(Template)]
@@ -50,6 +50,21 @@ fn filter_opt_escaper_none() {
     );
 }
 
+#[derive(Template)]
+#[template(
+    source = "{{ s|e(\"none\") }} {{ s|e(\"html\") }} {{ s }}",
+    ext = "html"
+)]
+struct OptEscaperPerExprTemplate<'a> {
+    s: &'a str,
+}
+
+#[test]
+fn filter_opt_escaper_per_expr() {
+    let t = OptEscaperPerExprTemplate { s: "<b>" };
+    assert_eq!(t.render().unwrap(), "<b> &lt;b&gt; &lt;b&gt;");
+}
+
 #[derive(Template)]
 #[template(
     source = "{{ \"<h1 class=\\\"title\\\">Foo Bar</h1>\"|escape(\"none\") }}
@@ -74,6 +89,47 @@ fn filter_opt_escaper_html() {
     );
 }
 
+#[derive(Template)]
+#[template(source = "{\"name\": \"{{ name }}\"}", ext = "json")]
+struct JsonEscapeTemplate<'a> {
+    name: &'a str,
+}
+
+#[test]
+fn test_json_escaper() {
+    let t = JsonEscapeTemplate {
+        name: "\"Bobby\" </script>\n",
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        r#"{"name": "\"Bobby\" \u003c/script\u003e\n"}"#
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "<div title=\"{{ title|attr }}\" class={{ class|attr }} data-x='{{ title|e(\"attr\") }}'>{{ title }}</div>",
+    ext = "html"
+)]
+struct AttrEscapeTemplate<'a> {
+    title: &'a str,
+    class: &'a str,
+}
+
+#[test]
+fn test_attr_escaper() {
+    let t = AttrEscapeTemplate {
+        title: "\"'><script>",
+        class: "a onclick=alert(1)",
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "<div title=\"&quot;&#x27;&gt;&lt;script&gt;\" \
+         class=a&#x20;onclick&#x3D;alert&#x28;1&#x29; \
+         data-x='&quot;&#x27;&gt;&lt;script&gt;'>&quot;&#x27;&gt;&lt;script&gt;</div>"
+    );
+}
+
 #[derive(Template)]
 #[template(path = "format.html", escape = "none")]
 struct FormatTemplate<'a> {
@@ -87,7 +143,11 @@ fn filter_format() {
 }
 
 #[derive(Template)]
-#[template(source = "{{ var|fmt(\"{:?}\") }}", ext = "html", escape = "none")]
+#[template(
+    source = "{{ var|fmt(\"{:?}\") }} {{ var|fmt(\"{0} {0}\") }}",
+    ext = "html",
+    escape = "none"
+)]
 struct FmtTemplate<'a> {
     var: &'a str,
 }
@@ -95,7 +155,92 @@ struct FmtTemplate<'a> {
 #[test]
 fn filter_fmt() {
     let t = FmtTemplate { var: "formatted" };
-    assert_eq!(t.render().unwrap(), "\"formatted\"");
+    assert_eq!(t.render().unwrap(), "\"formatted\" formatted formatted");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ price|format(\"{:.2}\") }} {{ name|format(\"[{:>5}]\") }} \
+              {{ price|format(\"{{{:e}}}\") }}",
+    ext = "html"
+)]
+struct FormatSpecTemplate<'a> {
+    price: f64,
+    name: &'a str,
+}
+
+#[test]
+fn filter_format_spec() {
+    let t = FormatSpecTemplate {
+        price: 1234.5,
+        name: "<a>",
+    };
+    assert_eq!(t.render().unwrap(), "1234.50 [  &lt;a&gt;] {1.2345e3}");
+}
+
+#[derive(Template)]
+#[template(
+    source = "[{{ s|trim|upper }}] [{{ s|trim_start }}] [{{ s|trim_end }}] \
+              [{{ path|trim(\"/\") }}] [{{ path|upper|trim_start(\"/\")|trim_end(\"/\")|lower }}]",
+    ext = "txt"
+)]
+struct TrimTemplate<'a> {
+    s: &'a str,
+    path: String,
+}
+
+#[test]
+fn filter_trim() {
+    let t = TrimTemplate {
+        s: "  hello\t",
+        path: "/a/B/".into(),
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "[HELLO] [hello\t] [  hello] [a/B] [a/b]"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "[{{ n|trim }}] [{{ s|e|trim|safe }}] [{{ s|upper|trim|trim_end(\">\") }}]",
+    ext = "html"
+)]
+struct TrimDisplayTemplate<'a> {
+    n: u32,
+    s: &'a str,
+}
+
+#[test]
+fn filter_trim_display() {
+    let t = TrimDisplayTemplate { n: 42, s: " <b> " };
+    assert_eq!(t.render().unwrap(), "[42] [&lt;b&gt;] [&lt;B]");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ delta|abs }} {{ price|round }} {{ price|round(1) }} \
+              {{ price|floor }} {{ price|ceil }} {{ (price * 2.0)|abs|round(2) }}",
+    ext = "txt"
+)]
+struct NumberTemplate {
+    delta: i32,
+    price: f64,
+}
+
+#[test]
+fn filter_number() {
+    let t = NumberTemplate {
+        delta: -3,
+        price: -2.46,
+    };
+    assert_eq!(t.render().unwrap(), "3 -2 -2.5 -3 -2 4.92");
+
+    let t = NumberTemplate {
+        delta: i32::MIN,
+        price: 0.0,
+    };
+    assert!(t.render().is_err());
 }
 
 #[derive(Template)]
@@ -127,6 +272,15 @@ mod filters {
         let s = format!("{}", s);
         Ok(s.trim().to_owned())
     }
+    // for test_custom_error
+    pub fn parse_port(s: &str) -> ::askama::Result<u16> {
+        s.parse().map_err(::askama::Error::custom)
+    }
+    // for test_escaping_filter
+    pub fn strong(s: &str) -> ::askama::Result<String> {
+        use ::askama::escaping::{escape, Html};
+        Ok(format!("<strong>{}</strong>", escape(s, Html)))
+    }
 }
 
 #[test]
@@ -135,6 +289,104 @@ fn test_my_filter() {
     assert_eq!(t.render().unwrap(), "faa");
 }
 
+#[derive(Template)]
+#[template(source = "{{ s|strong|safe }} {{ s }}", ext = "html")]
+struct EscapingFilterTemplate<'a> {
+    s: &'a str,
+}
+
+#[test]
+fn test_escaping_filter() {
+    let t = EscapingFilterTemplate { s: "<b> & 'c'" };
+    assert_eq!(
+        t.render().unwrap(),
+        "<strong>&lt;b&gt; &amp; &#x27;c&#x27;</strong> &lt;b&gt; &amp; &#x27;c&#x27;"
+    );
+}
+
+pub mod filter_library {
+    pub fn shout<T: std::fmt::Display>(s: T) -> ::askama::Result<String> {
+        Ok(format!("{}!", s.to_string().to_uppercase()))
+    }
+    // named like a built-in filter, which takes precedence
+    pub fn upper(_: &str) -> ::askama::Result<String> {
+        Ok("shadowed".into())
+    }
+}
+
+mod views {
+    use askama::Template;
+
+    #[derive(Template)]
+    #[template(
+        source = "{{ s|shout }} {{ n|shout }} {{ s|upper }}",
+        ext = "txt",
+        filters = "crate::filter_library"
+    )]
+    pub struct FilterLibraryTemplate<'a> {
+        pub s: &'a str,
+        pub n: u32,
+    }
+}
+
+#[test]
+fn test_filters_path() {
+    let t = views::FilterLibraryTemplate { s: "hey", n: 3 };
+    assert_eq!(t.render().unwrap(), "HEY! 3! HEY");
+}
+
+#[derive(Template)]
+#[template(source = "{{ s|parse_port }}", ext = "txt")]
+struct CustomErrorTemplate<'a> {
+    s: &'a str,
+}
+
+#[test]
+fn test_custom_error() {
+    let t = CustomErrorTemplate { s: "8080" };
+    assert_eq!(t.render().unwrap(), "8080");
+
+    let t = CustomErrorTemplate { s: "port" };
+    let err = t.render().unwrap_err();
+    match err {
+        askama::Error::Render {
+            template,
+            filter,
+            ref source,
+        } => {
+            assert_eq!(template, "CustomErrorTemplate.txt");
+            assert_eq!(filter, "parse_port");
+            assert!(matches!(**source, askama::Error::Custom(_)));
+        }
+        _ => panic!("expected a render error, got {:?}", err),
+    }
+    assert_eq!(
+        err.to_string(),
+        "error in filter `parse_port` of template `CustomErrorTemplate.txt`: \
+         custom error: invalid digit found in string"
+    );
+}
+
+#[derive(Template)]
+#[template(source = "port: {% include \"parse-port.txt\" %}", ext = "txt")]
+struct IncludedErrorTemplate<'a> {
+    s: &'a str,
+}
+
+#[test]
+fn test_included_error() {
+    let t = IncludedErrorTemplate { s: "port" };
+    let err = t.render().unwrap_err();
+    assert!(matches!(
+        err,
+        askama::Error::Render {
+            template: "parse-port.txt",
+            filter: "parse_port",
+            ..
+        }
+    ));
+}
+
 #[derive(Template)]
 #[template(path = "filters_join.html")]
 struct JoinTemplate<'a> {
@@ -163,7 +415,251 @@ fn test_vec_join() {
     assert_eq!(t.render().unwrap(), "foo, bar, bazz");
 }
 
-#[cfg(feature = "serde-json")]
+struct User {
+    name: &'static str,
+    address: (&'static str, u32),
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ tags|join(\", \") }}|{{ users|join(\"<br>\", \"name\") }}|\
+              {{ users|join(\" \", \"address.1\") }}",
+    ext = "html"
+)]
+struct JoinEscapeTemplate<'a> {
+    tags: &'a [&'a str],
+    users: Vec<User>,
+}
+
+#[test]
+fn test_join_escape_and_attribute() {
+    let t = JoinEscapeTemplate {
+        tags: &["a&b", "<c>"],
+        users: vec![
+            User {
+                name: "<Ann>",
+                address: ("x", 1),
+            },
+            User {
+                name: "Bob",
+                address: ("y", 2),
+            },
+        ],
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "a&amp;b, &lt;c&gt;|&lt;Ann&gt;<br>Bob|1 2"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ users|map(\"name\")|join(\", \") }}|\
+              {% for n in users|map(\"address.1\") %}{{ n }} {% endfor %}|\
+              {% if let Some(name) = users|map(\"name\")|first %}{{ name }}{% endif %}",
+    ext = "html"
+)]
+struct MapTemplate {
+    users: Vec<User>,
+}
+
+#[test]
+fn test_map() {
+    let t = MapTemplate {
+        users: vec![
+            User {
+                name: "<Ann>",
+                address: ("x", 1),
+            },
+            User {
+                name: "Bob",
+                address: ("y", 2),
+            },
+        ],
+    };
+    assert_eq!(t.render().unwrap(), "&lt;Ann&gt;, Bob|1 2 |&lt;Ann&gt;");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ members|select(\"active\")|map(\"name\")|join(\", \") }}|\
+              {% for m in members|reject(\"active\") %}{{ m.name }} {% endfor %}|\
+              {{ members|select(\"age\", \">=\", 18)|length }} \
+              {{ members|reject(\"age\", \"<\", min_age)|map(\"name\")|join(\",\") }} \
+              {{ members|select(\"info.0\", \"==\", \"admin\")|map(\"name\")|join(\",\") }}",
+    ext = "html"
+)]
+struct SelectTemplate<'a> {
+    members: Vec<Member<'a>>,
+    min_age: u32,
+}
+
+struct Member<'a> {
+    name: &'a str,
+    active: bool,
+    age: u32,
+    info: (&'a str, u8),
+}
+
+#[test]
+fn test_select_reject() {
+    let member = |name, active, age, role| Member {
+        name,
+        active,
+        age,
+        info: (role, 0),
+    };
+    let t = SelectTemplate {
+        members: vec![
+            member("<Ann>", true, 34, "admin"),
+            member("Bob", false, 17, "user"),
+            member("Cid", true, 21, "user"),
+        ],
+        min_age: 30,
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "&lt;Ann&gt;, Cid|Bob |2 &lt;Ann&gt; &lt;Ann&gt;"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ users|map(\"address.0\")|unique|join(\", \") }}|\
+              {% for user in users|unique(\"address.0\") %}{{ user.name }} {% endfor %}|\
+              {{ tags|unique|sort|join(\",\") }}",
+    ext = "html"
+)]
+struct UniqueTemplate {
+    users: Vec<User>,
+    tags: Vec<&'static str>,
+}
+
+#[test]
+fn test_unique() {
+    let t = UniqueTemplate {
+        users: vec![
+            User {
+                name: "Ann",
+                address: ("no", 1),
+            },
+            User {
+                name: "Bob",
+                address: ("<se>", 2),
+            },
+            User {
+                name: "Cid",
+                address: ("no", 3),
+            },
+        ],
+        tags: vec!["rust", "web", "rust", "async", "web"],
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "no, &lt;se&gt;|Ann Bob |async,rust,web"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ nums|sort|join(\",\") }} {{ nums|sort(true)|join(\",\") }} \
+              {{ users|sort(\"address.1\")|map(\"name\")|join(\",\") }} \
+              {{ users|sort(\"name\", true)|map(\"name\")|join(\",\") }} \
+              {{ users|sort(\"name\", false, false)|map(\"name\")|join(\",\") }}",
+    ext = "txt"
+)]
+struct SortTemplate {
+    nums: Vec<i32>,
+    users: Vec<User>,
+}
+
+#[test]
+fn test_sort() {
+    let t = SortTemplate {
+        nums: vec![3, 1, 2],
+        users: vec![
+            User {
+                name: "bob",
+                address: ("x", 2),
+            },
+            User {
+                name: "Cid",
+                address: ("y", 3),
+            },
+            User {
+                name: "ann",
+                address: ("z", 1),
+            },
+        ],
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "1,2,3 3,2,1 ann,bob,Cid bob,ann,Cid ann,bob,Cid"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ text|slice(0, 5) }}|{{ text|slice(-3) }}|{{ text|slice(n, 100) }}|\
+              {% for x in nums|slice(1, -1) %}{{ x }}{% endfor %}|{{ nums|slice(2)|join(\",\") }}",
+    ext = "html"
+)]
+struct SliceTemplate<'a> {
+    text: &'a str,
+    nums: Vec<u8>,
+    n: usize,
+}
+
+#[test]
+fn test_slice() {
+    let t = SliceTemplate {
+        text: "<héllo> wörld",
+        nums: vec![1, 2, 3, 4],
+        n: 8,
+    };
+    assert_eq!(t.render().unwrap(), "&lt;héll|rld|wörld|23|3,4");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ text|reverse }}|{% for x in nums|reverse %}{{ x }}{% endfor %}|\
+              {{ nums|sort(true)|reverse|join(\",\") }}|{{ text|upper|reverse }}",
+    ext = "html"
+)]
+struct ReverseTemplate<'a> {
+    text: &'a str,
+    nums: Vec<u8>,
+}
+
+#[test]
+fn test_reverse() {
+    let t = ReverseTemplate {
+        text: "<ünï> 日本",
+        nums: vec![2, 3, 1],
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "本日 &gt;ïnü&lt;|132|1,2,3|本日 &gt;ÏNÜ&lt;"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "[{{ s|center(9) }}][{{ s|ljust(7) }}][{{ s|rjust(7, '.') }}][{{ n|rjust(4, '0') }}]",
+    ext = "txt"
+)]
+struct PaddingTemplate<'a> {
+    s: &'a str,
+    n: u32,
+}
+
+#[test]
+fn test_padding() {
+    let t = PaddingTemplate { s: "héllo", n: 7 };
+    assert_eq!(t.render().unwrap(), "[  héllo  ][héllo  ][..héllo][0007]");
+}
+
+#[cfg(feature = "serde_json")]
 #[derive(Template)]
 #[template(path = "json.html")]
 struct JsonTemplate<'a> {
@@ -171,7 +667,7 @@ struct JsonTemplate<'a> {
     bar: &'a Value,
 }
 
-#[cfg(feature = "serde-json")]
+#[cfg(feature = "serde_json")]
 #[test]
 fn test_json() {
     let val = json!({"arr": [ "one", 2, true, null ]});
@@ -196,51 +692,184 @@ fn test_json() {
     );
 }
 
+#[cfg(feature = "serde_json")]
 #[derive(Template)]
-#[template(source = "{{ x|mytrim|safe }}", ext = "html")]
-struct NestedFilterTemplate {
-    x: String,
+#[template(
+    source = "<script>const data = {{ data|json_script }};</script>",
+    ext = "html"
+)]
+struct JsonScriptTemplate<'a> {
+    data: &'a Value,
 }
 
+#[cfg(feature = "serde_json")]
 #[test]
-fn test_nested_filter_ref() {
-    let t = NestedFilterTemplate {
-        x: " floo & bar".to_string(),
-    };
-    assert_eq!(t.render().unwrap(), "floo & bar");
+fn test_json_script() {
+    let val = json!({"comment": "</script><b>&amp;</b>", "n": 1});
+    let t = JsonScriptTemplate { data: &val };
+    assert_eq!(
+        t.render().unwrap(),
+        r#"<script>const data = {"comment":"\u003c/script\u003e\u003cb\u003e\u0026amp;\u003c/b\u003e","n":1};</script>"#
+    );
 }
 
+#[cfg(feature = "markdown")]
 #[derive(Template)]
 #[template(
-    source = "{% let p = baz.print(foo.as_ref()) %}{{ p|upper }}",
+    source = "{{ text|markdown }}\n{{ text|markdown(true) }}",
     ext = "html"
 )]
-struct FilterLetFilterTemplate {
-    foo: String,
-    baz: Baz,
-}
-
-struct Baz {}
-
-impl Baz {
-    fn print(&self, s: &str) -> String {
-        s.trim().to_owned()
-    }
+struct MarkdownTemplate<'a> {
+    text: &'a str,
 }
 
+#[cfg(feature = "markdown")]
 #[test]
-fn test_filter_let_filter() {
-    let t = FilterLetFilterTemplate {
-        foo: " bar ".to_owned(),
-        baz: Baz {},
+fn test_markdown() {
+    let t = MarkdownTemplate {
+        text: "*Hi* <b>there</b>",
     };
-    assert_eq!(t.render().unwrap(), "BAR");
+    assert_eq!(
+        t.render().unwrap(),
+        "<p><em>Hi</em> &lt;b&gt;there&lt;/b&gt;</p>\n\n<p><em>Hi</em> <b>there</b></p>\n"
+    );
 }
 
+#[cfg(feature = "case")]
 #[derive(Template)]
-#[template(source = "{{ foo|truncate(10) }}{{ foo|truncate(5) }}", ext = "txt")]
-struct TruncateFilter {
-    foo: String,
+#[template(
+    source = "pub struct {{ name|pascalcase }};\n\
+              impl {{ name|pascalcase }} {\n\
+              {%- for field in fields %}\n\
+              pub const {{ field|shoutycase }}: &str = \"{{ field|kebabcase }}\";\n\
+              pub fn {{ field|snakecase }}(&self) {}\n\
+              // {{ field|camelcase }}\n\
+              {%- endfor %}\n\
+              }",
+    ext = "txt"
+)]
+struct CaseTemplate<'a> {
+    name: &'a str,
+    fields: &'a [&'a str],
+}
+
+#[cfg(feature = "case")]
+#[test]
+fn test_case() {
+    let t = CaseTemplate {
+        name: "http_server",
+        fields: &["maxConnections", "TLSVersion2"],
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "pub struct HttpServer;\n\
+         impl HttpServer {\n\
+         pub const MAX_CONNECTIONS: &str = \"max-connections\";\n\
+         pub fn max_connections(&self) {}\n\
+         // maxConnections\n\
+         pub const TLS_VERSION2: &str = \"tls-version2\";\n\
+         pub fn tls_version2(&self) {}\n\
+         // tlsVersion2\n\
+         }"
+    );
+}
+
+#[cfg(feature = "base64")]
+#[derive(Template)]
+#[template(
+    source = "<img src=\"data:image/png;base64,{{ bytes|base64 }}\"> {{ bytes|base64(true) }} \
+              {{ token|base64_decode }}",
+    ext = "html"
+)]
+struct Base64Template<'a> {
+    bytes: &'a [u8],
+    token: &'a str,
+}
+
+#[cfg(feature = "base64")]
+#[test]
+fn test_base64() {
+    let t = Base64Template {
+        bytes: &[0xfb, 0xff, 0xfe],
+        token: "PGI+",
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "<img src=\"data:image/png;base64,+//+\"> -__- &lt;b&gt;"
+    );
+
+    let t = Base64Template {
+        bytes: &[],
+        token: "+//+",
+    };
+    let err = t.render().unwrap_err();
+    assert!(matches!(
+        err,
+        askama::Error::Render { filter: "base64_decode", ref source, .. }
+            if matches!(**source, askama::Error::Utf8(_))
+    ));
+}
+
+#[derive(Template)]
+#[template(source = "{{ x|mytrim|safe }}", ext = "html")]
+struct NestedFilterTemplate {
+    x: String,
+}
+
+#[test]
+fn test_nested_filter_ref() {
+    let t = NestedFilterTemplate {
+        x: " floo & bar".to_string(),
+    };
+    assert_eq!(t.render().unwrap(), "floo & bar");
+}
+
+#[derive(Template)]
+#[template(source = "{{ x|safe }} {{ x|e|safe }}", ext = "txt")]
+struct SafeTextTemplate<'a> {
+    x: &'a str,
+}
+
+#[test]
+fn test_safe_text() {
+    let t = SafeTextTemplate { x: "<a> & b" };
+    assert_eq!(t.render().unwrap(), "<a> & b <a> & b");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% let p = baz.print(foo.as_ref()) %}{{ p|upper }}",
+    ext = "html"
+)]
+struct FilterLetFilterTemplate {
+    foo: String,
+    baz: Baz,
+}
+
+struct Baz {}
+
+impl Baz {
+    fn print(&self, s: &str) -> String {
+        s.trim().to_owned()
+    }
+}
+
+#[test]
+fn test_filter_let_filter() {
+    let t = FilterLetFilterTemplate {
+        foo: " bar ".to_owned(),
+        baz: Baz {},
+    };
+    assert_eq!(t.render().unwrap(), "BAR");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ foo|truncate(10) }}{{ foo|truncate(5) }}{{ foo|truncate(7, \"...\") }}{{ foo|truncate(7, \"\", true) }}",
+    ext = "txt"
+)]
+struct TruncateFilter {
+    foo: String,
 }
 
 #[test]
@@ -248,5 +877,551 @@ fn test_filter_truncate() {
     let t = TruncateFilter {
         foo: "alpha bar".into(),
     };
-    assert_eq!(t.render().unwrap(), "alpha baralpha...");
+    assert_eq!(t.render().unwrap(), "alpha baralpha…alpha b...alpha");
+}
+
+#[cfg(feature = "serde_json")]
+#[derive(Template)]
+#[template(
+    source = "{{ bar|json_pretty }}|{{ bar|json_pretty(4) }}",
+    ext = "html"
+)]
+struct JsonPrettyTemplate<'a> {
+    bar: &'a Value,
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn test_json_pretty() {
+    let val = json!({"a": ["<b>"]});
+    let t = JsonPrettyTemplate { bar: &val };
+    assert_eq!(
+        t.render().unwrap(),
+        "{\n  \"a\": [\n    \"<b>\"\n  ]\n}|{\n    \"a\": [\n        \"<b>\"\n    ]\n}"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ size|filesizeformat }} {{ size|filesizeformat(true) }} {{ 0|filesizeformat }}",
+    ext = "txt"
+)]
+struct FileSizeFormatTemplate {
+    size: u64,
+}
+
+#[test]
+fn test_filesizeformat() {
+    let t = FileSizeFormatTemplate { size: 1_500_000 };
+    assert_eq!(t.render().unwrap(), "1.50 MB 1.43 MiB 0 B");
+}
+
+#[derive(Template)]
+#[template(
+    source = "<a href=\"/search?q={{ q|urlencode }}&path={{ q|urlencode_strict }}\">",
+    ext = "html"
+)]
+struct UrlencodeTemplate<'a> {
+    q: &'a str,
+}
+
+#[test]
+fn test_urlencode() {
+    let t = UrlencodeTemplate { q: "a/b & <c>" };
+    assert_eq!(
+        t.render().unwrap(),
+        "<a href=\"/search?q=a/b%20%26%20%3Cc%3E&path=a%2Fb%20%26%20%3Cc%3E\">"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ day|date }} {{ day|date(\"%A %-d %B\") }} <time>{{ at|date(\"<%H:%M>\") }}</time>",
+    ext = "html"
+)]
+struct DateTemplate {
+    day: chrono::NaiveDate,
+    at: chrono::NaiveDateTime,
+}
+
+#[test]
+fn test_date() {
+    let day = chrono::NaiveDate::from_ymd_opt(2022, 1, 31).unwrap();
+    let t = DateTemplate {
+        day,
+        at: day.and_hms_opt(14, 30, 0).unwrap(),
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "2022-01-31 Monday 31 January <time>&lt;14:30&gt;</time>"
+    );
+}
+
+#[cfg(feature = "chrono-tz")]
+#[derive(Template)]
+#[template(
+    source = "{{ at|date(\"%H:%M\", tz=\"America/New_York\") }} {{ at|date(tz=zone) }}",
+    ext = "txt"
+)]
+struct DateTzTemplate<'a> {
+    at: chrono::DateTime<chrono::Utc>,
+    zone: &'a str,
+}
+
+#[cfg(feature = "chrono-tz")]
+#[test]
+fn test_date_tz() {
+    use chrono::TimeZone;
+
+    let at = chrono::Utc
+        .with_ymd_and_hms(2022, 1, 31, 14, 30, 0)
+        .unwrap();
+    let t = DateTzTemplate {
+        at,
+        zone: "Europe/Paris",
+    };
+    assert_eq!(t.render().unwrap(), "09:30 2022-01-31T15:30:00+01:00");
+
+    let t = DateTzTemplate {
+        at,
+        zone: "Europe/Atlantis",
+    };
+    assert_eq!(
+        t.render().unwrap_err().to_string(),
+        "error in filter `date` of template `DateTzTemplate.txt`: \
+         unknown time zone: \"Europe/Atlantis\""
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ n }} item{{ n|pluralize }}, {{ n }} cherr{{ n|pluralize(\"y\", \"ies\") }}",
+    ext = "txt"
+)]
+struct PluralizeTemplate {
+    n: u32,
+}
+
+#[test]
+fn test_pluralize() {
+    let t = PluralizeTemplate { n: 1 };
+    assert_eq!(t.render().unwrap(), "1 item, 1 cherry");
+    let t = PluralizeTemplate { n: 0 };
+    assert_eq!(t.render().unwrap(), "0 items, 0 cherries");
+    let t = PluralizeTemplate { n: 12 };
+    assert_eq!(t.render().unwrap(), "12 items, 12 cherries");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% if let Some(x) = values|first %}{{ x }}{% else %}none{% endif %} \
+              {% match names|last %}{% when Some with (n) %}{{ n }}{% when None %}none{% endmatch %}",
+    ext = "html"
+)]
+struct FirstLastTemplate<'a> {
+    values: Vec<u32>,
+    names: &'a [&'a str],
+}
+
+#[test]
+fn test_first_last() {
+    let t = FirstLastTemplate {
+        values: vec![3, 2, 1],
+        names: &["a", "<b>"],
+    };
+    assert_eq!(t.render().unwrap(), "3 &lt;b&gt;");
+    let t = FirstLastTemplate {
+        values: vec![],
+        names: &[],
+    };
+    assert_eq!(t.render().unwrap(), "none none");
+}
+
+#[cfg(feature = "rand")]
+#[derive(Template)]
+#[template(
+    source = "{% if let Some(q) = quotes|random %}{{ q }}{% endif %} \
+              {% match quotes|random(7) %}{% when Some with (q) %}{{ q }}{% when None %}-{% endmatch %}",
+    ext = "html"
+)]
+struct RandomTemplate<'a> {
+    quotes: &'a [&'a str],
+}
+
+#[cfg(feature = "rand")]
+#[test]
+fn test_random() {
+    let t = RandomTemplate { quotes: &["<a>"] };
+    assert_eq!(t.render().unwrap(), "&lt;a&gt; &lt;a&gt;");
+
+    let t = RandomTemplate {
+        quotes: &["a", "b", "c", "d"],
+    };
+    let rendered = t.render().unwrap();
+    let (picked, seeded) = rendered.split_once(' ').unwrap();
+    assert!(t.quotes.contains(&picked));
+    assert!(t.quotes.contains(&seeded));
+    for _ in 0..10 {
+        assert_eq!(t.render().unwrap().split_once(' ').unwrap().1, seeded);
+    }
+
+    let t = RandomTemplate { quotes: &[] };
+    assert_eq!(t.render().unwrap(), " -");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ name|default(\"<anon>\") }} {{ nick|default(\"-\", true) }} \
+              {{ count|default(0) }} {% let t = tags|default(no_tags, true) %}{{ t.len() }} \
+              {{ values|first|default(0) }}",
+    ext = "html"
+)]
+struct DefaultTemplate {
+    name: Option<String>,
+    nick: String,
+    count: Option<u32>,
+    tags: Vec<&'static str>,
+    no_tags: Vec<&'static str>,
+    values: Vec<u32>,
+}
+
+#[test]
+fn test_default() {
+    let t = DefaultTemplate {
+        name: None,
+        nick: String::new(),
+        count: None,
+        tags: vec![],
+        no_tags: vec!["none"],
+        values: vec![],
+    };
+    assert_eq!(t.render().unwrap(), "&lt;anon&gt; - 0 1 0");
+    let t = DefaultTemplate {
+        name: Some("Ann".into()),
+        nick: "annie".into(),
+        count: Some(3),
+        tags: vec!["a", "b"],
+        no_tags: vec!["none"],
+        values: vec![5],
+    };
+    assert_eq!(t.render().unwrap(), "Ann annie 3 2 5");
+}
+
+struct Product {
+    name: &'static str,
+    category: &'static str,
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% for (category, items) in products|groupby(\"category\") %}\
+              {{ category }}: {{ items.len() }}x {% for p in items -%}
+              {{ p.name }}{% if !loop.last %}, {% endif %}
+              {%- endfor %}; {% endfor %}",
+    ext = "txt"
+)]
+struct GroupByTemplate {
+    products: Vec<Product>,
+}
+
+#[test]
+fn test_groupby() {
+    let product = |name, category| Product { name, category };
+    let t = GroupByTemplate {
+        products: vec![
+            product("apple", "fruit"),
+            product("carrot", "vegetable"),
+            product("pear", "fruit"),
+            product("leek", "vegetable"),
+            product("bread", "bakery"),
+        ],
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "fruit: 2x apple, pear; vegetable: 2x carrot, leek; bakery: 1x bread; "
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% for row in cells|batch(3) %}{% for cell in row %}[{{ cell }}]{% endfor %}\n{% endfor %}\
+              {% for row in cells|batch(3, \"-\") %}{{ row|join(\"\") }}|{% endfor %}",
+    ext = "html"
+)]
+struct BatchTemplate<'a> {
+    cells: Vec<&'a str>,
+}
+
+#[test]
+fn test_batch() {
+    let t = BatchTemplate {
+        cells: vec!["a", "b", "c", "<d>"],
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "[a][b][c]\n[&lt;d&gt;]\nabc|&lt;d&gt;--|"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ name|length }} {{ name|bytelength }} {{ items|length }} \
+              {{ map|count }} {{ items|map(\"0\")|length }}",
+    ext = "txt"
+)]
+struct LengthTemplate<'a> {
+    name: &'a str,
+    items: Vec<(u8, u8)>,
+    map: std::collections::BTreeMap<u8, u8>,
+}
+
+#[test]
+fn test_length() {
+    let t = LengthTemplate {
+        name: "Zoë",
+        items: vec![(1, 2), (3, 4)],
+        map: vec![(1, 1)].into_iter().collect(),
+    };
+    assert_eq!(t.render().unwrap(), "3 4 2 1 2");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% for (k, v) in map|dictsort %}{{ k }}={{ v }} {% endfor %}|\
+              {% for (k, v) in map|dictsort(\"value\", true) %}{{ k }}={{ v }} {% endfor %}",
+    ext = "txt"
+)]
+struct DictSortTemplate {
+    map: std::collections::HashMap<&'static str, u32>,
+}
+
+#[test]
+fn test_dictsort() {
+    let t = DictSortTemplate {
+        map: vec![("b", 1), ("c", 3), ("a", 2)].into_iter().collect(),
+    };
+    assert_eq!(t.render().unwrap(), "a=2 b=1 c=3 |c=3 a=2 b=1 ");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ text|wordwrap(12) }}|{{ text|wordwrap(12, \"<br>\") }}|{{ \"abcdefghij\"|wordwrap(4, \"-\", true) }}",
+    ext = "html"
+)]
+struct WordWrapTemplate<'a> {
+    text: &'a str,
+}
+
+#[test]
+fn test_wordwrap() {
+    let t = WordWrapTemplate {
+        text: "Dear <user>, your order has shipped.",
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "Dear &lt;user&gt;,\nyour order\nhas shipped.|\
+         Dear &lt;user&gt;,&lt;br&gt;your order&lt;br&gt;has shipped.|abcd-efgh-ij"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ comment|linebreaks }}|{{ comment|linebreaksbr }}|{{ comment|paragraphbreaks }}",
+    ext = "html"
+)]
+struct LinebreaksTemplate<'a> {
+    comment: &'a str,
+}
+
+#[test]
+fn test_linebreaks() {
+    let t = LinebreaksTemplate {
+        comment: "<script>\nx\n\n&y",
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "<p>&lt;script&gt;<br/>x</p><p>&amp;y</p>|\
+         &lt;script&gt;<br/>x<br/><br/>&amp;y|\
+         <p>&lt;script&gt;\nx</p><p>&amp;y</p>"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "<li>{{ title|highlight(query) }}</li><li>{{ title|highlight(\"<\\\\w+>\", true) }}</li>",
+    ext = "html"
+)]
+struct HighlightTemplate<'a> {
+    title: &'a str,
+    query: &'a str,
+}
+
+#[test]
+fn test_highlight() {
+    let t = HighlightTemplate {
+        title: "Rust <Tips> for rust users",
+        query: "RUST",
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "<li><mark>Rust</mark> &lt;Tips&gt; for <mark>rust</mark> users</li>\
+         <li>Rust <mark>&lt;Tips&gt;</mark> for rust users</li>"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% for email in emails %}\
+              {% if email|matches(\"^[^@]+@[^@]+$\") %}{{ email }}{% else %}({{ email }}){% endif %} \
+              {% endfor %}{{ code|matches(\"^\\\\d{4}$\") }} {{ code|matches(pattern) }}",
+    ext = "txt"
+)]
+struct MatchesTemplate<'a> {
+    emails: &'a [&'a str],
+    code: &'a str,
+    pattern: &'a str,
+}
+
+#[test]
+fn test_matches() {
+    let t = MatchesTemplate {
+        emails: &["ann@example.com", "bo", "a@b@c"],
+        code: "0150",
+        pattern: "^0",
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "ann@example.com (bo) (a@b@c) true true"
+    );
+
+    let t = MatchesTemplate { pattern: "(", ..t };
+    assert!(matches!(
+        t.render(),
+        Err(askama::Error::Render {
+            filter: "matches",
+            ..
+        })
+    ));
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ article|wordcount }} words, {{ article|wordcount * 60 / 200 }} s|{{ empty|wordcount }}",
+    ext = "txt"
+)]
+struct WordcountTemplate<'a> {
+    article: &'a str,
+    empty: &'a str,
+}
+
+#[test]
+fn test_wordcount() {
+    let t = WordcountTemplate {
+        article: "  one two\tthree\n\nfour  five  ",
+        empty: " \n ",
+    };
+    assert_eq!(t.render().unwrap(), "5 words, 1 s|0");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ s|replace(\"-\", sep) }}|{{ s|replace(\"-\", \"\", 1) }}|{{ s|replace(\"b\", \"<b>\")|upper }}",
+    ext = "html"
+)]
+struct ReplaceTemplate<'a> {
+    s: &'a str,
+    sep: String,
+}
+
+#[test]
+fn test_replace() {
+    let t = ReplaceTemplate {
+        s: "a-b-c",
+        sep: " & ".into(),
+    };
+    assert_eq!(t.render().unwrap(), "a &amp; b &amp; c|ab-c|A-&lt;B&gt;-C");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ body|striptags|truncate(14) }}|{{ body|striptags|wordcount }}",
+    ext = "html"
+)]
+struct StriptagsTemplate<'a> {
+    body: &'a str,
+}
+
+#[test]
+fn test_striptags() {
+    let t = StriptagsTemplate {
+        body: "<h1>Tom &amp; Jerry</h1>\n<p>A <em>cat</em> &lt;and&gt; a mouse",
+    };
+    assert_eq!(t.render().unwrap(), "Tom &amp; Jerry A …|8");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ text|truncatewords(3) }}|{{ text|truncatewords(2, more) }}|{{ text|truncatewords(9) }}",
+    ext = "html"
+)]
+struct TruncatewordsTemplate<'a> {
+    text: &'a str,
+    more: &'a str,
+}
+
+#[test]
+fn test_truncatewords() {
+    let t = TruncatewordsTemplate {
+        text: " Fish  &\nchips  on Friday ",
+        more: " <more>",
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "Fish &amp; chips…|Fish &amp; &lt;more&gt;|Fish &amp; chips on Friday"
+    );
+}
+
+struct Purchase {
+    name: &'static str,
+    price: u32,
+    weight: f64,
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ purchases|map(\"price\")|sum }} {{ purchases|map(\"price\")|sum(100) }} \
+              {{ purchases|map(\"weight\")|sum }} {{ empty|sum }} \
+              {% if let Some(price) = purchases|map(\"price\")|max %}{{ price }}{% endif %} \
+              {% if let Some(name) = purchases|sort(\"price\")|map(\"name\")|first %}{{ name }}{% endif %} \
+              {% match empty|min %}{% when Some with (n) %}{{ n }}{% when None %}none{% endmatch %}",
+    ext = "txt"
+)]
+struct SumMaxMinTemplate<'a> {
+    purchases: &'a [Purchase],
+    empty: Vec<u8>,
+}
+
+#[test]
+fn test_sum_max_min() {
+    let t = SumMaxMinTemplate {
+        purchases: &[
+            Purchase {
+                name: "tea",
+                price: 3,
+                weight: 0.25,
+            },
+            Purchase {
+                name: "cake",
+                price: 12,
+                weight: 1.5,
+            },
+            Purchase {
+                name: "milk",
+                price: 2,
+                weight: 1.0,
+            },
+        ],
+        empty: vec![],
+    };
+    assert_eq!(t.render().unwrap(), "17 117 2.75 0 12 milk none");
 }
@@ -0,0 +1,134 @@
+use std::fmt::Display;
+
+use askama::Template;
+
+#[derive(Template)]
+#[template(
+    source = "{% for item in items %}{{ item }}{% if !loop.last %}, {% endif %}{% endfor %}",
+    ext = "txt"
+)]
+struct ListTemplate<'a, T: Display> {
+    items: &'a [T],
+}
+
+#[test]
+fn test_lifetime_and_bound() {
+    let t = ListTemplate { items: &[1, 2, 3] };
+    assert_eq!(t.render().unwrap(), "1, 2, 3");
+    assert_eq!(t.to_string(), "1, 2, 3");
+}
+
+#[derive(Template)]
+#[template(source = "{{ name }}: {{ inner.render().unwrap() }}", ext = "txt")]
+struct NestedLifetimesTemplate<'a, 'b: 'a, T: ?Sized + Display + 'b> {
+    name: &'b T,
+    inner: &'a ListTemplate<'b, u8>,
+}
+
+#[test]
+fn test_lifetime_bounds() {
+    let list = ListTemplate { items: &[4, 5] };
+    let t = NestedLifetimesTemplate {
+        name: "list",
+        inner: &list,
+    };
+    assert_eq!(t.render().unwrap(), "list: 4, 5");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% for row in rows %}{{ row|join(\"\") }}|{% endfor %}{{ N }}x{{ M }}",
+    ext = "txt"
+)]
+struct GridTemplate<const N: usize, const M: usize = 2> {
+    rows: [[u8; M]; N],
+}
+
+#[test]
+fn test_const_generics() {
+    let t: GridTemplate<3> = GridTemplate {
+        rows: [[1, 2], [3, 4], [5, 6]],
+    };
+    assert_eq!(t.render().unwrap(), "12|34|56|3x2");
+
+    let t = GridTemplate::<1, 3> { rows: [[7, 8, 9]] };
+    assert_eq!(t.render().unwrap(), "789|1x3");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% let copy = value.clone() %}{{ copy }}{{ value }}\
+              {% for s in strs %}[{% include \"included.html\" %}]{% endfor %}",
+    ext = "html"
+)]
+struct WhereTemplate<'a, T, S = &'static str>
+where
+    T: Display + Clone,
+    S: AsRef<str> + Display,
+    T: 'a,
+{
+    value: &'a T,
+    strs: Vec<S>,
+}
+
+#[test]
+fn test_where_clause() {
+    let t = WhereTemplate {
+        value: &'a',
+        strs: vec!["b", "c"],
+    };
+    assert_eq!(t.render().unwrap(), "aa[INCLUDED: b][INCLUDED: c]");
+    assert_eq!(t.mime_type(), "text/html; charset=utf-8");
+    assert_eq!(
+        <WhereTemplate<char> as askama::SizedTemplate>::extension(),
+        Some("html")
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% extends \"base.html\" %}{% block content %}{{ body }}{% endblock %}",
+    ext = "html"
+)]
+struct GenericChildTemplate<'a, T: Display, B>
+where
+    B: Display,
+{
+    title: &'a T,
+    body: B,
+}
+
+#[test]
+fn test_generic_child() {
+    let t = GenericChildTemplate {
+        title: &7,
+        body: "child",
+    };
+    assert_eq!(t.render().unwrap(), "7\nchild\nFoo\nCopyright 2017");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% macro show(x) %}<{{ x }}>{% endmacro %}\
+              {% for x in items %}{% call show(x) %}{% endfor %}\
+              {% if let Some(x) = extra %}{% call show(x) %}{% endif %}",
+    ext = "txt"
+)]
+struct MacroTemplate<'a, T, U>
+where
+    T: Display,
+    U: Display + Copy,
+    Option<U>: Default,
+{
+    items: &'a [T],
+    extra: Option<U>,
+}
+
+#[test]
+fn test_macro_with_generics() {
+    let t = MacroTemplate {
+        items: &["a", "b"],
+        extra: Some(1),
+    };
+    assert_eq!(t.render().unwrap(), "<a><b><1>");
+}
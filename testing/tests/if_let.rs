@@ -107,3 +107,45 @@ fn test_if_let_else() {
     };
     assert_eq!(s.render().unwrap(), "fail");
 }
+
+struct User {
+    name: &'static str,
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% if let Some(user) = user %}Hi {{ user.name }}\
+              {%- else if let Some(name) = guest %}Hi guest {{ name }}\
+              {%- else %}Hi {{ name }}{% endif %}",
+    ext = "txt"
+)]
+struct IfLetOption {
+    user: Option<User>,
+    guest: Option<&'static str>,
+    name: &'static str,
+}
+
+#[test]
+fn test_if_let_option() {
+    let t = IfLetOption {
+        user: Some(User { name: "alice" }),
+        guest: Some("bob"),
+        name: "you",
+    };
+    assert_eq!(t.render().unwrap(), "Hi alice");
+
+    let t = IfLetOption {
+        user: None,
+        guest: Some("bob"),
+        name: "you",
+    };
+    assert_eq!(t.render().unwrap(), "Hi guest bob");
+
+    // `name` is only bound in its branch, so the field is used in `else`.
+    let t = IfLetOption {
+        user: None,
+        guest: None,
+        name: "you",
+    };
+    assert_eq!(t.render().unwrap(), "Hi you");
+}
@@ -12,3 +12,182 @@ fn test_include() {
     let s = IncludeTemplate { strs: &strs };
     assert_eq!(s.render().unwrap(), "\n  INCLUDED: foo\n  INCLUDED: bar")
 }
+
+#[derive(Template)]
+#[template(
+    source = "{% include \"included.html\" %}|\
+              {% for s in strs %}{% include \"included.html\" %}|{% endfor %}\
+              {%- include \"included.html\" %}",
+    ext = "html",
+    include_mode = "function"
+)]
+struct IncludeFunctionTemplate<'a> {
+    s: &'a str,
+    strs: Vec<&'a str>,
+}
+
+#[test]
+fn test_include_function() {
+    let t = IncludeFunctionTemplate {
+        s: "<top>",
+        strs: vec!["a", "b"],
+    };
+    // Inside the loop, `s` is the loop variable, so the include is inlined.
+    assert_eq!(
+        t.render().unwrap(),
+        "INCLUDED: &lt;top&gt;|INCLUDED: a|INCLUDED: b|INCLUDED: &lt;top&gt;"
+    );
+    assert_eq!(
+        <IncludeFunctionTemplate as askama::SizedTemplate>::TEMPLATE_FIELDS,
+        ["s", "strs"]
+    );
+}
+
+#[derive(Template)]
+#[template(path = "greeting.html")]
+struct GreetingTemplate<'a> {
+    name: &'a str,
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% for g in greetings %}{% include \"greeting.html\" with g %} {% endfor %}\
+              {%- include \"greeting.html\" with self.you() -%}",
+    ext = "html"
+)]
+struct IncludeWithTemplate<'a> {
+    greetings: Vec<GreetingTemplate<'a>>,
+}
+
+impl IncludeWithTemplate<'_> {
+    fn you(&self) -> GreetingTemplate<'static> {
+        GreetingTemplate { name: "<you>" }
+    }
+}
+
+#[test]
+fn test_include_with() {
+    let t = IncludeWithTemplate {
+        greetings: vec![
+            GreetingTemplate { name: "foo" },
+            GreetingTemplate { name: "bar" },
+        ],
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "Hello, foo! Hello, bar! Hello, &lt;you&gt;!"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% extends \"card-base.html\" %}\
+              {% block body %}{% include \"card-item.html\" %}{% endblock %}",
+    name = "card.html",
+    partial = (
+        "card-base.html",
+        "<div>{% block body %}{% endblock %}</div>{% include \"included.html\" %}"
+    ),
+    partial = ("card-item.html", "<b>{{ s }}</b>")
+)]
+struct InlinePartialsTemplate<'a> {
+    s: &'a str,
+}
+
+#[test]
+fn test_inline_partials() {
+    let t = InlinePartialsTemplate { s: "<x>" };
+    assert_eq!(
+        t.render().unwrap(),
+        "<div><b>&lt;x&gt;</b></div>INCLUDED: &lt;x&gt;"
+    );
+    assert_eq!(t.extension(), Some("html"));
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% include \"theme/included.html\" or \"included.html\" %}|\
+              {% include \"included.html\" or \"greeting.html\" %}",
+    ext = "html"
+)]
+struct IncludeFallbackTemplate<'a> {
+    s: &'a str,
+}
+
+#[test]
+fn test_include_fallback() {
+    let t = IncludeFallbackTemplate { s: "x" };
+    assert_eq!(t.render().unwrap(), "INCLUDED: x|INCLUDED: x");
+}
+
+struct Address {
+    city: &'static str,
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ address.city }} {{ address.city|upper }}\n\
+              {% for s in strs %}{{ s }}{% endfor %}\n\
+              {% include \"greeting.html\" %} {{ self.strs.len() }}",
+    ext = "txt"
+)]
+struct FieldsTemplate<'a> {
+    address: Address,
+    name: &'a str,
+    strs: &'a [&'a str],
+}
+
+#[test]
+fn test_template_fields() {
+    use askama::SizedTemplate;
+
+    let t = FieldsTemplate {
+        address: Address { city: "Oslo" },
+        name: "you",
+        strs: &["a", "b"],
+    };
+    assert_eq!(t.render().unwrap(), "Oslo OSLO\nab\nHello, you! 2");
+    assert_eq!(
+        <FieldsTemplate as SizedTemplate>::TEMPLATE_FIELDS,
+        ["address.city", "name", "strs"]
+    );
+}
+
+// The partial is escaped with the escaper of the including template, which
+// `escape_as_parent` acknowledges instead of warning about it.
+#[derive(Template)]
+#[template(
+    source = "{% include \"greeting.html\" escape_as_parent %}",
+    ext = "txt"
+)]
+struct EscapeAsParentTemplate<'a> {
+    name: &'a str,
+}
+
+#[test]
+fn test_include_escape_as_parent() {
+    let t = EscapeAsParentTemplate { name: "<you>" };
+    assert_eq!(t.render().unwrap(), "Hello, <you>!");
+}
+
+#[derive(Template)]
+#[template(
+    source = "<span>{% include_str \"icon.svg\" %}</span> {{ name }}",
+    ext = "html"
+)]
+struct IncludeStrTemplate<'a> {
+    name: &'a str,
+}
+
+#[test]
+fn test_include_str() {
+    let t = IncludeStrTemplate { name: "<b>" };
+    assert_eq!(
+        t.render().unwrap(),
+        "<span><svg viewBox=\"0 0 1 1\"><path d=\"{{ not a template }}\"/></svg>\n</span> &lt;b&gt;"
+    );
+    assert!(
+        <IncludeStrTemplate<'_> as askama::SizedTemplate>::size_hint()
+            > include_str!("../templates/icon.svg").len()
+    );
+}
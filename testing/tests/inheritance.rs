@@ -29,6 +29,14 @@ fn test_simple_extends() {
     );
 }
 
+#[test]
+fn test_template_fields() {
+    use askama::SizedTemplate;
+
+    assert_eq!(<BaseTemplate as SizedTemplate>::TEMPLATE_FIELDS, ["title"]);
+    assert_eq!(<ChildTemplate as SizedTemplate>::TEMPLATE_FIELDS, ["title"]);
+}
+
 #[derive(Template)]
 #[template(source = "{% extends \"base.html\" %}", ext = "html")]
 struct EmptyChild<'a> {
@@ -304,3 +312,125 @@ fn test_let_block() {
     let t = LetChild {};
     assert_eq!(t.render().unwrap(), "1");
 }
+
+#[derive(Template)]
+#[template(path = "multi-layout.html")]
+struct MultiLayoutTemplate;
+
+#[derive(Template)]
+#[template(path = "multi-page.html")]
+struct MultiPageTemplate;
+
+#[test]
+fn test_multi_level_blocks() {
+    assert_eq!(
+        MultiLayoutTemplate.render().unwrap(),
+        "<title>Layout - Base</title>\n\
+         <nav>layout nav</nav>\n\
+         <main>layout content</main>\n\
+         base footer"
+    );
+    assert_eq!(
+        MultiPageTemplate.render().unwrap(),
+        "<title>Page - Layout - Base</title>\n\
+         <nav>layout nav</nav>\n\
+         <main>page content, not layout content</main>\n\
+         page footer after base footer"
+    );
+}
+
+#[derive(Template)]
+#[template(path = "multi-page.html", block = "main")]
+struct MultiPageMainBlock;
+
+#[derive(Template)]
+#[template(path = "multi-page.html", block = "title")]
+struct MultiPageTitleBlock;
+
+#[derive(Template)]
+#[template(path = "child.html", block = "content")]
+struct ChildContentBlock<'a> {
+    title: &'a str,
+}
+
+#[test]
+fn test_render_block() {
+    assert_eq!(
+        MultiPageMainBlock.render().unwrap(),
+        "<nav>layout nav</nav>\n<main>page content, not layout content</main>"
+    );
+    assert_eq!(
+        MultiPageTitleBlock.render().unwrap(),
+        "Page - Layout - Base"
+    );
+    let t = ChildContentBlock { title: "<a>" };
+    assert_eq!(t.render().unwrap(), "(&lt;a&gt;) Content goes here");
+}
+
+#[derive(Template)]
+#[template(
+    source = r#"{% extends "multi-layout.html" %}
+{%- block nav %}{{ title }} &gt; {{ super() }}{% endblock %}
+{%- block content %}{{- super() -}}!{% endblock %}"#,
+    ext = "html"
+)]
+struct SuperExprTemplate<'a> {
+    title: &'a str,
+}
+
+#[test]
+fn test_super_expr() {
+    let t = SuperExprTemplate { title: "<Home>" };
+    assert_eq!(
+        t.render().unwrap(),
+        "<title>Layout - Base</title>\n\
+         <nav>&lt;Home&gt; &gt; layout nav</nav>\n\
+         <main>layout content!</main>\n\
+         base footer"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% extends \"mobile-base.html\" if mobile else \"base.html\" %}\
+              {% block content %}({{ title }}) Content goes here{% endblock %}",
+    ext = "html"
+)]
+struct ConditionalExtends<'a> {
+    title: &'a str,
+    mobile: bool,
+}
+
+#[test]
+fn test_conditional_extends() {
+    let t = ConditionalExtends {
+        title: "Bar",
+        mobile: true,
+    };
+    assert_eq!(t.render().unwrap(), "[mobile] Bar\n(Bar) Content goes here");
+    let t = ConditionalExtends {
+        title: "Bar",
+        mobile: false,
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "Bar\n(Bar) Content goes here\nFoo\nCopyright 2017"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% extends \"required-base.html\" %}\
+              {% block title %}{{ title }}{% endblock %}\
+              {% block content %}[{% call super() %}]{% endblock %}",
+    ext = "html"
+)]
+struct RequiredBlocks<'a> {
+    title: &'a str,
+}
+
+#[test]
+fn test_required_blocks() {
+    let t = RequiredBlocks { title: "Foo" };
+    assert_eq!(t.render().unwrap(), "<title>Foo</title>\n[]");
+}
@@ -119,3 +119,31 @@ fn test_let_destruct_with_path_and_with_keyword() {
     };
     assert_eq!(t.render().unwrap(), "hello");
 }
+
+#[derive(Template)]
+#[template(source = "{% let [a, b, c] = v %}{{a}}{{b}}{{c}}", ext = "txt")]
+struct LetDestructoringArray {
+    v: [i32; 3],
+}
+
+#[test]
+fn test_let_destruct_array() {
+    let t = LetDestructoringArray { v: [1, 2, 3] };
+    assert_eq!(t.render().unwrap(), "123");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% let [first, (x, y)] = v %}{{first.0}}{{first.1}}:{{x}},{{y}}",
+    ext = "txt"
+)]
+struct LetDestructoringNestedArray<'a> {
+    v: &'a [(&'a str, i32); 2],
+}
+
+#[test]
+fn test_let_destruct_nested_array() {
+    let v = [("a", 1), ("b", 2)];
+    let t = LetDestructoringNestedArray { v: &v };
+    assert_eq!(t.render().unwrap(), "a1:b,2");
+}
@@ -0,0 +1,99 @@
+//! Generated code must not trip the lints enabled by the deriving crate.
+#![deny(
+    warnings,
+    missing_docs,
+    clippy::all,
+    clippy::pedantic,
+    clippy::nursery,
+    clippy::arithmetic_side_effects,
+    clippy::float_arithmetic,
+    clippy::integer_division,
+    clippy::integer_division_remainder_used,
+    clippy::missing_asserts_for_indexing,
+    clippy::modulo_arithmetic
+)]
+
+use askama::Template;
+
+#[derive(Template)]
+#[template(
+    source = "{% let unused = 1 %}{% for (i, x) in items.iter().enumerate() %}{{ x }}{% endfor %}",
+    ext = "txt"
+)]
+struct UnusedTemplate<'a> {
+    items: &'a [u32],
+}
+
+#[test]
+fn test_unused() {
+    let t = UnusedTemplate { items: &[1, 2] };
+    assert_eq!(t.render().unwrap(), "12");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% for x in items %}{% if true %}{% break %}{% endif %}{{ x }}{% endfor %}\
+              {% match opt %}{% when Some with (v) %}some{% when None %}none{% endmatch %}\
+              {{ ((a + 1)) * (2) }}{% if let n = a %}{% endif %}",
+    ext = "txt"
+)]
+struct MiscTemplate<'a> {
+    items: &'a [u32],
+    opt: Option<u32>,
+    a: u32,
+}
+
+#[test]
+fn test_misc() {
+    let t = MiscTemplate {
+        items: &[1],
+        opt: Some(1),
+        a: 1,
+    };
+    assert_eq!(t.render().unwrap(), "some4");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% macro m(unused) %}m{% endmacro %}{% call m(1) %}\
+              {% for x in items %}{{ loop.index }}{% else %}empty{% endfor %}",
+    ext = "txt"
+)]
+struct MacroTemplate<'a> {
+    items: &'a [u32],
+}
+
+#[test]
+fn test_macro() {
+    let t = MacroTemplate { items: &[] };
+    assert_eq!(t.render().unwrap(), "mempty");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ a / 2 }} {{ a % 2 }} {{ f * 2.0 }} {{ items[0] + items[1] }}",
+    ext = "txt"
+)]
+struct ArithmeticTemplate<'a> {
+    a: u32,
+    f: f64,
+    items: &'a [u32],
+}
+
+#[test]
+fn test_arithmetic() {
+    let t = ArithmeticTemplate {
+        a: 5,
+        f: 0.5,
+        items: &[1, 2],
+    };
+    assert_eq!(t.render().unwrap(), "2 1 1 3");
+}
+
+/// Only rendered asynchronously, through a public `render_async()`, which
+/// compiling this test already checks.
+#[derive(Template)]
+#[template(source = "{{ s }}", ext = "txt", async = true)]
+pub struct AsyncTemplate<'a> {
+    s: &'a str,
+}
@@ -73,6 +73,34 @@ fn test_for_range() {
     );
 }
 
+struct Page {
+    first: u64,
+    last: u64,
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% for i in page.first..=page.last %}{{ loop.index }}/{{ loop.length }}:{{ i }} {% endfor %}
+{%- for i in 0..page.last - page.first %}{{ i }}{% endfor %}",
+    ext = "txt"
+)]
+struct ForRangeFieldsTemplate {
+    page: Page,
+}
+
+#[test]
+fn test_for_range_fields() {
+    let t = ForRangeFieldsTemplate {
+        page: Page { first: 7, last: 9 },
+    };
+    assert_eq!(t.render().unwrap(), "1/3:7 2/3:8 3/3:9 01");
+
+    let t = ForRangeFieldsTemplate {
+        page: Page { first: 9, last: 9 },
+    };
+    assert_eq!(t.render().unwrap(), "1/1:9 ");
+}
+
 #[derive(Template)]
 #[template(source = "{% for i in [1, 2, 3] %}{{ i }}{% endfor %}", ext = "txt")]
 struct ForArrayTemplate;
@@ -435,3 +463,152 @@ fn test_for_in_if() {
     let t = ForInIf { limit: 1 };
     assert_eq!(t.render().unwrap(), ":(");
 }
+
+#[derive(Template)]
+#[template(
+    source = "{% for v in values if v.is_positive() %}{{ loop.index }}/{{ loop.length }}{% if !loop.last %},{% endif %}{% endfor %}",
+    ext = "txt"
+)]
+struct ForLength<'a> {
+    values: &'a [i32],
+}
+
+#[test]
+fn test_for_length() {
+    let t = ForLength {
+        values: &[-1, 2, 3, 4],
+    };
+    assert_eq!(t.render().unwrap(), "1/3,2/3,3/3");
+
+    let t = ForLength { values: &[] };
+    assert_eq!(t.render().unwrap(), "");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% for x in outer %}{{ loop.length }}:{% for y in x %}{{ loop.index0 }}{% endfor %};{% endfor %}",
+    ext = "txt"
+)]
+struct ForNestedLength<'a> {
+    outer: &'a [&'a [i32]],
+}
+
+#[test]
+fn test_for_nested_length() {
+    let t = ForNestedLength {
+        outer: &[&[1, 2], &[3]],
+    };
+    assert_eq!(t.render().unwrap(), "2:01;2:0;");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% for [k, v] in pairs %}{{ k }}={{ v }};{% endfor %}",
+    ext = "txt"
+)]
+struct ForArrayDestructuringTemplate {
+    pairs: Vec<[&'static str; 2]>,
+}
+
+#[test]
+fn test_for_array_destructuring() {
+    let t = ForArrayDestructuringTemplate {
+        pairs: vec![["a", "1"], ["b", "2"]],
+    };
+    assert_eq!(t.render().unwrap(), "a=1;b=2;");
+}
+
+#[derive(Template)]
+#[template(
+    source = "<table>{% for row in rows %}<tr><td>{{ row }}</td></tr>{% endfor %}</table>",
+    ext = "html"
+)]
+struct ForSizeHintTemplate {
+    rows: Vec<&'static str>,
+}
+
+#[test]
+fn test_for_size_hint() {
+    let t = ForSizeHintTemplate {
+        rows: vec!["abc"; 1000],
+    };
+    let rendered = t.render().unwrap();
+    assert_eq!(rendered.len(), 15 + 1000 * 21);
+    assert_eq!(t.size_hint(), rendered.len());
+
+    // Without a value to look at, the configured loop factor is used.
+    assert_eq!(
+        <ForSizeHintTemplate as askama::SizedTemplate>::size_hint(),
+        15 + 2 * 21
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% for v in values if v.is_positive() %}{{ loop.index }}:{{ v }}\
+              {% if loop.last %}.{% endif %}{% if loop.index == 3 %}{% break %}{% endif %} \
+              {% else %}none{% endfor %}",
+    ext = "txt"
+)]
+struct ForElseBreakTemplate<'a> {
+    values: &'a [i32],
+}
+
+#[test]
+fn test_for_else_break() {
+    let t = ForElseBreakTemplate {
+        values: &[1, 2, 3, 4],
+    };
+    assert_eq!(t.render().unwrap(), "1:1 2:2 3:3");
+
+    let t = ForElseBreakTemplate { values: &[-1, 2] };
+    assert_eq!(t.render().unwrap(), "1:2. ");
+
+    let t = ForElseBreakTemplate { values: &[-1, -2] };
+    assert_eq!(t.render().unwrap(), "none");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% for (code, name) in countries %}{{ code }}={{ name }}{% if !loop.last %}, {% endif %}{% endfor %}\n\
+              {% for (team, members) in teams %}{{ team }}:\
+              {% for (name, roles) in members %} {{ name }}({{ roles.len() }}: {{ roles|join(\"/\") }}){% endfor %};\
+              {% endfor %}\n\
+              {% for (code, _) in countries|dictsort(\"key\", true) %}{{ code }}={{ countries[code]|upper }} {% endfor %}",
+    ext = "txt"
+)]
+struct ForMapTemplate<'a> {
+    countries: std::collections::HashMap<&'a str, &'a str>,
+    teams:
+        &'a std::collections::BTreeMap<&'a str, std::collections::BTreeMap<String, Vec<&'a str>>>,
+}
+
+#[test]
+fn test_for_map() {
+    use std::collections::{BTreeMap, HashMap};
+
+    let mut countries = HashMap::new();
+    countries.insert("no", "Norway");
+    let mut teams = BTreeMap::new();
+    let mut core = BTreeMap::new();
+    core.insert("bo".to_string(), vec!["dev"]);
+    core.insert("ann".to_string(), vec!["dev", "lead"]);
+    teams.insert("core", core);
+    teams.insert("docs", BTreeMap::new());
+
+    let t = ForMapTemplate {
+        countries,
+        teams: &teams,
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "no=Norway\ncore: ann(2: dev/lead) bo(1: dev);docs:;\nno=NORWAY "
+    );
+
+    let mut t = t;
+    t.countries.insert("se", "Sweden");
+    assert_eq!(
+        t.render().unwrap().lines().last(),
+        Some("se=SWEDEN no=NORWAY ")
+    );
+}
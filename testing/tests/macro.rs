@@ -73,3 +73,21 @@ fn str_cmp() {
     let t = StrCmpTemplate;
     assert_eq!(t.render().unwrap(), "AfooBotherCneitherD");
 }
+
+#[derive(Template)]
+#[template(path = "macro-default-args.html")]
+struct MacroDefaultArgsTemplate<'a> {
+    label: &'a str,
+}
+
+#[test]
+fn test_macro_default_args() {
+    let t = MacroDefaultArgsTemplate { label: "<Back>" };
+    assert_eq!(
+        t.render().unwrap(),
+        "<button class=\"primary\" title=\"Save\">Save</button>\n\
+         <button class=\"danger\" title=\"Delete\">Delete</button>\n\
+         <button class=\"primary\" title=\"Go on\">Go</button>\n\
+         <button class=\"link\" title=\"&lt;Back&gt;\">&lt;Back&gt;</button>"
+    );
+}
@@ -172,3 +172,91 @@ fn test_match_option_result_option() {
     };
     assert_eq!(s.render().unwrap(), "num=4711");
 }
+
+#[allow(dead_code)]
+enum Shape {
+    Circle { radius: u32 },
+    Rect { width: u32, height: u32 },
+    Tri(u32, u32, u32),
+}
+
+#[derive(Template)]
+#[template(path = "match-patterns.html")]
+struct MatchPatternsTemplate {
+    shape: Shape,
+}
+
+#[test]
+fn test_match_patterns() {
+    let s = MatchPatternsTemplate {
+        shape: Shape::Circle { radius: 12 },
+    };
+    assert_eq!(s.render().unwrap(), "big circle 12");
+    let s = MatchPatternsTemplate {
+        shape: Shape::Circle { radius: 3 },
+    };
+    assert_eq!(s.render().unwrap(), "circle 3");
+    let s = MatchPatternsTemplate {
+        shape: Shape::Rect {
+            width: 4,
+            height: 5,
+        },
+    };
+    assert_eq!(s.render().unwrap(), "rect 4");
+    let s = MatchPatternsTemplate {
+        shape: Shape::Tri(6, 7, 8),
+    };
+    assert_eq!(s.render().unwrap(), "tri 6");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% match n -%}
+              {% when 0 -%} zero
+              {%- when 1 | 2 | 3 -%} small
+              {%- when m @ (4 | 5) -%} {{ m }} is four or five
+              {%- when m if *m % 2 == 0 -%} {{ m }} is even
+              {%- else -%} odd
+              {%- endmatch %}",
+    ext = "txt"
+)]
+struct MatchOrBindingGuardTemplate {
+    n: u32,
+}
+
+#[test]
+fn test_match_or_binding_guard() {
+    let render = |n| MatchOrBindingGuardTemplate { n }.render().unwrap();
+    assert_eq!(render(0), "zero");
+    assert_eq!(render(2), "small");
+    assert_eq!(render(5), "5 is four or five");
+    assert_eq!(render(8), "8 is even");
+    assert_eq!(render(9), "odd");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% match n -%}
+              {% when ..=0 -%} none
+              {%- when m @ 1..=9 -%} {{ m }} is a digit
+              {%- when 10..100 -%} two digits
+              {%- when _ -%} many
+              {%- endmatch %} {% match c -%}
+              {% when 'a'..='z' | 'A'..='Z' -%} letter
+              {%- else -%} other
+              {%- endmatch %}",
+    ext = "txt"
+)]
+struct MatchRangeTemplate {
+    n: i32,
+    c: char,
+}
+
+#[test]
+fn test_match_range() {
+    let render = |n, c| MatchRangeTemplate { n, c }.render().unwrap();
+    assert_eq!(render(-3, 'a'), "none letter");
+    assert_eq!(render(7, 'Q'), "7 is a digit letter");
+    assert_eq!(render(99, '1'), "two digits other");
+    assert_eq!(render(100, '_'), "many other");
+}
@@ -53,3 +53,111 @@ fn test_nested() {
     };
     assert_eq!(t.render().unwrap(), "bar foo");
 }
+
+struct Item {
+    name: &'static str,
+    active: bool,
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ items.iter().filter(|x| x.active).count()|fmt(\"{:02}\") }} \
+              {{ items.iter().map(|item| item.name).collect::<Vec<_>>().join(\",\") }} \
+              {% for (i, item) in items.iter().enumerate().filter(|(i, _)| i % 2 == 0) %}\
+              {{ i }}={{ item.name }} {% endfor %}\
+              {{ \"12\".parse::<u32>().unwrap() + offset }} \
+              {{ missing.unwrap_or_else(|| offset) }}",
+    ext = "txt"
+)]
+struct ClosureTemplate<'a> {
+    items: &'a [Item],
+    offset: u32,
+    missing: Option<u32>,
+}
+
+#[test]
+fn test_closure() {
+    let t = ClosureTemplate {
+        items: &[
+            Item {
+                name: "a",
+                active: true,
+            },
+            Item {
+                name: "b",
+                active: false,
+            },
+            Item {
+                name: "c",
+                active: true,
+            },
+        ],
+        offset: 1,
+        missing: None,
+    };
+    assert_eq!(t.render().unwrap(), "02 a,b,c 0=a 2=c 13 1");
+}
+
+#[derive(Debug)]
+struct NotFound(u32);
+
+impl From<NotFound> for askama::Error {
+    fn from(err: NotFound) -> Self {
+        askama::Error::custom(format!("no user with id {}", err.0))
+    }
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ self.lookup(id)?.name }} {{ self.parse(s)? + 1 }}",
+    ext = "txt"
+)]
+struct TryTemplate<'a> {
+    users: &'a [Item],
+    id: u32,
+    s: &'a str,
+}
+
+impl TryTemplate<'_> {
+    fn lookup(&self, id: &u32) -> Result<&Item, NotFound> {
+        self.users.get(*id as usize).ok_or(NotFound(*id))
+    }
+
+    fn parse(&self, s: &str) -> askama::Result<u32> {
+        s.parse().map_err(askama::Error::custom)
+    }
+}
+
+#[test]
+fn test_try() {
+    let users = [Item {
+        name: "foo",
+        active: true,
+    }];
+    let t = TryTemplate {
+        users: &users,
+        id: 0,
+        s: "41",
+    };
+    assert_eq!(t.render().unwrap(), "foo 42");
+
+    let t = TryTemplate {
+        users: &users,
+        id: 1,
+        s: "41",
+    };
+    assert_eq!(
+        t.render().unwrap_err().to_string(),
+        "custom error: no user with id 1"
+    );
+
+    let t = TryTemplate {
+        users: &users,
+        id: 0,
+        s: "x",
+    };
+    assert_eq!(
+        t.render().unwrap_err().to_string(),
+        "custom error: invalid digit found in string"
+    );
+}
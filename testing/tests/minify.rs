@@ -0,0 +1,70 @@
+#![cfg(feature = "minify")]
+
+use askama::Template;
+
+#[derive(Template)]
+#[template(
+    source = "<!DOCTYPE html>\n<html>\n  <body>\n    <p class=\"greeting\">\n      Hello, {{ name }}!\n    </p>\n    <!-- comment -->\n  </body>\n</html>\n",
+    ext = "html",
+    minify = true
+)]
+struct MinifyTemplate<'a> {
+    name: &'a str,
+}
+
+#[test]
+fn test_minify() {
+    let t = MinifyTemplate { name: "<world>" };
+    assert_eq!(
+        t.render().unwrap(),
+        "<!doctype html><body><p class=greeting>Hello, &LTworld>!"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% extends \"minify-base.html\" %}{% block body %}\n  <b> {{ n }} </b>\n{% endblock %}",
+    ext = "html",
+    config = "configs/minify.toml"
+)]
+struct MinifyConfigTemplate {
+    n: u32,
+}
+
+#[derive(Template)]
+#[template(
+    source = "<p>\n  {{ n }}\n</p>",
+    ext = "txt",
+    config = "configs/minify.toml"
+)]
+struct MinifyConfigTextTemplate {
+    n: u32,
+}
+
+#[derive(Template)]
+#[template(
+    source = "<p>\n  {{ n }}\n</p>",
+    ext = "html",
+    config = "configs/minify.toml",
+    minify = false
+)]
+struct NoMinifyTemplate {
+    n: u32,
+}
+
+#[test]
+fn test_minify_config() {
+    assert_eq!(
+        MinifyConfigTemplate { n: 1 }.render().unwrap(),
+        "<div><b> 1 </b></div>"
+    );
+    // The configuration only applies to HTML templates.
+    assert_eq!(
+        MinifyConfigTextTemplate { n: 1 }.render().unwrap(),
+        "<p>\n  1\n</p>"
+    );
+    assert_eq!(
+        NoMinifyTemplate { n: 1 }.render().unwrap(),
+        "<p>\n  1\n</p>"
+    );
+}
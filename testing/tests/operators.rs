@@ -63,3 +63,53 @@ fn test_short_circuit() {
     let t = ShortCircuitTemplate {};
     assert_eq!(t.render().unwrap(), "truetrue");
 }
+
+#[derive(Template)]
+#[template(
+    source = "{{ if cond { a } else { b } }} {{ (if !cond { a } else { b })|upper }} \
+              {{ if n > 10 { \"big\" } else if n > 5 { \"medium\" } else { \"small\" } }}",
+    ext = "html"
+)]
+struct IfExprTemplate {
+    cond: bool,
+    n: u32,
+    a: String,
+    b: String,
+}
+
+#[test]
+fn test_if_expr() {
+    let t = IfExprTemplate {
+        cond: true,
+        n: 7,
+        a: "<a>".into(),
+        b: "b".into(),
+    };
+    assert_eq!(t.render().unwrap(), "&lt;a&gt; B medium");
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ price * quantity + shipping }} {{ price * (quantity + shipping) }} \
+              {{ ((price + 1) * (quantity - 1)) / (shipping % 4) }} {{ -(price - quantity) % 3 }}\
+              {% let rest = quantity % shipping %} {{ rest }}\
+              {% if quantity % 2 == 1 -%} odd {%- endif %} {{ total / 2.0 }}",
+    ext = "txt"
+)]
+struct ArithmeticTemplate {
+    price: i32,
+    quantity: i32,
+    shipping: i32,
+    total: f64,
+}
+
+#[test]
+fn test_arithmetic() {
+    let t = ArithmeticTemplate {
+        price: 10,
+        quantity: 3,
+        shipping: 5,
+        total: 5.0,
+    };
+    assert_eq!(t.render().unwrap(), "35 80 22 -1 3odd 2.5");
+}
@@ -0,0 +1,29 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(path = "./relative.html")]
+struct RelativeTemplate<'a> {
+    name: &'a str,
+    origin: &'a str,
+}
+
+#[test]
+fn test_relative() {
+    let t = RelativeTemplate {
+        name: "world",
+        origin: "tests",
+    };
+    assert_eq!(t.render().unwrap(), "Hello, world! (from tests)");
+}
+
+#[derive(Template)]
+#[template(path = "../templates/hello.html")]
+struct ParentDirTemplate<'a> {
+    name: &'a str,
+}
+
+#[test]
+fn test_parent_dir() {
+    let t = ParentDirTemplate { name: "world" };
+    assert_eq!(t.render().unwrap(), "Hello, world!");
+}
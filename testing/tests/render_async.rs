@@ -0,0 +1,57 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+use askama::Template;
+
+#[derive(Template)]
+#[template(
+    source = "{{ name }}: {% for item in self.items().await %}{{ item }}{% if !loop.last %}, {% endif %}\
+              {% endfor %} ({{ self.count().await }})",
+    ext = "html",
+    async = true
+)]
+struct AsyncTemplate<'a> {
+    name: &'a str,
+}
+
+impl AsyncTemplate<'_> {
+    async fn items(&self) -> Vec<&'static str> {
+        vec!["<a>", "b"]
+    }
+
+    async fn count(&self) -> usize {
+        self.items().await.len()
+    }
+}
+
+#[test]
+fn test_render_async() {
+    let t = AsyncTemplate { name: "list" };
+    assert_send(&t.render_async());
+    assert_eq!(
+        block_on(t.render_async()).unwrap(),
+        "list: &lt;a&gt;, b (2)"
+    );
+}
+
+fn assert_send<T: Send>(_: &T) {}
+
+// The futures above never wait on anything, so polling once is enough.
+fn block_on<F: Future>(fut: F) -> F::Output {
+    fn noop_raw_waker() -> RawWaker {
+        fn clone(_: *const ()) -> RawWaker {
+            noop_raw_waker()
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+    let mut fut = Box::pin(fut);
+    match Pin::new(&mut fut).poll(&mut Context::from_waker(&waker)) {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("future did not complete"),
+    }
+}
@@ -363,6 +363,30 @@ fn test_comment() {
     assert_eq!(t.render().unwrap(), "  ");
 }
 
+#[derive(Template)]
+#[template(source = "{#! Generated by {{ tool }} !#}\n<p>hi</p>", ext = "html")]
+struct HtmlOutputCommentTemplate;
+
+#[derive(Template)]
+#[template(
+    source = "{#!\n  Generated file.\n\n  Do not edit!\n!#}\nfn main() {}",
+    ext = "rs",
+    escape = "none"
+)]
+struct RustOutputCommentTemplate;
+
+#[test]
+fn test_output_comment() {
+    assert_eq!(
+        HtmlOutputCommentTemplate.render().unwrap(),
+        "<!-- Generated by {{ tool }} -->\n<p>hi</p>"
+    );
+    assert_eq!(
+        RustOutputCommentTemplate.render().unwrap(),
+        "// Generated file.\n//\n// Do not edit!\nfn main() {}"
+    );
+}
+
 #[derive(Template)]
 #[template(source = "{% if !foo %}Hello{% endif %}", ext = "txt")]
 struct NegationTemplate {
@@ -401,6 +425,33 @@ fn test_index() {
     assert_eq!(t.render().unwrap(), "baz");
 }
 
+#[derive(Template)]
+#[template(
+    source = "{{ matrix[1][0] }} {{ matrix[i][j + 1] }} {{ matrix[0].len() }} \
+              {{ config[\"title\"] }} {{ config[\"lang\"]|default(\"en\") }} \
+              {{ config[\"title\"]|default(\"untitled\") }} {{ matrix[i][5]|default(0) }}",
+    ext = "txt"
+)]
+struct ChainedIndexTemplate {
+    matrix: Vec<Vec<u32>>,
+    i: usize,
+    j: usize,
+    config: HashMap<String, String>,
+}
+
+#[test]
+fn test_chained_index() {
+    let mut config = HashMap::new();
+    config.insert("title".into(), "Home".into());
+    let t = ChainedIndexTemplate {
+        matrix: vec![vec![1, 2], vec![3, 4]],
+        i: 1,
+        j: 0,
+        config,
+    };
+    assert_eq!(t.render().unwrap(), "3 4 2 Home en Home 0");
+}
+
 #[derive(Template)]
 #[template(source = "foo", ext = "txt")]
 struct Empty;
@@ -443,6 +494,21 @@ fn test_raw_ws() {
     assert_eq!(template.render().unwrap(), "<{{hello}}>\n<{{bye}}>");
 }
 
+#[derive(Template)]
+#[template(
+    source = "{% raw %}{% endrawing %}{{ \"{% endraw\" }}{%- raw -%}{% endraw %}!",
+    ext = "txt"
+)]
+struct RawTemplateLookalike;
+
+#[test]
+fn test_raw_endraw_lookalike() {
+    assert_eq!(
+        RawTemplateLookalike.render().unwrap(),
+        "{% endrawing %}{{ \"{% endraw\" }}{%- raw -%}!"
+    );
+}
+
 mod without_import_on_derive {
     #[derive(askama::Template)]
     #[template(source = "foo", ext = "txt")]
@@ -464,3 +530,104 @@ fn test_define_string_var() {
     let template = DefineStringVar;
     assert_eq!(template.render().unwrap(), "");
 }
+
+#[test]
+fn test_render_into_existing_buffer() {
+    let t = EscapeTemplate { name: "<world>" };
+    let mut buf = String::with_capacity(t.size_hint() + 16);
+    buf.push_str("prefix: ");
+    t.render_into(&mut buf).unwrap();
+    assert_eq!(buf, "prefix: Hello, &lt;world&gt;!");
+}
+
+#[test]
+fn test_write_into() {
+    let t = EscapeTemplate { name: "<wörld>" };
+    let mut buf = b"prefix: ".to_vec();
+    t.write_into(&mut buf).unwrap();
+    assert_eq!(buf, "prefix: Hello, &lt;wörld&gt;!".as_bytes());
+
+    struct Closed;
+    impl std::io::Write for Closed {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    match t.write_into(&mut Closed) {
+        Err(askama::Error::Io(err)) => assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe),
+        res => panic!("expected an io error, got {:?}", res),
+    }
+}
+
+#[derive(Template)]
+#[template(
+    source = "{{ int }} {{ neg }} {{ s }} {{ owned }} {{ float }} {{ generic }} {{ 7 }}",
+    ext = "html"
+)]
+struct FastWritableTemplate<'a, T: std::fmt::Display> {
+    int: u64,
+    neg: i8,
+    s: &'a str,
+    owned: String,
+    float: f64,
+    generic: T,
+}
+
+#[test]
+fn test_fast_writable() {
+    let t = FastWritableTemplate {
+        int: 1234567890,
+        neg: i8::MIN,
+        s: "<a>",
+        owned: "b&c".into(),
+        float: 0.5,
+        generic: '>',
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "1234567890 -128 &lt;a&gt; b&amp;c 0.5 &gt; 7"
+    );
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn test_render_to_bytes() {
+    let s = VariablesTemplate {
+        strvar: "foo",
+        num: 42,
+        i18n: "Iñtërnâtiônàlizætiøn".to_string(),
+    };
+    let bytes = s.render_to_bytes().unwrap();
+    assert_eq!(bytes, s.render().unwrap().as_bytes());
+}
+
+#[derive(Debug)]
+struct Point {
+    x: i32,
+    label: &'static str,
+}
+
+#[derive(Template)]
+#[template(
+    source = "[{% debug point %}] [{%- debug point.x * 2 -%}] {{ point.label }}",
+    ext = "html"
+)]
+struct DebugTemplate {
+    point: Point,
+}
+
+#[test]
+fn test_debug() {
+    let t = DebugTemplate {
+        point: Point { x: 3, label: "<a>" },
+    };
+    let expected = if cfg!(debug_assertions) {
+        "[Point { x: 3, label: &quot;&lt;a&gt;&quot; }] [6] &lt;a&gt;"
+    } else {
+        "[] [] &lt;a&gt;"
+    };
+    assert_eq!(t.render().unwrap(), expected);
+}
@@ -0,0 +1,50 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(
+    source = "{% spaceless -%}
+<ul>
+  {% for item in items %}
+    <li>
+      <a href=\"#{{ item }}\">{{ item }}</a>
+    </li>
+  {% endfor %}
+</ul>
+{%- endspaceless %}",
+    ext = "html"
+)]
+struct NestedTemplate<'a> {
+    items: &'a [&'a str],
+}
+
+#[test]
+fn test_spaceless_nested() {
+    let t = NestedTemplate {
+        items: &["a", "<b>"],
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "<ul><li><a href=\"#a\">a</a></li><li><a href=\"#&lt;b&gt;\">&lt;b&gt;</a></li></ul>"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% spaceless %}<p>
+  Hello, <b>{{ name }}</b> !
+  <span title=\"a >  < b\"> </span>
+</p>{% endspaceless %}",
+    ext = "html"
+)]
+struct TextTemplate<'a> {
+    name: &'a str,
+}
+
+#[test]
+fn test_spaceless_text() {
+    let t = TextTemplate { name: "world" };
+    assert_eq!(
+        t.render().unwrap(),
+        "<p>\n  Hello, <b>world</b> !\n  <span title=\"a >  < b\"></span></p>"
+    );
+}
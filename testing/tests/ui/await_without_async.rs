@@ -0,0 +1,14 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(source = "{{ self.name().await }}", ext = "txt")]
+struct MyTemplate;
+
+impl MyTemplate {
+    async fn name(&self) -> &'static str {
+        "world"
+    }
+}
+
+fn main() {
+}
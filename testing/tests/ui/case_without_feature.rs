@@ -0,0 +1,10 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(source = "{{ s|snakecase }}", ext = "txt")]
+struct SnakeCase<'a> {
+    s: &'a str,
+}
+
+fn main() {
+}
@@ -0,0 +1,10 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(source = "{{ s }}", ext = "txt", config = "missing.toml")]
+struct MissingConfig<'a> {
+    s: &'a str,
+}
+
+fn main() {
+}
@@ -0,0 +1,11 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(
+    source = "{% block a %}{% endblock %}{% if true %}{% block a %}{% endblock %}{% endif %}",
+    ext = "txt"
+)]
+struct DuplicatedBlock;
+
+fn main() {
+}
@@ -0,0 +1,10 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(source = "{{ get(\"title\")|default(\"\") }}", ext = "txt", extra = "values")]
+struct MissingExtraField {
+    ctx: std::collections::HashMap<String, String>,
+}
+
+fn main() {
+}
@@ -0,0 +1,10 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(source = "{{ at|date(\"%H:%M\", zone=\"UTC\") }}", ext = "txt")]
+struct FilterNamedArgument<'a> {
+    at: &'a str,
+}
+
+fn main() {
+}
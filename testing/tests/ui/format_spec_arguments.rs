@@ -0,0 +1,16 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(source = "{{ price|format(\"{:.1$}\") }}", ext = "txt")]
+struct PrecisionArgument {
+    price: f64,
+}
+
+#[derive(Template)]
+#[template(source = "{{ price|format(\"{} {}\") }}", ext = "txt")]
+struct TwoPlaceholders {
+    price: f64,
+}
+
+fn main() {
+}
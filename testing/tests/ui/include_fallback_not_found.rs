@@ -0,0 +1,11 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(
+    source = "{% include \"theme/header.html\" or \"default/header.html\" %}",
+    ext = "html"
+)]
+struct IncludeFallbackNotFound;
+
+fn main() {
+}
@@ -0,0 +1,10 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(source = "{{ s|shout }}", ext = "txt", filters = "crate::my filters")]
+struct InvalidFiltersPath<'a> {
+    s: &'a str,
+}
+
+fn main() {
+}
@@ -0,0 +1,10 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(source = "{% include \"included.html\" %}", ext = "html", include_mode = "macro")]
+struct InvalidIncludeMode<'a> {
+    s: &'a str,
+}
+
+fn main() {
+}
@@ -0,0 +1,10 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(source = "{% if s|matches(\"[a-z\") %}{% endif %}", ext = "txt")]
+struct InvalidMatchesPattern<'a> {
+    s: &'a str,
+}
+
+fn main() {
+}
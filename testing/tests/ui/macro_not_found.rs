@@ -0,0 +1,11 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(source = "{% macro heading(s) %}<h1>{{ s }}</h1>{% endmacro %}
+{% call headnig(title) %}", ext = "html")]
+struct MacroNotFound<'a> {
+    title: &'a str,
+}
+
+fn main() {
+}
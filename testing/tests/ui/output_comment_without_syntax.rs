@@ -0,0 +1,8 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(source = "{#! Generated !#}\n{{ 1 }}", ext = "txt")]
+struct TextOutputComment;
+
+fn main() {
+}
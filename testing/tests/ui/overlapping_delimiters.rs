@@ -0,0 +1,10 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(source = "<% x %>", ext = "txt", delimiters = ("<%= %>", "<% %>", "<%# %>"))]
+struct OverlappingDelimiters {
+    x: u32,
+}
+
+fn main() {
+}
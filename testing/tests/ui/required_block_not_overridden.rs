@@ -0,0 +1,15 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(
+    source = "{% extends \"layout.html\" %}{% block title %}Title{% endblock %}",
+    name = "page.html",
+    partial = (
+        "layout.html",
+        "<title>{% block title %}{% endblock %}</title>\n{% block content required %}{% endblock %}"
+    )
+)]
+struct MissingOverride;
+
+fn main() {
+}
@@ -0,0 +1,8 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(source = "{% block title required %}Title{% endblock %}", ext = "html")]
+struct RequiredBlockWithContent;
+
+fn main() {
+}
@@ -0,0 +1,8 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(source = "{% block title %}{{ super() }}{% endblock %}", ext = "html")]
+struct SuperWithoutParent;
+
+fn main() {
+}
@@ -0,0 +1,8 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(source = "{% block title %}Title{% endblock %}", ext = "html", block = "content")]
+struct UnknownBlock;
+
+fn main() {
+}
@@ -0,0 +1,10 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(source = "{{ s|e(\"latex\") }}", ext = "html")]
+struct UnknownEscaper<'a> {
+    s: &'a str,
+}
+
+fn main() {
+}
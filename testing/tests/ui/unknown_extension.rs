@@ -0,0 +1,10 @@
+use askama::Template;
+
+#[derive(Template)]
+#[template(source = "{{ s }}", ext = "tex")]
+struct UnknownExtension<'a> {
+    s: &'a str,
+}
+
+fn main() {
+}
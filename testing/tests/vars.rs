@@ -131,3 +131,76 @@ fn test_decl_assign_range() {
     let t = DeclAssignRange;
     assert_eq!(t.render().unwrap(), "1");
 }
+
+struct User<'a> {
+    name: &'a str,
+    bio: &'a str,
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% let name = user.name|capitalize|trim %}\
+              {% let bio = user.bio|linebreaksbr %}\
+              {% let shout = user.name|trim|upper|e %}\
+              {% for row in rows %}{% for col in row %}\
+              {{ name }}{{ col }}{{ shout }}:{{ bio }};\
+              {% endfor %}{% endfor %}",
+    ext = "html"
+)]
+struct LetFilterTemplate<'a> {
+    user: User<'a>,
+    rows: Vec<Vec<u8>>,
+}
+
+#[test]
+fn test_let_filter() {
+    let t = LetFilterTemplate {
+        user: User {
+            name: "ann<b> ",
+            bio: "a\nb",
+        },
+        rows: vec![vec![1, 2], vec![3]],
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "Ann&lt;b&gt;1ANN&lt;B&gt;:a<br/>b;\
+         Ann&lt;b&gt;2ANN&lt;B&gt;:a<br/>b;\
+         Ann&lt;b&gt;3ANN&lt;B&gt;:a<br/>b;"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% set greeting = \"Hello\" %}\
+              {% set line -%}\n  {{ greeting }}, <b>{{ name }}</b>!\n{%- endset %}\
+              {% for i in 0..2 %}{{ i }}: {{ line }}|{% endfor %}",
+    ext = "html"
+)]
+struct SetTemplate<'a> {
+    name: &'a str,
+}
+
+#[test]
+fn test_set() {
+    let t = SetTemplate { name: "<Ann>" };
+    assert_eq!(
+        t.render().unwrap(),
+        "0: Hello, <b>&lt;Ann&gt;</b>!|1: Hello, <b>&lt;Ann&gt;</b>!|"
+    );
+}
+
+#[derive(Template)]
+#[template(
+    source = "{% let x %}{% if flag %}{% set x %}yes{% endset %}\
+              {% else %}{% set x %}no{% endset %}{% endif %}{{ x }}",
+    ext = "txt"
+)]
+struct SetDeclTemplate {
+    flag: bool,
+}
+
+#[test]
+fn test_set_decl() {
+    assert_eq!(SetDeclTemplate { flag: true }.render().unwrap(), "yes");
+    assert_eq!(SetDeclTemplate { flag: false }.render().unwrap(), "no");
+}
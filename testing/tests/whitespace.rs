@@ -39,3 +39,83 @@ fn test_extra_whitespace() {
     template.nested_1.nested_2.hash.insert("key", "value");
     assert_eq!(template.render().unwrap(), "\n0\n0\n0\n0\n\n\n\n0\n0\n0\n0\n0\n\na0\na1\nvalue\n\n\n\n\n\n[\n  \"a0\",\n  \"a1\",\n  \"a2\",\n  \"a3\"\n]\n[\n  \"a0\",\n  \"a1\",\n  \"a2\",\n  \"a3\"\n][\n  \"a0\",\n  \"a1\",\n  \"a2\",\n  \"a3\"\n]\n[\n  \"a1\"\n][\n  \"a1\"\n]\n[\n  \"a1\",\n  \"a2\"\n][\n  \"a1\",\n  \"a2\"\n]\n[\n  \"a1\"\n][\n  \"a1\"\n]1-1-1\n3333 3\n2222 2\n0000 0\n3333 3\n\ntruefalse\nfalsefalsefalse\n\n\n\n\n\n\n\n\n\n\n\n\n\n");
 }
+
+macro_rules! whitespace_template {
+    ($name:ident, $mode:literal) => {
+        #[derive(askama::Template)]
+        #[template(path = "whitespace-modes.html", whitespace = $mode)]
+        struct $name<'a> {
+            items: &'a [&'a str],
+        }
+    };
+}
+
+whitespace_template!(SuppressTemplate, "suppress");
+whitespace_template!(PreserveTemplate, "preserve");
+whitespace_template!(MinimizeTemplate, "minimize");
+
+#[test]
+fn test_whitespace_suppress() {
+    let t = SuppressTemplate { items: &["a", "b"] };
+    assert_eq!(
+        t.render().unwrap(),
+        "<ul>\n  <li>a</li><li>b</li>\n</ul>\n<pre>\n  \n    a\n    b\n</pre>\n  {{ kept }}  "
+    );
+}
+
+#[test]
+fn test_whitespace_preserve() {
+    let t = PreserveTemplate { items: &["a", "b"] };
+    assert_eq!(
+        t.render().unwrap(),
+        "<ul>\n  \n    <li>a</li>\n  \n    <li>b</li>\n  \n</ul>\n<pre>\n  \n    a\n  \n    b\n  \n</pre>\n  {{ kept }}  "
+    );
+}
+
+#[test]
+fn test_whitespace_minimize() {
+    let t = MinimizeTemplate { items: &["a", "b"] };
+    assert_eq!(
+        t.render().unwrap(),
+        "<ul>\n<li>a</li><li>b</li>\n</ul>\n<pre>\n  \n    a\n    b\n</pre>\n  {{ kept }}  "
+    );
+}
+
+#[derive(askama::Template)]
+#[template(
+    source = "<ul>\n  {% for item in items %}\n    {% if item.is_empty() %}\n      <li>-</li>\n    {% else %}\n      <li>{{ item }}</li>\n    {% endif %}\n  {% endfor %}\n</ul>\n",
+    ext = "html",
+    trim_blocks = true,
+    lstrip_blocks = true
+)]
+struct TrimBlocksTemplate<'a> {
+    items: &'a [&'a str],
+}
+
+#[test]
+fn test_trim_blocks() {
+    let t = TrimBlocksTemplate {
+        items: &["a", "", "b"],
+    };
+    assert_eq!(
+        t.render().unwrap(),
+        "<ul>\n      <li>a</li>\n      <li>-</li>\n      <li>b</li>\n</ul>"
+    );
+}
+
+#[derive(askama::Template)]
+#[template(
+    source = "a\n  {% if true -%}\n  b {{ c }}\n{%- endif %}\n{% if true %}\nd\n{% endif %}\n",
+    ext = "txt",
+    trim_blocks = true,
+    lstrip_blocks = true
+)]
+struct TrimBlocksMarkersTemplate {
+    c: u32,
+}
+
+#[test]
+fn test_trim_blocks_markers() {
+    let t = TrimBlocksMarkersTemplate { c: 1 };
+    assert_eq!(t.render().unwrap(), "a\nb 1d\n");
+}